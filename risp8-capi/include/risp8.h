@@ -0,0 +1,169 @@
+/* risp8 C API. Generated by cbindgen from risp8-capi/src/lib.rs, do not edit. */
+
+#ifndef RISP8_H
+#define RISP8_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Why [risp8_run_frame] stopped before the end of the frame.
+typedef enum Risp8StopReason {
+  // The frame has been executed entirely.
+  RISP8_STOP_REASON_NONE,
+  // The program executed the SUPER-CHIP exit instruction.
+  RISP8_STOP_REASON_EXITED,
+  // The program faulted, the core is paused on the faulting instruction.
+  RISP8_STOP_REASON_TRAP,
+  // The state diverged from the movie being played.
+  RISP8_STOP_REASON_MOVIE_DESYNC,
+  // Execution reached a breakpoint, the instruction at the breakpoint has not been executed yet.
+  RISP8_STOP_REASON_BREAKPOINT,
+  // The last executed instruction accessed a watched address.
+  RISP8_STOP_REASON_WATCHPOINT,
+} Risp8StopReason;
+
+// The execution methods, see `risp8::ExecutionMethod`. Passed to [risp8_set_execution_method] as an integer.
+typedef enum Risp8ExecutionMethod {
+  RISP8_EXECUTION_METHOD_INTERPRETER,
+  RISP8_EXECUTION_METHOD_CACHED_INTERPRETER,
+  RISP8_EXECUTION_METHOD_CACHED_INTERPRETER2,
+  RISP8_EXECUTION_METHOD_CACHED_INTERPRETER3,
+  RISP8_EXECUTION_METHOD_JIT,
+} Risp8ExecutionMethod;
+
+// Opaque handle to a Chip8 core.
+typedef struct Risp8 Risp8;
+
+// A buffer allocated by the library, released with [risp8_buffer_free].
+typedef struct Risp8Buffer {
+  // The data, NULL if the buffer is empty.
+  uint8_t *data;
+  // The length of the data in bytes.
+  size_t len;
+} Risp8Buffer;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Creates a new core running the given ROM, configured for the given platform.
+//
+// `platform` is the short name of the platform (`vip`, `chip48`, `schip`, `xochip`, `eti660` or `dream6800`), or NULL
+// for the default configuration. The ROM is copied. The core does not pace frames and its random number generator is
+// seeded with `seed`.
+//
+// Returns NULL if the platform is unknown or the ROM does not fit in memory.
+//
+// # Safety
+//
+// `rom` must point to `rom_len` readable bytes. `platform` must be NULL or a NUL-terminated string.
+struct Risp8 *risp8_new(const uint8_t *rom,
+                        size_t rom_len,
+                        const char *platform,
+                        uint64_t seed);
+
+// Destroys a core created with [risp8_new].
+//
+// # Safety
+//
+// `risp8` must be NULL or a core returned by [risp8_new] that has not been destroyed yet.
+void risp8_free(struct Risp8 *risp8);
+
+// Executes instructions until the next 60Hz timer tick.
+//
+// # Safety
+//
+// `risp8` must be NULL or a valid core.
+enum Risp8StopReason risp8_run_frame(struct Risp8 *risp8);
+
+// Sets a key (0 to 15) as pressed or released. Invalid keys are ignored.
+//
+// # Safety
+//
+// `risp8` must be NULL or a valid core.
+void risp8_set_key(struct Risp8 *risp8, uint8_t key, bool pressed);
+
+// Selects the method used to execute instructions, one of the values of [Risp8ExecutionMethod].
+//
+// Returns false if the method is unknown or if `risp8` is NULL, in which case the method is left unchanged.
+//
+// # Safety
+//
+// `risp8` must be NULL or a valid core.
+bool risp8_set_execution_method(struct Risp8 *risp8,
+                                uint32_t method);
+
+// Adds a breakpoint at `pc`. Returns false if it is already set or if `risp8` is NULL.
+//
+// [risp8_run_frame] stops before executing the instruction at a breakpoint, and executes it when called again.
+//
+// # Safety
+//
+// `risp8` must be NULL or a valid core.
+bool risp8_add_breakpoint(struct Risp8 *risp8,
+                          uint16_t pc);
+
+// Removes the breakpoint at `pc`. Returns false if it is not set or if `risp8` is NULL.
+//
+// # Safety
+//
+// `risp8` must be NULL or a valid core.
+bool risp8_remove_breakpoint(struct Risp8 *risp8, uint16_t pc);
+
+// Returns the width of the screen in the current resolution, or 0 if `risp8` is NULL.
+//
+// # Safety
+//
+// `risp8` must be NULL or a valid core.
+size_t risp8_screen_width(const struct Risp8 *risp8);
+
+// Returns the height of the screen in the current resolution, or 0 if `risp8` is NULL.
+//
+// # Safety
+//
+// `risp8` must be NULL or a valid core.
+size_t risp8_screen_height(const struct Risp8 *risp8);
+
+// Copies the pixels of the screen in the current resolution row by row, one byte per pixel.
+//
+// Each pixel holds one bit per XO-CHIP bitplane, so 0 is off and 1 is lit without bitplanes.
+// Returns the number of bytes written, or 0 if the buffer is smaller than width * height bytes.
+//
+// # Safety
+//
+// `risp8` must be NULL or a valid core. `buffer` must be NULL or point to `len` writable bytes.
+size_t risp8_screen(const struct Risp8 *risp8, uint8_t *buffer, size_t len);
+
+// Returns the current state serialized in the risp8 save state format.
+//
+// The buffer must be released with [risp8_buffer_free]. It is empty if `risp8` is NULL.
+//
+// # Safety
+//
+// `risp8` must be NULL or a valid core.
+struct Risp8Buffer risp8_save_state(const struct Risp8 *risp8);
+
+// Restores a state saved with [risp8_save_state]. The data is copied.
+//
+// Returns false if the save state is invalid, in which case the state is left unchanged.
+//
+// # Safety
+//
+// `risp8` must be NULL or a valid core. `data` must point to `len` readable bytes.
+bool risp8_load_state(struct Risp8 *risp8, const uint8_t *data, size_t len);
+
+// Releases a buffer returned by the library. Empty buffers are ignored.
+//
+// # Safety
+//
+// `buffer` must have been returned by the library and not released yet.
+void risp8_buffer_free(struct Risp8Buffer buffer);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* RISP8_H */
@@ -0,0 +1,301 @@
+//! C API of risp8.
+//!
+//! The header `include/risp8.h` is generated by cbindgen, and the tests check it is up to date. A core is created with [risp8_new] and must be
+//! destroyed with [risp8_free]. Buffers returned by the library must be released with [risp8_buffer_free], and
+//! buffers given to the library are only borrowed for the duration of the call.
+//!
+//! Every function accepts a NULL core and does nothing in that case.
+
+use risp8::{Chip8, Chip8Builder, ExecutionMethod, Platform, StopReason};
+
+use std::ffi::{c_char, CStr};
+use std::ptr::{self, null_mut};
+use std::slice;
+
+/// Opaque handle to a Chip8 core.
+pub struct Risp8 {
+    core: Chip8,
+}
+
+/// The execution methods, see `risp8::ExecutionMethod`. Passed to [risp8_set_execution_method] as an integer.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Risp8ExecutionMethod {
+    Interpreter,
+    CachedInterpreter,
+    CachedInterpreter2,
+    CachedInterpreter3,
+    Jit,
+}
+
+impl Risp8ExecutionMethod {
+    const ALL: [Self; 5] = [
+        Self::Interpreter,
+        Self::CachedInterpreter,
+        Self::CachedInterpreter2,
+        Self::CachedInterpreter3,
+        Self::Jit,
+    ];
+
+    /// Returns the execution method with the given value, or None if the value is unknown.
+    fn from_u32(method: u32) -> Option<Self> {
+        Self::ALL.into_iter().find(|&m| m as u32 == method)
+    }
+}
+
+impl From<Risp8ExecutionMethod> for ExecutionMethod {
+    fn from(method: Risp8ExecutionMethod) -> Self {
+        match method {
+            Risp8ExecutionMethod::Interpreter => ExecutionMethod::Interpreter,
+            Risp8ExecutionMethod::CachedInterpreter => ExecutionMethod::CachedInterpreter,
+            Risp8ExecutionMethod::CachedInterpreter2 => ExecutionMethod::CachedInterpreter2,
+            Risp8ExecutionMethod::CachedInterpreter3 => ExecutionMethod::CachedInterpreter3,
+            Risp8ExecutionMethod::Jit => ExecutionMethod::Jit,
+        }
+    }
+}
+
+/// Why [risp8_run_frame] stopped before the end of the frame.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Risp8StopReason {
+    /// The frame has been executed entirely.
+    None,
+    /// The program executed the SUPER-CHIP exit instruction.
+    Exited,
+    /// The program faulted, the core is paused on the faulting instruction.
+    Trap,
+    /// The state diverged from the movie being played.
+    MovieDesync,
+    /// Execution reached a breakpoint, the instruction at the breakpoint has not been executed yet.
+    Breakpoint,
+    /// The last executed instruction accessed a watched address.
+    Watchpoint,
+}
+
+impl From<Option<StopReason>> for Risp8StopReason {
+    fn from(reason: Option<StopReason>) -> Self {
+        match reason {
+            None => Risp8StopReason::None,
+            Some(StopReason::Exited) => Risp8StopReason::Exited,
+            Some(StopReason::Trap(_)) => Risp8StopReason::Trap,
+            Some(StopReason::MovieDesync { .. }) => Risp8StopReason::MovieDesync,
+            Some(StopReason::Breakpoint(_)) => Risp8StopReason::Breakpoint,
+            Some(StopReason::Watchpoint(_)) => Risp8StopReason::Watchpoint,
+        }
+    }
+}
+
+/// A buffer allocated by the library, released with [risp8_buffer_free].
+#[repr(C)]
+pub struct Risp8Buffer {
+    /// The data, NULL if the buffer is empty.
+    pub data: *mut u8,
+    /// The length of the data in bytes.
+    pub len: usize,
+}
+
+impl Risp8Buffer {
+    const EMPTY: Self = Self { data: null_mut(), len: 0 };
+}
+
+/// Creates a new core running the given ROM, configured for the given platform.
+///
+/// `platform` is the short name of the platform (`vip`, `chip48`, `schip`, `xochip`, `eti660` or `dream6800`), or NULL
+/// for the default configuration. The ROM is copied. The core does not pace frames and its random number generator is
+/// seeded with `seed`.
+///
+/// Returns NULL if the platform is unknown or the ROM does not fit in memory.
+///
+/// # Safety
+///
+/// `rom` must point to `rom_len` readable bytes. `platform` must be NULL or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn risp8_new(rom: *const u8, rom_len: usize, platform: *const c_char, seed: u64) -> *mut Risp8 {
+    if rom.is_null() {
+        return null_mut();
+    }
+
+    let mut builder = Chip8Builder::new(slice::from_raw_parts(rom, rom_len)).pacing(false).seed(seed);
+    if !platform.is_null() {
+        match CStr::from_ptr(platform).to_str().ok().and_then(Platform::from_name) {
+            Some(platform) => builder = builder.platform(platform),
+            None => return null_mut(),
+        }
+    }
+
+    match builder.build_sync() {
+        Ok(core) => Box::into_raw(Box::new(Risp8 { core })),
+        Err(_) => null_mut(),
+    }
+}
+
+/// Destroys a core created with [risp8_new].
+///
+/// # Safety
+///
+/// `risp8` must be NULL or a core returned by [risp8_new] that has not been destroyed yet.
+#[no_mangle]
+pub unsafe extern "C" fn risp8_free(risp8: *mut Risp8) {
+    if !risp8.is_null() {
+        drop(Box::from_raw(risp8));
+    }
+}
+
+/// Executes instructions until the next 60Hz timer tick.
+///
+/// # Safety
+///
+/// `risp8` must be NULL or a valid core.
+#[no_mangle]
+pub unsafe extern "C" fn risp8_run_frame(risp8: *mut Risp8) -> Risp8StopReason {
+    match risp8.as_mut() {
+        Some(risp8) => risp8.core.run_frame().into(),
+        None => Risp8StopReason::None,
+    }
+}
+
+/// Sets a key (0 to 15) as pressed or released. Invalid keys are ignored.
+///
+/// # Safety
+///
+/// `risp8` must be NULL or a valid core.
+#[no_mangle]
+pub unsafe extern "C" fn risp8_set_key(risp8: *mut Risp8, key: u8, pressed: bool) {
+    if let Some(risp8) = risp8.as_mut() {
+        risp8.core.set_key(key as usize, pressed);
+    }
+}
+
+/// Selects the method used to execute instructions, one of the values of [Risp8ExecutionMethod].
+///
+/// Returns false if the method is unknown or if `risp8` is NULL, in which case the method is left unchanged.
+///
+/// # Safety
+///
+/// `risp8` must be NULL or a valid core.
+#[no_mangle]
+pub unsafe extern "C" fn risp8_set_execution_method(risp8: *mut Risp8, method: u32) -> bool {
+    match (risp8.as_mut(), Risp8ExecutionMethod::from_u32(method)) {
+        (Some(risp8), Some(method)) => {
+            risp8.core.set_execution_method(method.into());
+            true
+        },
+        _ => false,
+    }
+}
+
+/// Adds a breakpoint at `pc`. Returns false if it is already set or if `risp8` is NULL.
+///
+/// [risp8_run_frame] stops before executing the instruction at a breakpoint, and executes it when called again.
+///
+/// # Safety
+///
+/// `risp8` must be NULL or a valid core.
+#[no_mangle]
+pub unsafe extern "C" fn risp8_add_breakpoint(risp8: *mut Risp8, pc: u16) -> bool {
+    risp8.as_mut().is_some_and(|risp8| risp8.core.add_breakpoint(pc))
+}
+
+/// Removes the breakpoint at `pc`. Returns false if it is not set or if `risp8` is NULL.
+///
+/// # Safety
+///
+/// `risp8` must be NULL or a valid core.
+#[no_mangle]
+pub unsafe extern "C" fn risp8_remove_breakpoint(risp8: *mut Risp8, pc: u16) -> bool {
+    risp8.as_mut().is_some_and(|risp8| risp8.core.remove_breakpoint(pc))
+}
+
+/// Returns the width of the screen in the current resolution, or 0 if `risp8` is NULL.
+///
+/// # Safety
+///
+/// `risp8` must be NULL or a valid core.
+#[no_mangle]
+pub unsafe extern "C" fn risp8_screen_width(risp8: *const Risp8) -> usize {
+    risp8.as_ref().map_or(0, |risp8| risp8.core.screen().width())
+}
+
+/// Returns the height of the screen in the current resolution, or 0 if `risp8` is NULL.
+///
+/// # Safety
+///
+/// `risp8` must be NULL or a valid core.
+#[no_mangle]
+pub unsafe extern "C" fn risp8_screen_height(risp8: *const Risp8) -> usize {
+    risp8.as_ref().map_or(0, |risp8| risp8.core.screen().height())
+}
+
+/// Copies the pixels of the screen in the current resolution row by row, one byte per pixel.
+///
+/// Each pixel holds one bit per XO-CHIP bitplane, so 0 is off and 1 is lit without bitplanes.
+/// Returns the number of bytes written, or 0 if the buffer is smaller than width * height bytes.
+///
+/// # Safety
+///
+/// `risp8` must be NULL or a valid core. `buffer` must be NULL or point to `len` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn risp8_screen(risp8: *const Risp8, buffer: *mut u8, len: usize) -> usize {
+    let Some(risp8) = risp8.as_ref() else {
+        return 0;
+    };
+
+    let screen = risp8.core.screen();
+    let size = screen.width() * screen.height();
+    if buffer.is_null() || len < size {
+        return 0;
+    }
+
+    let buffer = slice::from_raw_parts_mut(buffer, size);
+    for (dst, row) in buffer.chunks_exact_mut(screen.width()).zip(screen.rows()) {
+        dst.copy_from_slice(row);
+    }
+
+    size
+}
+
+/// Returns the current state serialized in the risp8 save state format.
+///
+/// The buffer must be released with [risp8_buffer_free]. It is empty if `risp8` is NULL.
+///
+/// # Safety
+///
+/// `risp8` must be NULL or a valid core.
+#[no_mangle]
+pub unsafe extern "C" fn risp8_save_state(risp8: *const Risp8) -> Risp8Buffer {
+    let Some(risp8) = risp8.as_ref() else {
+        return Risp8Buffer::EMPTY;
+    };
+
+    let data = risp8.core.save_state().into_boxed_slice();
+    let len = data.len();
+    Risp8Buffer { data: Box::into_raw(data).cast(), len }
+}
+
+/// Restores a state saved with [risp8_save_state]. The data is copied.
+///
+/// Returns false if the save state is invalid, in which case the state is left unchanged.
+///
+/// # Safety
+///
+/// `risp8` must be NULL or a valid core. `data` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn risp8_load_state(risp8: *mut Risp8, data: *const u8, len: usize) -> bool {
+    match risp8.as_mut() {
+        Some(risp8) if !data.is_null() => risp8.core.load_state(slice::from_raw_parts(data, len)).is_ok(),
+        _ => false,
+    }
+}
+
+/// Releases a buffer returned by the library. Empty buffers are ignored.
+///
+/// # Safety
+///
+/// `buffer` must have been returned by the library and not released yet.
+#[no_mangle]
+pub unsafe extern "C" fn risp8_buffer_free(buffer: Risp8Buffer) {
+    if !buffer.data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(buffer.data, buffer.len)));
+    }
+}
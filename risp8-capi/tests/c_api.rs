@@ -0,0 +1,30 @@
+//! Builds the C test program against the library and runs it.
+
+#![cfg(unix)]
+
+use std::env;
+use std::path::PathBuf;
+use std::process::Command;
+
+#[test]
+fn c_test_program() {
+    let crate_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    // Integration tests are built in target/<profile>/deps, and the library in target/<profile>.
+    let lib_dir = env::current_exe().unwrap().parent().unwrap().parent().unwrap().to_path_buf();
+    let program = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("risp8_c_test");
+
+    let status = Command::new(env::var("CC").unwrap_or_else(|_| "cc".to_string()))
+        .args(["-Wall", "-Wextra", "-Werror"])
+        .arg(crate_dir.join("tests/test.c"))
+        .arg("-I").arg(crate_dir.join("include"))
+        .arg("-L").arg(&lib_dir)
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .arg("-lrisp8_capi")
+        .arg("-o").arg(&program)
+        .status()
+        .expect("Failed to run the C compiler");
+    assert!(status.success(), "Failed to compile the C test program");
+
+    let status = Command::new(&program).status().expect("Failed to run the C test program");
+    assert!(status.success(), "The C test program failed");
+}
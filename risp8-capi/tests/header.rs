@@ -0,0 +1,22 @@
+//! Checks the committed C header matches the one generated from the sources.
+//!
+//! Regenerate it with `cbindgen --config cbindgen.toml --output include/risp8.h` in the crate directory.
+
+use std::fs;
+use std::path::PathBuf;
+
+#[test]
+fn header_up_to_date() {
+    let crate_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+
+    let mut generated = Vec::new();
+    cbindgen::Builder::new()
+        .with_config(cbindgen::Config::from_file(crate_dir.join("cbindgen.toml")).unwrap())
+        .with_crate(&crate_dir)
+        .generate()
+        .expect("Failed to generate the C header")
+        .write(&mut generated);
+
+    let committed = fs::read(crate_dir.join("include/risp8.h")).unwrap();
+    assert!(String::from_utf8(generated).unwrap() == String::from_utf8(committed).unwrap(), "include/risp8.h is outdated");
+}
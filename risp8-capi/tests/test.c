@@ -0,0 +1,166 @@
+/* Exercises the risp8 C API. Returns 0 on success. */
+
+#include "risp8.h"
+
+#include <stdio.h>
+#include <string.h>
+
+#define CHECK(cond) do { \
+    if (!(cond)) { \
+        fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #cond); \
+        return 1; \
+    } \
+} while (0)
+
+/* Draws the digit 5 at the top-left corner then increments V0 forever. */
+static const uint8_t ROM[] = {
+    0x60, 0x05, /* LD V0, 5 */
+    0xF0, 0x29, /* LD F, V0 */
+    0x61, 0x00, /* LD V1, 0 */
+    0xD1, 0x15, /* DRW V1, V1, 5 */
+    0x70, 0x01, /* ADD V0, 1 */
+    0x12, 0x08, /* JP 0x208 */
+};
+
+static const enum Risp8ExecutionMethod METHODS[] = {
+    RISP8_EXECUTION_METHOD_INTERPRETER,
+    RISP8_EXECUTION_METHOD_CACHED_INTERPRETER,
+    RISP8_EXECUTION_METHOD_CACHED_INTERPRETER2,
+    RISP8_EXECUTION_METHOD_CACHED_INTERPRETER3,
+    RISP8_EXECUTION_METHOD_JIT,
+};
+
+static int test_screen(void) {
+    struct Risp8 *risp8 = risp8_new(ROM, sizeof(ROM), NULL, 1);
+    CHECK(risp8 != NULL);
+    CHECK(risp8_run_frame(risp8) == RISP8_STOP_REASON_NONE);
+
+    CHECK(risp8_screen_width(risp8) == 64);
+    CHECK(risp8_screen_height(risp8) == 32);
+
+    uint8_t screen[64 * 32];
+    CHECK(risp8_screen(risp8, screen, sizeof(screen) - 1) == 0);
+    CHECK(risp8_screen(risp8, screen, sizeof(screen)) == sizeof(screen));
+    /* Top row of the digit 5 is 0xF0. */
+    CHECK(screen[0] == 1 && screen[3] == 1 && screen[4] == 0);
+    /* Second row is 0x80. */
+    CHECK(screen[64] == 1 && screen[65] == 0);
+
+    risp8_set_key(risp8, 5, true);
+    risp8_set_key(risp8, 200, true);
+    risp8_free(risp8);
+    return 0;
+}
+
+static int test_save_state(void) {
+    struct Risp8 *risp8 = risp8_new(ROM, sizeof(ROM), "schip", 1);
+    CHECK(risp8 != NULL);
+    risp8_run_frame(risp8);
+
+    struct Risp8Buffer saved = risp8_save_state(risp8);
+    CHECK(saved.data != NULL && saved.len > 0);
+
+    risp8_run_frame(risp8);
+    risp8_run_frame(risp8);
+    struct Risp8Buffer later = risp8_save_state(risp8);
+    CHECK(later.len != saved.len || memcmp(later.data, saved.data, saved.len) != 0);
+
+    CHECK(risp8_load_state(risp8, saved.data, saved.len));
+    struct Risp8Buffer restored = risp8_save_state(risp8);
+    CHECK(restored.len == saved.len && memcmp(restored.data, saved.data, saved.len) == 0);
+
+    /* Invalid save states are rejected. */
+    CHECK(!risp8_load_state(risp8, saved.data, saved.len / 2));
+    CHECK(!risp8_load_state(risp8, NULL, 0));
+
+    risp8_buffer_free(saved);
+    risp8_buffer_free(later);
+    risp8_buffer_free(restored);
+    risp8_free(risp8);
+    return 0;
+}
+
+static int test_execution_methods(void) {
+    struct Risp8Buffer expected = { NULL, 0 };
+
+    for (size_t i = 0; i < sizeof(METHODS) / sizeof(METHODS[0]); i++) {
+        struct Risp8 *risp8 = risp8_new(ROM, sizeof(ROM), NULL, 1);
+        CHECK(risp8 != NULL);
+        CHECK(risp8_set_execution_method(risp8, METHODS[i]));
+        for (int frame = 0; frame < 10; frame++) {
+            CHECK(risp8_run_frame(risp8) == RISP8_STOP_REASON_NONE);
+        }
+
+        struct Risp8Buffer state = risp8_save_state(risp8);
+        if (expected.data == NULL) {
+            expected = state;
+        } else {
+            /* Every execution method produces the same state. */
+            CHECK(state.len == expected.len && memcmp(state.data, expected.data, state.len) == 0);
+            risp8_buffer_free(state);
+        }
+        risp8_free(risp8);
+    }
+
+    risp8_buffer_free(expected);
+    return 0;
+}
+
+static int test_breakpoints(void) {
+    for (size_t i = 0; i < sizeof(METHODS) / sizeof(METHODS[0]); i++) {
+        struct Risp8 *risp8 = risp8_new(ROM, sizeof(ROM), NULL, 1);
+        CHECK(risp8 != NULL);
+        CHECK(risp8_set_execution_method(risp8, METHODS[i]));
+        risp8_run_frame(risp8);
+
+        /* JP 0x208 is inside the block of the loop. */
+        CHECK(risp8_add_breakpoint(risp8, 0x20A));
+        CHECK(!risp8_add_breakpoint(risp8, 0x20A));
+        CHECK(risp8_run_frame(risp8) == RISP8_STOP_REASON_BREAKPOINT);
+        /* Resuming executes the instruction at the breakpoint and stops on the next iteration. */
+        CHECK(risp8_run_frame(risp8) == RISP8_STOP_REASON_BREAKPOINT);
+
+        CHECK(risp8_remove_breakpoint(risp8, 0x20A));
+        CHECK(!risp8_remove_breakpoint(risp8, 0x20A));
+        CHECK(risp8_run_frame(risp8) == RISP8_STOP_REASON_NONE);
+        risp8_free(risp8);
+    }
+
+    return 0;
+}
+
+static int test_errors(void) {
+    CHECK(risp8_new(NULL, 0, NULL, 0) == NULL);
+    CHECK(risp8_new(ROM, sizeof(ROM), "unknown", 0) == NULL);
+
+    /* NULL cores and buffers are ignored. */
+    CHECK(risp8_run_frame(NULL) == RISP8_STOP_REASON_NONE);
+    CHECK(risp8_screen_width(NULL) == 0);
+    struct Risp8Buffer empty = risp8_save_state(NULL);
+    CHECK(empty.data == NULL && empty.len == 0);
+    risp8_buffer_free(empty);
+    risp8_free(NULL);
+    CHECK(!risp8_set_execution_method(NULL, RISP8_EXECUTION_METHOD_INTERPRETER));
+
+    /* The exit instruction stops the frame. */
+    static const uint8_t EXIT[] = { 0x00, 0xFD };
+    struct Risp8 *risp8 = risp8_new(EXIT, sizeof(EXIT), NULL, 0);
+    CHECK(risp8 != NULL);
+    CHECK(risp8_run_frame(risp8) == RISP8_STOP_REASON_EXITED);
+
+    /* Unknown execution methods are rejected, as plain integers from other languages can hold any value. */
+    CHECK(!risp8_set_execution_method(risp8, RISP8_EXECUTION_METHOD_JIT + 1));
+    CHECK(!risp8_set_execution_method(risp8, UINT32_MAX));
+    risp8_free(risp8);
+
+    return 0;
+}
+
+int main(void) {
+    if (test_screen() || test_save_state() || test_execution_methods() || test_breakpoints() || test_errors()) {
+        return 1;
+    }
+
+    printf("risp8 C API test passed\n");
+    return 0;
+}
@@ -0,0 +1,629 @@
+//! Debug Adapter Protocol server for risp8.
+//!
+//! [DapServer] launches a ROM in a core running with [Chip8::run] on its own thread, and drives it through its channels
+//! for the editor connected to the server.
+//!
+//! The `launch` request takes the path of the ROM in `program`, and optionally the short name of a platform in
+//! `platform`, the path of a symbol file in `symbols` and `stopOnEntry`. Each line of a symbol file holds a name and its
+//! address, such as `main 0x200`. Addresses, both in symbol files and in requests, are hexadecimal with an optional `0x`
+//! prefix.
+//!
+//! Breakpoints are set by address with instruction breakpoints, or by symbol or address with function breakpoints.
+//! Both support conditions (see [Expression]) and hit counts. Source breakpoints are not supported. The variables view
+//! shows the registers and the stack, and the memory view reads and writes memory. The `evaluate` request and the new
+//! values of variables are evaluated as expressions.
+
+mod protocol;
+mod symbols;
+
+use symbols::Symbols;
+
+use risp8::{Breakpoint, Chip8, Expression, Platform, Receiver, Risp8Answer, Risp8Command, Sender, State};
+
+use serde_json::{json, Value};
+
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, Write};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// The only thread of the program.
+const THREAD_ID: u64 = 1;
+/// The variables reference of the registers.
+const REGISTERS: u64 = 1;
+/// The variables reference of the stack.
+const STACK: u64 = 2;
+
+/// The names of the registers in the variables view, after V0 to VF.
+const REGISTER_NAMES: [&str; 5] = ["I", "PC", "SP", "DT", "ST"];
+
+/// How long to wait for requests before checking the answers of the core.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The core launched by the `launch` request.
+struct Core {
+    commands: Sender<Risp8Command>,
+    answers: Receiver<Risp8Answer>,
+    thread: JoinHandle<()>,
+}
+
+/// A Debug Adapter Protocol server for one editor session.
+pub struct DapServer<W: Write> {
+    output: W,
+    /// The sequence number of the next message sent.
+    seq: u64,
+    core: Option<Core>,
+    symbols: Symbols,
+    /// The breakpoints set by instruction breakpoints, by address.
+    instruction_breakpoints: BTreeMap<u16, Breakpoint>,
+    /// The breakpoints set by function breakpoints, by address.
+    function_breakpoints: BTreeMap<u16, Breakpoint>,
+    stop_on_entry: bool,
+    /// True while the core executes instructions.
+    running: bool,
+    /// The stop answers received while waiting for another answer, handled after the request.
+    stop_answers: Vec<Risp8Answer>,
+    /// The events sent after the response to the current request.
+    events: Vec<Value>,
+}
+
+impl<W: Write> DapServer<W> {
+    /// Creates a server sending its messages to `output`.
+    pub fn new(output: W) -> Self {
+        Self {
+            output,
+            seq: 1,
+            core: None,
+            symbols: Symbols::default(),
+            instruction_breakpoints: BTreeMap::new(),
+            function_breakpoints: BTreeMap::new(),
+            stop_on_entry: false,
+            running: false,
+            stop_answers: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Serves the requests read from `input` until the editor disconnects, then stops the core.
+    pub fn run(mut self, mut input: impl BufRead + Send + 'static) -> io::Result<()> {
+        let (requests_in, requests) = mpsc::channel();
+        thread::spawn(move || {
+            while let Ok(Some(request)) = protocol::read_message(&mut input) {
+                if requests_in.send(request).is_err() {
+                    break;
+                }
+            }
+        });
+
+        loop {
+            self.handle_answers();
+            self.send_events()?;
+
+            match requests.recv_timeout(POLL_INTERVAL) {
+                Ok(request) => {
+                    if !self.handle_request(&request)? {
+                        break;
+                    }
+                },
+                Err(RecvTimeoutError::Timeout) => (),
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+
+        if let Some(core) = self.core.take() {
+            let _ = core.commands.send(Risp8Command::Exit);
+            let _ = core.thread.join();
+        }
+        Ok(())
+    }
+
+    /// Answers a request. Returns false when the session ends.
+    fn handle_request(&mut self, request: &Value) -> io::Result<bool> {
+        let command = request["command"].as_str().unwrap_or_default();
+        let args = &request["arguments"];
+
+        let body = match command {
+            "initialize" => Ok(capabilities()),
+            "launch" => self.launch(args),
+            "setBreakpoints" => Ok(source_breakpoints(args)),
+            "setInstructionBreakpoints" => self.set_instruction_breakpoints(args),
+            "setFunctionBreakpoints" => self.set_function_breakpoints(args),
+            "configurationDone" => self.configuration_done(),
+            "threads" => Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "Chip-8" }] })),
+            "stackTrace" => self.stack_trace(),
+            "scopes" => Ok(scopes()),
+            "variables" => self.variables(args),
+            "setVariable" => self.set_variable(args),
+            "evaluate" => self.evaluate(args),
+            "readMemory" => self.read_memory(args),
+            "writeMemory" => self.write_memory(args),
+            "continue" => self.resume(),
+            "next" | "stepIn" => self.step(),
+            "pause" => self.pause(),
+            "disconnect" | "terminate" => Ok(Value::Null),
+            _ => Err(format!("Unsupported request {command}")),
+        };
+
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": command,
+            "success": body.is_ok(),
+        });
+        match body {
+            Ok(Value::Null) => (),
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = message.into(),
+        }
+        self.send(response)?;
+
+        self.handle_answers();
+        self.send_events()?;
+        Ok(!matches!(command, "disconnect" | "terminate"))
+    }
+
+    fn launch(&mut self, args: &Value) -> Result<Value, String> {
+        if self.core.is_some() {
+            return Err("A program is already launched".to_string());
+        }
+
+        let Some(program) = args["program"].as_str() else {
+            return Err("Missing program".to_string());
+        };
+        let chip8 = match args["platform"].as_str() {
+            Some(name) => {
+                let platform = Platform::from_name(name).ok_or_else(|| format!("Unknown platform {name}"))?;
+                Chip8::with_platform(program, platform)
+            },
+            None => Chip8::new(program),
+        };
+        let (mut chip8, commands, answers) = chip8.map_err(|e| e.to_string())?;
+
+        if let Some(path) = args["symbols"].as_str() {
+            let text = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {path}: {e}"))?;
+            self.symbols = Symbols::parse(&text).map_err(|line| format!("Invalid symbol at line {line} of {path}"))?;
+        }
+        self.stop_on_entry = args["stopOnEntry"].as_bool().unwrap_or(false);
+
+        let thread = thread::spawn(move || chip8.run());
+        self.core = Some(Core { commands, answers, thread });
+        self.update_breakpoints(&BTreeMap::new())?;
+
+        self.events.push(event("initialized", Value::Null));
+        Ok(Value::Null)
+    }
+
+    fn configuration_done(&mut self) -> Result<Value, String> {
+        if self.stop_on_entry {
+            self.events.push(stopped("entry", None));
+        } else {
+            self.command(Risp8Command::Play)?;
+            self.running = true;
+        }
+        Ok(Value::Null)
+    }
+
+    fn set_instruction_breakpoints(&mut self, args: &Value) -> Result<Value, String> {
+        let old = self.breakpoints();
+        let mut replies = Vec::new();
+        self.instruction_breakpoints.clear();
+
+        for breakpoint in args["breakpoints"].as_array().into_iter().flatten() {
+            let reference = breakpoint["instructionReference"].as_str().unwrap_or_default();
+            let offset = breakpoint["offset"].as_i64().unwrap_or(0);
+            let address = parse_address(reference)
+                .and_then(|address| address.checked_add(offset))
+                .and_then(|address| u16::try_from(address).ok())
+                .ok_or_else(|| format!("Invalid address {reference}"));
+            replies.push(self.add_breakpoint(address, breakpoint, false));
+        }
+
+        self.update_breakpoints(&old)?;
+        Ok(json!({ "breakpoints": replies }))
+    }
+
+    fn set_function_breakpoints(&mut self, args: &Value) -> Result<Value, String> {
+        let old = self.breakpoints();
+        let mut replies = Vec::new();
+        self.function_breakpoints.clear();
+
+        for breakpoint in args["breakpoints"].as_array().into_iter().flatten() {
+            let name = breakpoint["name"].as_str().unwrap_or_default();
+            let address = self.symbols.address(name)
+                .or_else(|| parse_address(name).and_then(|address| u16::try_from(address).ok()))
+                .ok_or_else(|| format!("Unknown symbol {name}"));
+            replies.push(self.add_breakpoint(address, breakpoint, true));
+        }
+
+        self.update_breakpoints(&old)?;
+        Ok(json!({ "breakpoints": replies }))
+    }
+
+    /// Adds an instruction or function breakpoint with the condition and hit count of `args`, and returns its reply.
+    fn add_breakpoint(&mut self, address: Result<u16, String>, args: &Value, function: bool) -> Value {
+        let breakpoint = address.and_then(|address| {
+            let mut breakpoint = Breakpoint::new();
+            if let Some(condition) = args["condition"].as_str().filter(|condition| !condition.trim().is_empty()) {
+                let condition = Expression::parse(condition).map_err(|e| format!("Invalid condition: {e}"))?;
+                breakpoint.condition = Some(condition);
+            }
+            if let Some(hits) = args["hitCondition"].as_str().filter(|hits| !hits.trim().is_empty()) {
+                let hits = hits.trim().parse::<u64>().map_err(|_| format!("Invalid hit count {hits}"))?;
+                breakpoint.ignore_count = hits.saturating_sub(1);
+            }
+            Ok((address, breakpoint))
+        });
+
+        match breakpoint {
+            Ok((address, breakpoint)) => {
+                if function {
+                    self.function_breakpoints.insert(address, breakpoint);
+                } else {
+                    self.instruction_breakpoints.insert(address, breakpoint);
+                }
+                json!({ "verified": true, "instructionReference": format_address(address) })
+            },
+            Err(message) => json!({ "verified": false, "message": message }),
+        }
+    }
+
+    /// Returns the breakpoints of both kinds. The function breakpoints replace the instruction breakpoints at the same
+    /// address.
+    fn breakpoints(&self) -> BTreeMap<u16, Breakpoint> {
+        let mut breakpoints = self.instruction_breakpoints.clone();
+        breakpoints.extend(self.function_breakpoints.clone());
+        breakpoints
+    }
+
+    /// Sets the breakpoints in the core, which had the `old` breakpoints.
+    fn update_breakpoints(&mut self, old: &BTreeMap<u16, Breakpoint>) -> Result<(), String> {
+        if self.core.is_none() {
+            return Ok(());
+        }
+
+        let new = self.breakpoints();
+        for &pc in old.keys().filter(|pc| !new.contains_key(pc)) {
+            self.command(Risp8Command::RemoveBreakpoint(pc))?;
+        }
+        for (pc, breakpoint) in new {
+            if old.get(&pc) != Some(&breakpoint) {
+                self.command(Risp8Command::SetBreakpoint(pc, breakpoint))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn stack_trace(&mut self) -> Result<Value, String> {
+        let state = self.state()?;
+        let return_addresses = state.stack()[..state.sp()].iter().rev();
+        let frames: Vec<Value> = [state.pc()].iter().chain(return_addresses).enumerate().map(|(id, &address)| {
+            json!({
+                "id": id,
+                "name": self.symbols.name(address),
+                "line": 0,
+                "column": 0,
+                "instructionPointerReference": format_address(address),
+            })
+        }).collect();
+
+        Ok(json!({ "stackFrames": frames, "totalFrames": frames.len() }))
+    }
+
+    fn variables(&mut self, args: &Value) -> Result<Value, String> {
+        let state = self.state()?;
+        let variables: Vec<Value> = match args["variablesReference"].as_u64() {
+            Some(REGISTERS) => (0..16 + REGISTER_NAMES.len()).map(|n| {
+                let name = register_name(n);
+                let mut variable = json!({ "name": name, "value": format_register(&state, n), "variablesReference": 0 });
+                if name == "I" || name == "PC" {
+                    variable["memoryReference"] = format_address(register(&state, n)).into();
+                }
+                variable
+            }).collect(),
+            Some(STACK) => state.stack()[..state.sp()].iter().enumerate().map(|(i, &address)| {
+                json!({
+                    "name": format!("[{i}]"),
+                    "value": format!("{} ({})", format_address(address), self.symbols.name(address)),
+                    "variablesReference": 0,
+                    "memoryReference": format_address(address),
+                })
+            }).collect(),
+            _ => return Err("Invalid variables reference".to_string()),
+        };
+
+        Ok(json!({ "variables": variables }))
+    }
+
+    fn set_variable(&mut self, args: &Value) -> Result<Value, String> {
+        let name = args["name"].as_str().unwrap_or_default();
+        let value = args["value"].as_str().unwrap_or_default();
+        let value = Expression::parse(value).map_err(|e| e.to_string())?;
+
+        let mut state = self.state()?;
+        let value = value.evaluate(&state);
+        let formatted = match args["variablesReference"].as_u64() {
+            Some(REGISTERS) => (0..16 + REGISTER_NAMES.len())
+                .find(|&n| register_name(n).eq_ignore_ascii_case(name))
+                .filter(|&n| set_register(&mut state, n, value))
+                .map(|n| format_register(&state, n)),
+            Some(STACK) => name.strip_prefix('[').and_then(|name| name.strip_suffix(']'))
+                .and_then(|index| index.parse::<usize>().ok())
+                .zip(u16::try_from(value).ok())
+                .filter(|&(index, address)| index < state.sp() && state.set_stack(index, address).is_ok())
+                .map(|(_, address)| format_address(address)),
+            _ => None,
+        };
+        let Some(formatted) = formatted else {
+            return Err(format!("Cannot set {name} to {value}"));
+        };
+
+        self.set_state(&state)?;
+        Ok(json!({ "value": formatted }))
+    }
+
+    fn evaluate(&mut self, args: &Value) -> Result<Value, String> {
+        let expression = args["expression"].as_str().unwrap_or_default();
+        let expression = Expression::parse(expression).map_err(|e| e.to_string())?;
+        let value = expression.evaluate(&*self.state()?);
+        Ok(json!({ "result": format!("{value} (0x{value:X})"), "variablesReference": 0 }))
+    }
+
+    fn read_memory(&mut self, args: &Value) -> Result<Value, String> {
+        let address = memory_address(args)?;
+        let count = args["count"].as_u64().unwrap_or(0) as usize;
+
+        let state = self.state()?;
+        let memory = state.memory();
+        let beg = address.min(memory.len());
+        let end = address.saturating_add(count).min(memory.len());
+        Ok(json!({
+            "address": format!("{address:#X}"),
+            "data": protocol::encode_base64(&memory[beg..end]),
+            "unreadableBytes": count - (end - beg),
+        }))
+    }
+
+    fn write_memory(&mut self, args: &Value) -> Result<Value, String> {
+        let address = memory_address(args)?;
+        let data = args["data"].as_str().and_then(protocol::decode_base64).ok_or("Invalid data")?;
+        if address.saturating_add(data.len()) > self.state()?.memory_size() {
+            return Err("Write goes past the end of memory".to_string());
+        }
+
+        let len = data.len();
+        self.command(Risp8Command::WriteMemory(address as u16, data))?;
+        Ok(json!({ "bytesWritten": len }))
+    }
+
+    fn resume(&mut self) -> Result<Value, String> {
+        self.command(Risp8Command::Play)?;
+        self.running = true;
+        Ok(json!({ "allThreadsContinued": true }))
+    }
+
+    /// Runs the execution method once, with the semantics of [Risp8Command::SingleStep].
+    fn step(&mut self) -> Result<Value, String> {
+        self.command(Risp8Command::SingleStep)?;
+        self.running = true;
+        // The state is sent after the step has been executed.
+        self.state()?;
+        self.handle_answers();
+        if self.running {
+            self.running = false;
+            self.events.push(stopped("step", None));
+        }
+        Ok(Value::Null)
+    }
+
+    fn pause(&mut self) -> Result<Value, String> {
+        self.command(Risp8Command::Pause)?;
+        // The core may have stopped by itself before being paused.
+        self.state()?;
+        self.handle_answers();
+        if self.running {
+            self.running = false;
+            self.events.push(stopped("pause", None));
+        }
+        Ok(Value::Null)
+    }
+
+    /// Turns the answers of the core into events.
+    fn handle_answers(&mut self) {
+        let mut answers = std::mem::take(&mut self.stop_answers);
+        if let Some(core) = &self.core {
+            answers.extend(std::iter::from_fn(|| core.answers.try_recv().ok().flatten()));
+        }
+
+        for answer in answers {
+            let reason = match answer {
+                Risp8Answer::BreakpointHit(_) => ("breakpoint", None),
+                Risp8Answer::WatchpointHit(hit) => {
+                    ("data breakpoint", Some(format!("{:?} of {:#X} at {:#X}", hit.kind, hit.address, hit.pc)))
+                },
+                Risp8Answer::Trap(trap) => ("exception", Some(trap.to_string())),
+                Risp8Answer::MovieDesync { cycle } => ("exception", Some(format!("Movie desync after {cycle} instructions"))),
+                Risp8Answer::Exited => {
+                    self.running = false;
+                    self.events.push(event("exited", json!({ "exitCode": 0 })));
+                    self.events.push(event("terminated", Value::Null));
+                    continue;
+                },
+                Risp8Answer::Log(message) => {
+                    self.events.push(event("output", json!({ "category": "console", "output": message + "\n" })));
+                    continue;
+                },
+                _ => continue,
+            };
+
+            // An instruction accessing several watched addresses stops the core once.
+            if self.running {
+                self.running = false;
+                self.events.push(stopped(reason.0, reason.1));
+            }
+        }
+    }
+
+    /// Returns a copy of the state of the core.
+    fn state(&mut self) -> Result<Box<State>, String> {
+        self.command(Risp8Command::GetState)?;
+        self.wait(|answer| match answer {
+            Risp8Answer::State(state) => Some(state),
+            _ => None,
+        })
+    }
+
+    /// Replaces the state of the core.
+    fn set_state(&mut self, state: &State) -> Result<(), String> {
+        self.command(Risp8Command::LoadState(state.save()))?;
+        self.wait(|answer| match answer {
+            Risp8Answer::StateLoaded(result) => Some(result.map_err(|e| e.to_string())),
+            _ => None,
+        })?
+    }
+
+    /// Returns the first answer accepted by `accept`, keeping the answers that become events.
+    fn wait<T>(&mut self, mut accept: impl FnMut(Risp8Answer) -> Option<T>) -> Result<T, String> {
+        let core = self.core.as_ref().ok_or_else(not_launched)?;
+        loop {
+            let answer = core.answers.recv().map_err(|_| stopped_core())?;
+            match answer {
+                Risp8Answer::BreakpointHit(_) | Risp8Answer::WatchpointHit(_) | Risp8Answer::Trap(_) |
+                Risp8Answer::MovieDesync { .. } | Risp8Answer::Exited | Risp8Answer::Log(_) => self.stop_answers.push(answer),
+                answer => if let Some(value) = accept(answer) {
+                    return Ok(value);
+                },
+            }
+        }
+    }
+
+    fn command(&self, command: Risp8Command) -> Result<(), String> {
+        let core = self.core.as_ref().ok_or_else(not_launched)?;
+        core.commands.send(command).map_err(|_| stopped_core())
+    }
+
+    fn send(&mut self, mut message: Value) -> io::Result<()> {
+        message["seq"] = self.seq.into();
+        self.seq += 1;
+        protocol::write_message(&mut self.output, &message)
+    }
+
+    fn send_events(&mut self) -> io::Result<()> {
+        for event in std::mem::take(&mut self.events) {
+            self.send(event)?;
+        }
+        Ok(())
+    }
+}
+
+fn capabilities() -> Value {
+    json!({
+        "supportsConfigurationDoneRequest": true,
+        "supportsFunctionBreakpoints": true,
+        "supportsInstructionBreakpoints": true,
+        "supportsConditionalBreakpoints": true,
+        "supportsHitConditionalBreakpoints": true,
+        "supportsSetVariable": true,
+        "supportsReadMemoryRequest": true,
+        "supportsWriteMemoryRequest": true,
+        "supportsTerminateRequest": true,
+    })
+}
+
+fn scopes() -> Value {
+    json!({
+        "scopes": [
+            { "name": "Registers", "presentationHint": "registers", "variablesReference": REGISTERS, "expensive": false },
+            { "name": "Stack", "variablesReference": STACK, "expensive": false },
+        ]
+    })
+}
+
+/// Rejects the source breakpoints, as ROMs have no line information.
+fn source_breakpoints(args: &Value) -> Value {
+    let count = args["breakpoints"].as_array().map_or(0, Vec::len);
+    let breakpoints = vec![json!({ "verified": false, "message": "Source breakpoints are not supported" }); count];
+    json!({ "breakpoints": breakpoints })
+}
+
+fn event(event: &str, body: Value) -> Value {
+    let mut event = json!({ "type": "event", "event": event });
+    if !body.is_null() {
+        event["body"] = body;
+    }
+    event
+}
+
+fn stopped(reason: &str, text: Option<String>) -> Value {
+    let mut body = json!({ "reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true });
+    if let Some(text) = text {
+        body["text"] = text.into();
+    }
+    event("stopped", body)
+}
+
+fn not_launched() -> String {
+    "No program has been launched".to_string()
+}
+
+fn stopped_core() -> String {
+    "The core has been stopped".to_string()
+}
+
+/// Parses an address in hexadecimal with an optional `0x` prefix.
+fn parse_address(address: &str) -> Option<i64> {
+    let address = address.trim();
+    let address = address.strip_prefix("0x").or_else(|| address.strip_prefix("0X")).unwrap_or(address);
+    i64::from_str_radix(address, 16).ok()
+}
+
+fn format_address(address: u16) -> String {
+    format!("0x{address:04X}")
+}
+
+/// Returns the address of the `memoryReference` and `offset` of a memory request.
+fn memory_address(args: &Value) -> Result<usize, String> {
+    let reference = args["memoryReference"].as_str().unwrap_or_default();
+    let offset = args["offset"].as_i64().unwrap_or(0);
+    parse_address(reference)
+        .and_then(|address| address.checked_add(offset))
+        .and_then(|address| usize::try_from(address).ok())
+        .ok_or_else(|| format!("Invalid memory reference {reference}"))
+}
+
+fn register_name(n: usize) -> String {
+    match n {
+        0..16 => format!("V{n:X}"),
+        _ => REGISTER_NAMES[n - 16].to_string(),
+    }
+}
+
+fn register(state: &State, n: usize) -> u16 {
+    match n {
+        0..16 => state.v()[n] as u16,
+        16 => state.i(),
+        17 => state.pc(),
+        18 => state.sp() as u16,
+        19 => state.delay() as u16,
+        _ => state.sound() as u16,
+    }
+}
+
+fn format_register(state: &State, n: usize) -> String {
+    match n {
+        16 | 17 => format_address(register(state, n)),
+        _ => format!("0x{:02X}", register(state, n)),
+    }
+}
+
+/// Sets register n. Returns false if the value does not fit in the register or is invalid.
+fn set_register(state: &mut State, n: usize, value: i64) -> bool {
+    match n {
+        0..16 => u8::try_from(value).is_ok_and(|value| state.set_v(n, value).is_ok()),
+        16 => u16::try_from(value).map(|value| state.set_i(value)).is_ok(),
+        17 => u16::try_from(value).is_ok_and(|value| state.set_pc(value).is_ok()),
+        18 => usize::try_from(value).is_ok_and(|value| state.set_sp(value).is_ok()),
+        19 => u8::try_from(value).map(|value| state.set_delay(value)).is_ok(),
+        _ => u8::try_from(value).map(|value| state.set_sound(value)).is_ok(),
+    }
+}
@@ -0,0 +1,43 @@
+use risp8_dap::DapServer;
+
+use std::io::{stdin, stdout, BufReader};
+use std::net::{Ipv4Addr, TcpListener};
+
+fn print_usage_and_exit(exec: &str) -> ! {
+    eprintln!("Usage: {exec} [-p port]");
+    eprintln!("Serves the Debug Adapter Protocol on stdio, or on the given localhost port.");
+    std::process::exit(1);
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let port = match args.as_slice() {
+        [_] => None,
+        [_, flag, port] if flag == "-p" => Some(port.parse::<u16>().unwrap_or_else(|_| print_usage_and_exit(&args[0]))),
+        _ => print_usage_and_exit(&args[0]),
+    };
+
+    let Some(port) = port else {
+        if let Err(e) = DapServer::new(stdout().lock()).run(BufReader::new(stdin())) {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+        return;
+    };
+
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port)).unwrap_or_else(|e| {
+        eprintln!("Failed to listen on port {port}: {e}");
+        std::process::exit(1);
+    });
+    eprintln!("Waiting for the editor on port {port}");
+
+    for stream in listener.incoming() {
+        let result = stream.and_then(|stream| {
+            let input = BufReader::new(stream.try_clone()?);
+            DapServer::new(stream).run(input)
+        });
+        if let Err(e) = result {
+            eprintln!("Debug session failed: {e}");
+        }
+    }
+}
@@ -0,0 +1,78 @@
+//! Framing of the Debug Adapter Protocol messages and the encoding of memory.
+
+use serde_json::Value;
+
+use std::io::{self, BufRead, ErrorKind, Write};
+
+/// Reads the next message. Returns None at the end of the input.
+pub(crate) fn read_message(input: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut content_length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("Content-Length") {
+                content_length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let Some(len) = content_length else {
+        return Err(io::Error::new(ErrorKind::InvalidData, "Message without Content-Length"));
+    };
+    let mut content = vec![0; len];
+    input.read_exact(&mut content)?;
+    serde_json::from_slice(&content).map(Some).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))
+}
+
+/// Writes a message.
+pub(crate) fn write_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let content = message.to_string();
+    // Written at once so sockets do not delay the content after the header.
+    let message = format!("Content-Length: {}\r\n\r\n{content}", content.len());
+    output.write_all(message.as_bytes())?;
+    output.flush()
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encodes memory in base64, as used by the memory requests.
+pub(crate) fn encode_base64(data: &[u8]) -> String {
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, &byte)| bits | (byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64[(bits >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Decodes base64 data. Returns None if it is invalid.
+pub(crate) fn decode_base64(encoded: &str) -> Option<Vec<u8>> {
+    let encoded = encoded.trim_end_matches('=').as_bytes();
+    let mut data = Vec::with_capacity(encoded.len() * 3 / 4);
+    for chunk in encoded.chunks(4) {
+        if chunk.len() == 1 {
+            return None;
+        }
+        let mut bits = 0u32;
+        for (i, &c) in chunk.iter().enumerate() {
+            let value = BASE64.iter().position(|&b| b == c)? as u32;
+            bits |= value << (18 - 6 * i);
+        }
+        data.extend_from_slice(&bits.to_be_bytes()[1..chunk.len()]);
+    }
+    Some(data)
+}
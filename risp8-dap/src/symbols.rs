@@ -0,0 +1,52 @@
+//! Symbol files naming the addresses of a ROM.
+//!
+//! Each line of a symbol file holds a name and its address, separated by spaces, `=` or `:`. Addresses are hexadecimal
+//! with an optional `0x` prefix. Empty lines and the lines starting with `#` or `;` are ignored.
+
+use crate::parse_address;
+
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Default)]
+pub(crate) struct Symbols {
+    addresses: HashMap<String, u16>,
+    names: BTreeMap<u16, String>,
+}
+
+impl Symbols {
+    /// Parses a symbol file. Returns the number of the first invalid line on error.
+    pub fn parse(text: &str) -> Result<Self, usize> {
+        let mut symbols = Self::default();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+
+            let mut fields = line.split(|c: char| c.is_whitespace() || c == '=' || c == ':').filter(|field| !field.is_empty());
+            let (Some(name), Some(address), None) = (fields.next(), fields.next(), fields.next()) else {
+                return Err(i + 1);
+            };
+            let address = parse_address(address).and_then(|address| u16::try_from(address).ok()).ok_or(i + 1)?;
+
+            symbols.addresses.insert(name.to_string(), address);
+            symbols.names.entry(address).or_insert_with(|| name.to_string());
+        }
+        Ok(symbols)
+    }
+
+    /// Returns the address of a symbol.
+    pub fn address(&self, name: &str) -> Option<u16> {
+        self.addresses.get(name).copied()
+    }
+
+    /// Returns the name of an address as `symbol+offset` from the closest symbol before it, or its hexadecimal value
+    /// if there is none.
+    pub fn name(&self, address: u16) -> String {
+        match self.names.range(..=address).next_back() {
+            Some((&beg, name)) if beg == address => name.clone(),
+            Some((&beg, name)) => format!("{name}+0x{:X}", address - beg),
+            None => format!("0x{address:04X}"),
+        }
+    }
+}
@@ -0,0 +1,196 @@
+//! Drives the server with a scripted Debug Adapter Protocol client.
+
+use risp8_dap::DapServer;
+
+use serde_json::{json, Value};
+
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+/// 6005 2208 1202 0000 7001 00EE: sets V0 to 5 then calls a subroutine incrementing it in a loop.
+const PROGRAM: [u8; 12] = [0x60, 0x05, 0x22, 0x08, 0x12, 0x02, 0x00, 0x00, 0x70, 0x01, 0x00, 0xEE];
+const SYMBOLS: &str = "# Symbols of the test program.\nstart 0x200\nloop = 202\nincrement: 0x208\n";
+
+struct Client {
+    input: BufReader<TcpStream>,
+    output: TcpStream,
+    seq: u64,
+    /// The events received while waiting for responses.
+    events: VecDeque<Value>,
+}
+
+impl Client {
+    /// Sends a request and returns its response.
+    fn request(&mut self, command: &str, arguments: Value) -> Value {
+        let request = json!({ "seq": self.seq, "type": "request", "command": command, "arguments": arguments }).to_string();
+        let message = format!("Content-Length: {}\r\n\r\n{request}", request.len());
+        self.output.write_all(message.as_bytes()).unwrap();
+        self.seq += 1;
+
+        loop {
+            let message = self.read();
+            if message["type"] == "event" {
+                self.events.push_back(message);
+            } else {
+                assert_eq!(message["request_seq"], self.seq - 1);
+                assert_eq!(message["command"], command);
+                return message;
+            }
+        }
+    }
+
+    /// Sends a request, checks it succeeded and returns the body of its response.
+    fn body(&mut self, command: &str, arguments: Value) -> Value {
+        let response = self.request(command, arguments);
+        assert_eq!(response["success"], true, "{command} failed: {response}");
+        response["body"].clone()
+    }
+
+    /// Returns the next event, which must be the given one.
+    fn event(&mut self, event: &str) -> Value {
+        let message = self.events.pop_front().unwrap_or_else(|| self.read());
+        assert_eq!(message["event"], event, "unexpected message {message}");
+        message["body"].clone()
+    }
+
+    fn read(&mut self) -> Value {
+        let mut len = 0;
+        loop {
+            let mut line = String::new();
+            self.input.read_line(&mut line).unwrap();
+            match line.trim_end().split_once(": ") {
+                Some(("Content-Length", value)) => len = value.parse().unwrap(),
+                _ => break,
+            }
+        }
+        let mut content = vec![0; len];
+        self.input.read_exact(&mut content).unwrap();
+        serde_json::from_slice(&content).unwrap()
+    }
+
+    fn register(&mut self, name: &str) -> Value {
+        let variables = self.body("variables", json!({ "variablesReference": 1 }));
+        let variable = variables["variables"].as_array().unwrap().iter().find(|v| v["name"] == name).unwrap();
+        variable["value"].clone()
+    }
+}
+
+#[test]
+fn scripted_session() {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
+    let rom = dir.join("risp8_dap_test.ch8");
+    let symbols = dir.join("risp8_dap_test.sym");
+    std::fs::write(&rom, PROGRAM).unwrap();
+    std::fs::write(&symbols, SYMBOLS).unwrap();
+
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let input = BufReader::new(stream.try_clone().unwrap());
+        DapServer::new(stream).run(input)
+    });
+
+    let output = TcpStream::connect((Ipv4Addr::LOCALHOST, port)).unwrap();
+    output.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+    let input = BufReader::new(output.try_clone().unwrap());
+    let mut client = Client { input, output, seq: 1, events: VecDeque::new() };
+
+    let capabilities = client.body("initialize", json!({ "adapterID": "risp8" }));
+    assert_eq!(capabilities["supportsInstructionBreakpoints"], true);
+    assert_eq!(client.request("launch", json!({ "program": "missing.ch8" }))["success"], false);
+    client.body("launch", json!({ "program": rom, "symbols": symbols, "stopOnEntry": true }));
+    client.event("initialized");
+
+    // Breakpoints by symbol and by address, with a condition.
+    let breakpoints = client.body("setFunctionBreakpoints", json!({ "breakpoints": [{ "name": "increment" }, { "name": "missing" }] }));
+    assert_eq!(breakpoints["breakpoints"][0]["verified"], true);
+    assert_eq!(breakpoints["breakpoints"][0]["instructionReference"], "0x0208");
+    assert_eq!(breakpoints["breakpoints"][1]["verified"], false);
+    let breakpoints = client.body("setInstructionBreakpoints", json!({
+        "breakpoints": [{ "instructionReference": "0x208", "offset": 2, "condition": "V0 == 7" }, { "instructionReference": "0x204", "condition": "V0 ==" }],
+    }));
+    assert_eq!(breakpoints["breakpoints"][0]["verified"], true);
+    assert_eq!(breakpoints["breakpoints"][1]["verified"], false);
+    let breakpoints = client.body("setInstructionBreakpoints", json!({
+        "breakpoints": [{ "instructionReference": "0x7FFFFFFFFFFFFFFF", "offset": 1 }, { "instructionReference": "208", "offset": 2, "condition": "V0 == 7" }],
+    }));
+    assert_eq!(breakpoints["breakpoints"][0]["verified"], false);
+    assert_eq!(breakpoints["breakpoints"][1]["verified"], true);
+    let breakpoints = client.body("setBreakpoints", json!({ "source": { "path": "game.8o" }, "breakpoints": [{ "line": 3 }] }));
+    assert_eq!(breakpoints["breakpoints"][0]["verified"], false);
+
+    client.body("configurationDone", json!({}));
+    assert_eq!(client.event("stopped")["reason"], "entry");
+    assert_eq!(client.body("threads", json!({}))["threads"][0]["id"], 1);
+
+    // Stop in the subroutine.
+    client.body("continue", json!({ "threadId": 1 }));
+    assert_eq!(client.event("stopped")["reason"], "breakpoint");
+    let frames = client.body("stackTrace", json!({ "threadId": 1 }));
+    assert_eq!(frames["stackFrames"][0]["name"], "increment");
+    assert_eq!(frames["stackFrames"][1]["name"], "loop+0x2");
+    assert_eq!(frames["stackFrames"][1]["instructionPointerReference"], "0x0204");
+    assert_eq!(client.register("V0"), "0x05");
+    assert_eq!(client.register("PC"), "0x0208");
+    let stack = client.body("variables", json!({ "variablesReference": 2 }));
+    assert_eq!(stack["variables"][0]["value"], "0x0204 (loop+0x2)");
+
+    client.body("continue", json!({ "threadId": 1 }));
+    assert_eq!(client.event("stopped")["reason"], "breakpoint");
+    assert_eq!(client.register("V0"), "0x06");
+
+    // The conditional breakpoint after the increment.
+    client.body("continue", json!({ "threadId": 1 }));
+    assert_eq!(client.event("stopped")["reason"], "breakpoint");
+    assert_eq!(client.register("PC"), "0x020A");
+    let result = client.body("evaluate", json!({ "expression": "mem[PC] << 8 | mem[PC + 1]" }));
+    assert_eq!(result["result"], "238 (0xEE)");
+
+    // Variables.
+    assert_eq!(client.body("setVariable", json!({ "variablesReference": 1, "name": "V0", "value": "V0 + 0x10" }))["value"], "0x17");
+    assert_eq!(client.register("V0"), "0x17");
+    assert_eq!(client.request("setVariable", json!({ "variablesReference": 1, "name": "SP", "value": "17" }))["success"], false);
+    // Values that do not fit in the register are rejected instead of truncated.
+    for (name, value) in [("V0", "300"), ("V0", "-1"), ("DT", "0x100"), ("I", "0x10000")] {
+        let response = client.request("setVariable", json!({ "variablesReference": 1, "name": name, "value": value }));
+        assert_eq!(response["success"], false, "{name} = {value}");
+    }
+    assert_eq!(client.register("V0"), "0x17");
+
+    // Steps execute one instruction with the interpreter.
+    client.body("next", json!({ "threadId": 1 }));
+    assert_eq!(client.event("stopped")["reason"], "step");
+    assert_eq!(client.register("PC"), "0x0204");
+    client.body("stepIn", json!({ "threadId": 1 }));
+    assert_eq!(client.event("stopped")["reason"], "step");
+    assert_eq!(client.register("PC"), "0x0202");
+
+    // Memory.
+    let memory = client.body("readMemory", json!({ "memoryReference": "0x200", "count": 4 }));
+    assert_eq!(memory["data"], "YAUiCA==");
+    let memory = client.body("readMemory", json!({ "memoryReference": "1FF", "offset": 1, "count": 4 }));
+    assert_eq!(memory["data"], "YAUiCA==");
+    let request = json!({ "memoryReference": "0x7FFFFFFFFFFFFFFF", "offset": 1, "count": 4 });
+    assert_eq!(client.request("readMemory", request)["success"], false);
+    assert_eq!(client.body("writeMemory", json!({ "memoryReference": "0x2FF", "offset": 1, "data": "q80=" }))["bytesWritten"], 2);
+    let memory = client.body("readMemory", json!({ "memoryReference": "0x300", "count": 3 }));
+    assert_eq!(memory["data"], "q80A");
+    let memory = client.body("readMemory", json!({ "memoryReference": "0xFFFF", "count": 4 }));
+    assert_eq!(memory["unreadableBytes"], 3);
+
+    // Pause a running core.
+    client.body("setFunctionBreakpoints", json!({ "breakpoints": [] }));
+    client.body("setInstructionBreakpoints", json!({ "breakpoints": [] }));
+    client.body("continue", json!({ "threadId": 1 }));
+    thread::sleep(Duration::from_millis(50));
+    client.body("pause", json!({ "threadId": 1 }));
+    assert_eq!(client.event("stopped")["reason"], "pause");
+
+    client.body("disconnect", json!({}));
+    server.join().unwrap().unwrap();
+}
@@ -0,0 +1,450 @@
+//! GDB remote serial protocol stub for risp8.
+//!
+//! [GdbStub] lets a debugger control a core running with [Chip8::run](risp8::Chip8::run) through its channels. The
+//! core is paused when the debugger attaches and resumed when it detaches.
+//!
+//! The registers are V0 to VF, I, PC, SP, DT (delay timer) and ST (sound timer), in this order. I and PC are 16-bit
+//! little-endian values and the others are 8-bit. They are described to the debugger with a target description.
+//!
+//! The supported packets are `?`, `g`, `G`, `p`, `P`, `m`, `M`, `Z0`, `z0` (breakpoints), `s` (single step, with the
+//! semantics of [Risp8Command::SingleStep]), `c` (continue, interrupted with Ctrl-C), `D` (detach), `k` (kill, which
+//! only closes the connection), `qSupported`, `qXfer:features:read` and `QStartNoAckMode`. The other packets get the
+//! empty reply, meaning they are not supported.
+
+mod packet;
+
+use packet::{Connection, Input};
+
+use risp8::{Receiver, Risp8Answer, Risp8Command, Sender, State, Trap};
+
+use std::fmt::Write;
+use std::io::{self, ErrorKind};
+use std::net::TcpStream;
+
+/// The stop reply of breakpoints, single steps and watchpoints (SIGTRAP).
+const SIGTRAP: &str = "S05";
+/// The stop reply of an interrupt request (SIGINT).
+const SIGINT: &str = "S02";
+/// The stop reply of invalid opcodes (SIGILL).
+const SIGILL: &str = "S04";
+/// The stop reply of the other traps (SIGSEGV).
+const SIGSEGV: &str = "S0b";
+/// The stop reply of the SUPER-CHIP exit instruction.
+const EXITED: &str = "W00";
+/// The reply to invalid packets.
+const ERROR: &str = "E01";
+
+/// The maximum size of a packet, 4096 bytes.
+const PACKET_SIZE: usize = 0x1000;
+
+/// The number of registers: V0 to VF, I, PC, SP, DT and ST.
+const REGISTER_COUNT: usize = 21;
+const REG_I: usize = 16;
+const REG_PC: usize = 17;
+const REG_SP: usize = 18;
+const REG_DT: usize = 19;
+const REG_ST: usize = 20;
+
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.risp8.chip8">
+    <reg name="v0" bitsize="8" regnum="0"/>
+    <reg name="v1" bitsize="8"/>
+    <reg name="v2" bitsize="8"/>
+    <reg name="v3" bitsize="8"/>
+    <reg name="v4" bitsize="8"/>
+    <reg name="v5" bitsize="8"/>
+    <reg name="v6" bitsize="8"/>
+    <reg name="v7" bitsize="8"/>
+    <reg name="v8" bitsize="8"/>
+    <reg name="v9" bitsize="8"/>
+    <reg name="va" bitsize="8"/>
+    <reg name="vb" bitsize="8"/>
+    <reg name="vc" bitsize="8"/>
+    <reg name="vd" bitsize="8"/>
+    <reg name="ve" bitsize="8"/>
+    <reg name="vf" bitsize="8"/>
+    <reg name="i" bitsize="16" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+    <reg name="sp" bitsize="8"/>
+    <reg name="dt" bitsize="8"/>
+    <reg name="st" bitsize="8"/>
+  </feature>
+</target>
+"#;
+
+/// A GDB remote serial protocol server for one debugger connection.
+pub struct GdbStub {
+    connection: Connection,
+    commands: Sender<Risp8Command>,
+    answers: Receiver<Risp8Answer>,
+    /// The reply to the `?` packet, the reason of the last stop.
+    last_stop: &'static str,
+    /// The stop reply of the last stop answer received while waiting for another answer.
+    stop: Option<&'static str>,
+}
+
+impl GdbStub {
+    /// Creates a stub serving the debugger connected to `stream`, controlling the core through its channels.
+    pub fn new(stream: TcpStream, commands: Sender<Risp8Command>, answers: Receiver<Risp8Answer>) -> io::Result<Self> {
+        Ok(Self {
+            connection: Connection::new(stream)?,
+            commands,
+            answers,
+            last_stop: SIGTRAP,
+            stop: None,
+        })
+    }
+
+    /// Pauses the core and serves the debugger until it detaches, kills the target or closes the connection.
+    ///
+    /// Returns an error if the connection fails or if the core stopped answering.
+    pub fn run(mut self) -> io::Result<()> {
+        self.send(Risp8Command::Pause)?;
+
+        loop {
+            let packet = match self.connection.poll() {
+                Ok(Some(Input::Packet(packet))) => packet,
+                Ok(Some(Input::Interrupt) | None) => continue,
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(()),
+                Err(e) => return Err(e),
+            };
+
+            match packet.as_str() {
+                "QStartNoAckMode" => {
+                    self.connection.send("OK")?;
+                    self.connection.disable_ack();
+                },
+                "k" => return Ok(()),
+                _ if packet.starts_with('D') || packet.starts_with("vKill") => {
+                    if packet.starts_with('D') {
+                        self.send(Risp8Command::Play)?;
+                    }
+                    return self.connection.send("OK");
+                },
+                _ => {
+                    let reply = self.handle(&packet)?;
+                    self.connection.send(&reply)?;
+                },
+            }
+        }
+    }
+
+    /// Returns the reply to the given packet.
+    fn handle(&mut self, packet: &str) -> io::Result<String> {
+        let Some(command) = packet.chars().next() else {
+            return Ok(String::new());
+        };
+        let args = &packet[command.len_utf8()..];
+
+        let reply = match command {
+            '?' => Some(self.last_stop.to_string()),
+            'g' => {
+                let state = self.state()?;
+                let mut reply = String::new();
+                (0..REGISTER_COUNT).for_each(|n| encode_register(&state, n, &mut reply));
+                Some(reply)
+            },
+            'G' => self.write_registers(args)?,
+            'p' => self.read_register(args)?,
+            'P' => self.write_register(args)?,
+            'm' => self.read_memory(args)?,
+            'M' => self.write_memory(args)?,
+            'Z' | 'z' => self.breakpoint(command == 'Z', args)?,
+            's' | 'c' => {
+                if !args.is_empty() && !self.resume_at(args)? {
+                    return Ok(ERROR.to_string());
+                }
+                self.last_stop = if command == 's' { self.step()? } else { self.resume()? };
+                Some(self.last_stop.to_string())
+            },
+            'H' => Some("OK".to_string()),
+            'q' => Some(query(args)),
+            _ => Some(String::new()),
+        };
+
+        Ok(reply.unwrap_or_else(|| ERROR.to_string()))
+    }
+
+    /// `G XX...`: writes every register.
+    fn write_registers(&mut self, args: &str) -> io::Result<Option<String>> {
+        let Some(data) = decode_hex(args) else {
+            return Ok(None);
+        };
+        if data.len() != (0..REGISTER_COUNT).map(register_size).sum() {
+            return Ok(None);
+        }
+
+        let mut state = self.state()?;
+        let mut offset = 0;
+        for n in 0..REGISTER_COUNT {
+            let size = register_size(n);
+            if !set_register(&mut state, n, decode_register(&data[offset..offset + size])) {
+                return Ok(None);
+            }
+            offset += size;
+        }
+
+        Ok(self.set_state(&state)?.then(|| "OK".to_string()))
+    }
+
+    /// `p n`: reads register n.
+    fn read_register(&mut self, args: &str) -> io::Result<Option<String>> {
+        let Some(n) = usize::from_str_radix(args, 16).ok().filter(|&n| n < REGISTER_COUNT) else {
+            return Ok(None);
+        };
+
+        let state = self.state()?;
+        let mut reply = String::new();
+        encode_register(&state, n, &mut reply);
+        Ok(Some(reply))
+    }
+
+    /// `P n=XX...`: writes register n.
+    fn write_register(&mut self, args: &str) -> io::Result<Option<String>> {
+        let Some((n, value)) = args.split_once('=') else {
+            return Ok(None);
+        };
+        let n = usize::from_str_radix(n, 16).ok().filter(|&n| n < REGISTER_COUNT);
+        let (Some(n), Some(value)) = (n, decode_hex(value)) else {
+            return Ok(None);
+        };
+        if value.len() != register_size(n) {
+            return Ok(None);
+        }
+
+        let mut state = self.state()?;
+        if !set_register(&mut state, n, decode_register(&value)) {
+            return Ok(None);
+        }
+        Ok(self.set_state(&state)?.then(|| "OK".to_string()))
+    }
+
+    /// `m addr,len`: reads memory. Reads past the end of memory are truncated.
+    fn read_memory(&mut self, args: &str) -> io::Result<Option<String>> {
+        let Some((address, len)) = parse_range(args) else {
+            return Ok(None);
+        };
+
+        let state = self.state()?;
+        let memory = state.memory();
+        if address >= memory.len() {
+            return Ok(None);
+        }
+
+        let len = len.min((PACKET_SIZE - 4) / 2);
+        let end = address.saturating_add(len).min(memory.len());
+        Ok(Some(encode_hex(&memory[address..end])))
+    }
+
+    /// `M addr,len:XX...`: writes memory.
+    fn write_memory(&mut self, args: &str) -> io::Result<Option<String>> {
+        let Some((range, data)) = args.split_once(':') else {
+            return Ok(None);
+        };
+        let (Some((address, len)), Some(data)) = (parse_range(range), decode_hex(data)) else {
+            return Ok(None);
+        };
+        if data.len() != len || address.saturating_add(len) > self.state()?.memory_size() {
+            return Ok(None);
+        }
+
+        if !data.is_empty() {
+            self.send(Risp8Command::WriteMemory(address as u16, data))?;
+        }
+        Ok(Some("OK".to_string()))
+    }
+
+    /// `Z0,addr,kind` and `z0,addr,kind`: adds or removes a breakpoint. The other kinds are not supported.
+    fn breakpoint(&mut self, add: bool, args: &str) -> io::Result<Option<String>> {
+        let mut args = args.split(',');
+        if args.next() != Some("0") {
+            return Ok(Some(String::new()));
+        }
+        let Some(pc) = args.next().and_then(|pc| u16::from_str_radix(pc, 16).ok()) else {
+            return Ok(None);
+        };
+
+        if add {
+            self.send(Risp8Command::AddBreakpoint(pc))?;
+        } else {
+            self.send(Risp8Command::RemoveBreakpoint(pc))?;
+        }
+        Ok(Some("OK".to_string()))
+    }
+
+    /// Sets PC to the address of an `s addr` or `c addr` packet. Returns false if it is invalid.
+    fn resume_at(&mut self, address: &str) -> io::Result<bool> {
+        let Ok(pc) = u16::from_str_radix(address, 16) else {
+            return Ok(false);
+        };
+
+        let mut state = self.state()?;
+        if state.set_pc(pc).is_err() {
+            return Ok(false);
+        }
+        self.set_state(&state)
+    }
+
+    /// Runs the execution method once and returns the stop reply.
+    fn step(&mut self) -> io::Result<&'static str> {
+        self.discard_answers()?;
+        self.send(Risp8Command::SingleStep)?;
+        // The state is sent after the step has been executed.
+        self.state()?;
+        Ok(self.stop.take().unwrap_or(SIGTRAP))
+    }
+
+    /// Resumes the core until it stops or the debugger interrupts it, and returns the stop reply.
+    fn resume(&mut self) -> io::Result<&'static str> {
+        self.discard_answers()?;
+        self.send(Risp8Command::Play)?;
+
+        loop {
+            while let Some(answer) = self.answers.try_recv().map_err(disconnected)? {
+                if let Some(reply) = stop_reply(&answer) {
+                    return Ok(reply);
+                }
+            }
+
+            if let Some(Input::Interrupt) = self.connection.poll()? {
+                self.send(Risp8Command::Pause)?;
+                // The core may have stopped by itself before being paused.
+                self.state()?;
+                return Ok(self.stop.take().unwrap_or(SIGINT));
+            }
+        }
+    }
+
+    /// Discards the answers of the core received since it stopped.
+    fn discard_answers(&mut self) -> io::Result<()> {
+        while self.answers.try_recv().map_err(disconnected)?.is_some() {}
+        self.stop = None;
+        Ok(())
+    }
+
+    /// Returns a copy of the state of the core.
+    fn state(&mut self) -> io::Result<Box<State>> {
+        self.send(Risp8Command::GetState)?;
+        self.wait(|answer| match answer {
+            Risp8Answer::State(state) => Some(state),
+            _ => None,
+        })
+    }
+
+    /// Replaces the state of the core. Returns false if the core rejected it.
+    fn set_state(&mut self, state: &State) -> io::Result<bool> {
+        self.send(Risp8Command::LoadState(state.save()))?;
+        self.wait(|answer| match answer {
+            Risp8Answer::StateLoaded(result) => Some(result.is_ok()),
+            _ => None,
+        })
+    }
+
+    /// Returns the first answer accepted by `accept`, remembering the stop answers received before it.
+    fn wait<T>(&mut self, mut accept: impl FnMut(Risp8Answer) -> Option<T>) -> io::Result<T> {
+        loop {
+            let answer = self.answers.recv().map_err(disconnected)?;
+            if let Some(reply) = stop_reply(&answer) {
+                self.stop = Some(reply);
+            } else if let Some(value) = accept(answer) {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn send(&self, command: Risp8Command) -> io::Result<()> {
+        self.commands.send(command).map_err(disconnected)
+    }
+}
+
+/// Returns the reply to a `q` packet.
+fn query(args: &str) -> String {
+    if args.starts_with("Supported") {
+        format!("PacketSize={PACKET_SIZE:x};qXfer:features:read+;QStartNoAckMode+")
+    } else if let Some(range) = args.strip_prefix("Xfer:features:read:target.xml:") {
+        let Some((offset, len)) = parse_range(range) else {
+            return ERROR.to_string();
+        };
+        let data = TARGET_XML.get(offset.min(TARGET_XML.len())..).unwrap_or_default();
+        let len = len.min(PACKET_SIZE - 5);
+        if data.len() > len {
+            format!("m{}", &data[..len])
+        } else {
+            format!("l{data}")
+        }
+    } else if args == "Attached" {
+        "1".to_string()
+    } else {
+        String::new()
+    }
+}
+
+/// Returns the stop reply of the answers sent when the core stops by itself.
+fn stop_reply(answer: &Risp8Answer) -> Option<&'static str> {
+    match answer {
+        Risp8Answer::BreakpointHit(_) | Risp8Answer::WatchpointHit(_) | Risp8Answer::MovieDesync { .. } => Some(SIGTRAP),
+        Risp8Answer::Trap(Trap::InvalidOpcode { .. }) => Some(SIGILL),
+        Risp8Answer::Trap(_) => Some(SIGSEGV),
+        Risp8Answer::Exited => Some(EXITED),
+        _ => None,
+    }
+}
+
+fn disconnected<E>(_: E) -> io::Error {
+    io::Error::new(ErrorKind::BrokenPipe, "The core has been stopped")
+}
+
+fn register_size(n: usize) -> usize {
+    if n == REG_I || n == REG_PC { 2 } else { 1 }
+}
+
+fn encode_register(state: &State, n: usize, out: &mut String) {
+    let value = match n {
+        REG_I => state.i(),
+        REG_PC => state.pc(),
+        REG_SP => state.sp() as u16,
+        REG_DT => state.delay() as u16,
+        REG_ST => state.sound() as u16,
+        _ => state.v()[n] as u16,
+    };
+    out.push_str(&encode_hex(&value.to_le_bytes()[..register_size(n)]));
+}
+
+fn decode_register(data: &[u8]) -> u16 {
+    u16::from_le_bytes([data[0], data.get(1).copied().unwrap_or(0)])
+}
+
+/// Sets register n. Returns false if the value is invalid.
+fn set_register(state: &mut State, n: usize, value: u16) -> bool {
+    match n {
+        REG_I => state.set_i(value),
+        REG_PC => return state.set_pc(value).is_ok(),
+        REG_SP => return state.set_sp(value as usize).is_ok(),
+        REG_DT => state.set_delay(value as u8),
+        REG_ST => state.set_sound(value as u8),
+        _ => return state.set_v(n, value as u8).is_ok(),
+    }
+    true
+}
+
+/// Parses `addr,len` in hexadecimal.
+fn parse_range(args: &str) -> Option<(usize, usize)> {
+    let (address, len) = args.split_once(',')?;
+    Some((usize::from_str_radix(address, 16).ok()?, usize::from_str_radix(len, 16).ok()?))
+}
+
+fn encode_hex(data: &[u8]) -> String {
+    data.iter().fold(String::with_capacity(data.len() * 2), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    })
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok()).collect()
+}
@@ -0,0 +1,59 @@
+use risp8::{Chip8, Platform};
+use risp8_gdb::GdbStub;
+
+use std::net::{Ipv4Addr, TcpListener};
+
+/// The port GDB connects to with `target remote :1234`.
+const DEFAULT_PORT: u16 = 1234;
+
+fn print_usage_and_exit(exec: &str) -> ! {
+    println!("Usage: {exec} [-p port] <ROM> [platform]");
+    let platforms: Vec<&str> = Platform::ALL.iter().map(|p| p.name()).collect();
+    println!("Platforms: {}", platforms.join(", "));
+    std::process::exit(1);
+}
+
+fn main() {
+    let mut args: Vec<String> = std::env::args().collect();
+    let exec = args.remove(0);
+
+    let mut port = DEFAULT_PORT;
+    if args.first().is_some_and(|arg| arg == "-p") {
+        port = args.get(1).and_then(|port| port.parse().ok()).unwrap_or_else(|| print_usage_and_exit(&exec));
+        args.drain(..2);
+    }
+    if args.len() != 1 && args.len() != 2 {
+        print_usage_and_exit(&exec);
+    }
+
+    let chip8 = match args.get(1) {
+        Some(name) => {
+            let platform = Platform::from_name(name).unwrap_or_else(|| print_usage_and_exit(&exec));
+            Chip8::with_platform(&args[0], platform)
+        },
+        None => Chip8::new(&args[0]),
+    };
+    let (mut chip8, chip8_in, chip8_out) = chip8
+        .unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        });
+
+    std::thread::spawn(move || {
+        chip8.run();
+    });
+
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port)).unwrap_or_else(|e| {
+        eprintln!("Failed to listen on port {port}: {e}");
+        std::process::exit(1);
+    });
+    println!("Waiting for GDB on port {port}");
+
+    for stream in listener.incoming() {
+        let result = stream.and_then(|stream| GdbStub::new(stream, chip8_in.clone(), chip8_out.clone())?.run());
+        match result {
+            Ok(()) => println!("GDB disconnected"),
+            Err(e) => eprintln!("GDB connection failed: {e}"),
+        }
+    }
+}
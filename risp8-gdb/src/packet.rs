@@ -0,0 +1,107 @@
+//! Framing of the remote serial protocol packets.
+
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+/// What the debugger sent.
+pub(crate) enum Input {
+    /// The data of a packet.
+    Packet(String),
+    /// The interrupt request (Ctrl-C), sent outside of packets while the target is running.
+    Interrupt,
+}
+
+/// A connection to the debugger.
+///
+/// Packets are sent without waiting for their acknowledgment, and the acknowledgments of the debugger are ignored.
+pub(crate) struct Connection {
+    stream: TcpStream,
+    /// The bytes received and not parsed yet.
+    buffer: Vec<u8>,
+    /// True until the debugger disables acknowledgments with QStartNoAckMode.
+    ack: bool,
+}
+
+impl Connection {
+    /// How long [poll](Self::poll) waits for data.
+    const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+    pub fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_read_timeout(Some(Self::POLL_INTERVAL))?;
+        stream.set_nodelay(true)?;
+        Ok(Self { stream, buffer: Vec::new(), ack: true })
+    }
+
+    /// Returns the next input of the debugger, or None if nothing complete has been received during the poll interval.
+    ///
+    /// Returns an [ErrorKind::UnexpectedEof] error when the debugger closes the connection.
+    pub fn poll(&mut self) -> io::Result<Option<Input>> {
+        if let Some(input) = self.parse()? {
+            return Ok(Some(input));
+        }
+
+        let mut data = [0; 1024];
+        match self.stream.read(&mut data) {
+            Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+            Ok(len) => self.buffer.extend_from_slice(&data[..len]),
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut | ErrorKind::Interrupted) => return Ok(None),
+            Err(e) => return Err(e),
+        }
+
+        self.parse()
+    }
+
+    /// Parses the first input of the buffer.
+    fn parse(&mut self) -> io::Result<Option<Input>> {
+        // Skip the acknowledgments and anything outside of packets.
+        while let Some(&byte) = self.buffer.first() {
+            match byte {
+                0x03 => {
+                    self.buffer.remove(0);
+                    return Ok(Some(Input::Interrupt));
+                },
+                b'$' => break,
+                _ => { self.buffer.remove(0); },
+            }
+        }
+
+        let Some(end) = self.buffer.iter().position(|&byte| byte == b'#') else {
+            return Ok(None);
+        };
+        if self.buffer.len() < end + 3 {
+            return Ok(None);
+        }
+
+        let packet: Vec<u8> = self.buffer.drain(..end + 3).collect();
+        let data = &packet[1..end];
+        let received = std::str::from_utf8(&packet[end + 1..]).ok().and_then(|checksum| u8::from_str_radix(checksum, 16).ok());
+        if received != Some(checksum(data)) {
+            if self.ack {
+                self.stream.write_all(b"-")?;
+            }
+            return Ok(None);
+        }
+
+        if self.ack {
+            self.stream.write_all(b"+")?;
+        }
+        Ok(Some(Input::Packet(String::from_utf8_lossy(data).into_owned())))
+    }
+
+    /// Sends a packet with the given data, which must not contain `$`, `#`, `}` nor `*`.
+    pub fn send(&mut self, data: &str) -> io::Result<()> {
+        let packet = format!("${data}#{:02x}", checksum(data.as_bytes()));
+        self.stream.write_all(packet.as_bytes())
+    }
+
+    /// Stops acknowledging the packets received.
+    pub fn disable_ack(&mut self) {
+        self.ack = false;
+    }
+}
+
+/// The checksum of a packet, the sum of its bytes modulo 256.
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, &byte| sum.wrapping_add(byte))
+}
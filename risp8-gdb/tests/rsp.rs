@@ -0,0 +1,136 @@
+//! Drives the stub with a scripted remote serial protocol client.
+
+use risp8::{Chip8Builder, ExecutionMethod};
+use risp8_gdb::GdbStub;
+
+use std::io::{Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+/// 6005 7001 A300 1202: sets V0 to 5 then increments it in a loop at 0x202.
+const PROGRAM: [u8; 8] = [0x60, 0x05, 0x70, 0x01, 0xA3, 0x00, 0x12, 0x02];
+
+struct Client {
+    stream: TcpStream,
+    ack: bool,
+}
+
+impl Client {
+    /// Sends a packet and returns the data of the reply.
+    fn send(&mut self, data: &str) -> String {
+        let checksum = data.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+        write!(self.stream, "${data}#{checksum:02x}").unwrap();
+        if self.ack {
+            assert_eq!(self.read_byte(), b'+', "packet {data} not acknowledged");
+        }
+        self.reply()
+    }
+
+    /// Reads a reply packet.
+    fn reply(&mut self) -> String {
+        assert_eq!(self.read_byte(), b'$');
+        let mut data = Vec::new();
+        loop {
+            match self.read_byte() {
+                b'#' => break,
+                byte => data.push(byte),
+            }
+        }
+        let checksum = [self.read_byte(), self.read_byte()];
+        let checksum = u8::from_str_radix(std::str::from_utf8(&checksum).unwrap(), 16).unwrap();
+        assert_eq!(checksum, data.iter().fold(0u8, |sum, &byte| sum.wrapping_add(byte)));
+        if self.ack {
+            self.stream.write_all(b"+").unwrap();
+        }
+        String::from_utf8(data).unwrap()
+    }
+
+    fn read_byte(&mut self) -> u8 {
+        let mut byte = [0];
+        self.stream.read_exact(&mut byte).unwrap();
+        byte[0]
+    }
+}
+
+#[test]
+fn scripted_session() {
+    let (mut chip8, chip8_in, chip8_out) = Chip8Builder::new(PROGRAM.to_vec())
+        .execution_method(ExecutionMethod::Interpreter)
+        .pacing(false)
+        .seed(0)
+        .build()
+        .unwrap();
+    thread::spawn(move || chip8.run());
+
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let stub = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        GdbStub::new(stream, chip8_in, chip8_out).unwrap().run()
+    });
+
+    let stream = TcpStream::connect((Ipv4Addr::LOCALHOST, port)).unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+    let mut client = Client { stream, ack: true };
+
+    assert!(client.send("qSupported:multiprocess+;xmlRegisters=i386").contains("qXfer:features:read+"));
+    assert!(client.send("qXfer:features:read:target.xml:0,fff").starts_with("l<?xml"));
+    assert_eq!(client.send("?"), "S05");
+    assert_eq!(client.send("Hg0"), "OK");
+    assert_eq!(client.send("vMustReplyEmpty"), "");
+    // Invalid UTF-8 is an unknown command.
+    client.stream.write_all(b"$\xff#ff").unwrap();
+    assert_eq!(client.read_byte(), b'+');
+    assert_eq!(client.reply(), "");
+
+    // V0-VF, I, PC (little-endian), SP, DT, ST.
+    assert_eq!(client.send("g"), format!("{}00000002000000", "00".repeat(16)));
+
+    // Continue to the breakpoint, twice.
+    assert_eq!(client.send("Z0,204,2"), "OK");
+    assert_eq!(client.send("c"), "S05");
+    assert_eq!(client.send("p11"), "0402");
+    assert_eq!(client.send("p0"), "06");
+    assert_eq!(client.send("c"), "S05");
+    assert_eq!(client.send("p0"), "07");
+    assert_eq!(client.send("z0,204,2"), "OK");
+
+    // Registers.
+    assert_eq!(client.send("P0=ff"), "OK");
+    assert_eq!(client.send("p0"), "ff");
+    assert_eq!(client.send("P10=3412"), "OK");
+    assert_eq!(client.send("p10"), "3412");
+    assert_eq!(client.send("P12=11"), "E01");
+    assert_eq!(client.send("p99"), "E01");
+
+    // Memory.
+    assert_eq!(client.send("m300,2"), "0000");
+    assert_eq!(client.send("M300,2:abcd"), "OK");
+    assert_eq!(client.send("m300,2"), "abcd");
+    assert_eq!(client.send("m200,4"), "60057001");
+    assert_eq!(client.send("m10000,1"), "E01");
+    assert_eq!(client.send("Mffff,2:0000"), "E01");
+
+    // The interpreter executes one instruction per step.
+    assert_eq!(client.send("s"), "S05");
+    assert_eq!(client.send("p11"), "0602");
+    assert_eq!(client.send("s"), "S05");
+    assert_eq!(client.send("p11"), "0202");
+    assert_eq!(client.send("s202"), "S05");
+    assert_eq!(client.send("p11"), "0402");
+    assert_eq!(client.send("p10"), "0003");
+
+    // Interrupt a continue.
+    assert_eq!(client.send("QStartNoAckMode"), "OK");
+    client.ack = false;
+    write!(client.stream, "$c#63").unwrap();
+    thread::sleep(Duration::from_millis(50));
+    client.stream.write_all(&[0x03]).unwrap();
+    assert_eq!(client.reply(), "S02");
+    assert_eq!(client.send("?"), "S02");
+    assert_eq!(client.send("g").len(), 46);
+
+    assert_eq!(client.send("D"), "OK");
+    stub.join().unwrap().unwrap();
+}
@@ -1,10 +1,10 @@
 use std::thread;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use pixels::{Pixels, SurfaceTexture};
 
-use risp8::{Chip8, ExecutionMethod, Receiver, Risp8Answer, Risp8Command, Sender, State};
+use risp8::{Chip8, ExecutionMethod, FrameDelivery, Platform, Receiver, Risp8Answer, Risp8Command, Screen, Sender, Stats, Trap};
 
 use winit::application::ApplicationHandler;
 use winit::dpi::LogicalSize;
@@ -13,8 +13,13 @@ use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop};
 use winit::keyboard::{KeyCode, PhysicalKey};
 use winit::window::Window;
 
-const BLACK: [u8; 4] = [0x00, 0x00, 0x00, 0xFF];
-const WHITE: [u8; 4] = [0xFF, 0xFF, 0xFF, 0xFF];
+/// The colors of the 4 XO-CHIP pixel values.
+const PALETTE: [[u8; 4]; 4] = [
+    [0x00, 0x00, 0x00, 0xFF], // Black.
+    [0xFF, 0xFF, 0xFF, 0xFF], // White.
+    [0xAA, 0xAA, 0xAA, 0xFF], // Light gray.
+    [0x55, 0x55, 0x55, 0xFF], // Dark gray.
+];
 
 /// The context used to run the app.
 struct App {
@@ -22,6 +27,16 @@ struct App {
     pub recv: Receiver<Risp8Answer>,
     pub is_playing: bool,
     pub execution_method: ExecutionMethod,
+    /// The last trap raised by the program, until emulation is resumed.
+    pub trap: Option<Trap>,
+    /// True while the rewind key is held.
+    pub rewinding: bool,
+    /// True if emulation was running when rewinding started, to resume it when the rewind key is released.
+    pub resume_after_rewind: bool,
+    /// The last performance counters received from the core.
+    pub stats: Option<Stats>,
+    /// When the performance counters have last been requested.
+    pub stats_requested: Instant,
 
     pub update_title: bool,
     window: Option<Arc<Window>>,
@@ -29,8 +44,11 @@ struct App {
 }
 
 impl App {
+    /// The interval at which the performance counters are requested while emulation is running.
+    const STATS_INTERVAL: Duration = Duration::from_millis(500);
+
     fn generate_window_title(&self) -> String {
-        let playing = if self.is_playing { "Running" } else { "Paused" };
+        let playing = if self.rewinding { "Rewinding" } else if self.is_playing { "Running" } else { "Paused" };
         let exec = match self.execution_method {
             ExecutionMethod::Interpreter => "Interpreter",
             ExecutionMethod::CachedInterpreter => "Cached interpreter",
@@ -39,7 +57,11 @@ impl App {
             ExecutionMethod::Jit => "Jit",
         };
 
-        format!("{playing} - {exec} - risp8")
+        let stats = self.stats.map(|stats| format!(" - {stats}")).unwrap_or_default();
+        match self.trap {
+            Some(trap) => format!("{playing} ({trap}) - {exec}{stats} - risp8"),
+            None => format!("{playing} - {exec}{stats} - risp8"),
+        }
     }
 
     fn handle_keyboard(&mut self, event: KeyEvent) {
@@ -93,19 +115,39 @@ impl App {
                 self.execution_method = ExecutionMethod::Jit;
                 self.update_title = true;
             },
-            KeyCode::KeyS  => if pressed { self.send.send(Risp8Command::SingleStep).unwrap() },
-            KeyCode::KeyP => {
-                if pressed {
-                    if self.is_playing {
-                        self.send.send(Risp8Command::Pause).unwrap();
-                        self.is_playing = false;
-                    } else {
+            KeyCode::KeyS if pressed => {
+                self.send.send(Risp8Command::SingleStep).unwrap();
+                self.send.send(Risp8Command::GetScreen).unwrap();
+                self.send.send(Risp8Command::GetStats).unwrap();
+            },
+            KeyCode::Backspace => {
+                if pressed && !self.rewinding {
+                    // Pause while rewinding so the emulation does not advance between the rewind steps.
+                    self.rewinding = true;
+                    self.resume_after_rewind = self.is_playing;
+                    self.send.send(Risp8Command::Pause).unwrap();
+                    self.is_playing = false;
+                    self.update_title = true;
+                } else if !pressed && self.rewinding {
+                    self.rewinding = false;
+                    if self.resume_after_rewind {
                         self.send.send(Risp8Command::Play).unwrap();
                         self.is_playing = true;
                     }
                     self.update_title = true;
                 }
             },
+            KeyCode::KeyP if pressed => {
+                if self.is_playing {
+                    self.send.send(Risp8Command::Pause).unwrap();
+                    self.is_playing = false;
+                } else {
+                    self.send.send(Risp8Command::Play).unwrap();
+                    self.is_playing = true;
+                    self.trap = None;
+                }
+                self.update_title = true;
+            },
             _ => (),
         }
     }
@@ -164,14 +206,18 @@ impl ApplicationHandler for App {
     }
 
     fn new_events(&mut self, event_loop: &ActiveEventLoop, cause: StartCause) {
-        match cause {
-            StartCause::ResumeTimeReached { .. } => {
-                // This is either a timer resumed or any other kind of event, poll the screen anyway.
-                if self.is_playing && self.send.send(Risp8Command::GetScreen).is_err() {
+        if let StartCause::ResumeTimeReached { .. } = cause {
+            // The frames are sent by the core while playing, but not while paused to rewind.
+            if self.rewinding && (self.send.send(Risp8Command::Rewind(1)).is_err() || self.send.send(Risp8Command::GetScreen).is_err()) {
+                event_loop.exit();
+            }
+
+            if self.is_playing && self.stats_requested.elapsed() >= App::STATS_INTERVAL {
+                self.stats_requested = Instant::now();
+                if self.send.send(Risp8Command::GetStats).is_err() {
                     event_loop.exit();
                 }
-            },
-            _ => (),
+            }
         }
     }
 
@@ -183,10 +229,24 @@ impl ApplicationHandler for App {
             };
 
             match answer {
-                Risp8Answer::Screen(screen) => {
+                Risp8Answer::Screen(screen) | Risp8Answer::Frame(screen) => {
                     chip8_screen_to_rgba(&screen, self.pixels_mut().frame_mut());
                     self.window().request_redraw();
                 },
+                Risp8Answer::Exited | Risp8Answer::MovieDesync { .. } | Risp8Answer::BreakpointHit(_) | Risp8Answer::WatchpointHit(_) => {
+                    self.is_playing = false;
+                    self.update_title = true;
+                },
+                Risp8Answer::Trap(trap) => {
+                    self.is_playing = false;
+                    self.trap = Some(trap);
+                    self.update_title = true;
+                },
+                Risp8Answer::Stats(stats) => {
+                    self.stats = Some(stats);
+                    self.update_title = true;
+                },
+                Risp8Answer::Log(message) => println!("{message}"),
                 _ => (), // TODO: sound.
             }
         }
@@ -196,7 +256,7 @@ impl ApplicationHandler for App {
             self.update_title = false;
         }
 
-        if self.is_playing {
+        if self.is_playing || self.rewinding {
             event_loop.set_control_flow(ControlFlow::wait_duration(Duration::from_millis(16)));
         }
     }
@@ -206,19 +266,16 @@ impl ApplicationHandler for App {
 fn new_pixels(window: Arc<Window>) -> Pixels<'static> {
     let window_size = window.inner_size();
     let surface_texture = SurfaceTexture::new(window_size.width, window_size.height, window);
-    Pixels::new(State::SCREEN_WIDTH as u32, State::SCREEN_HEIGHT as u32, surface_texture).unwrap()
+    Pixels::new(Screen::HIRES_WIDTH as u32, Screen::HIRES_HEIGHT as u32, surface_texture).unwrap()
 }
 
-/// Copies the chip8 screen to a RGBA buffer.
-fn chip8_screen_to_rgba(screen: &[[bool; 64]; 32], rgba: &mut [u8]) {
+/// Copies the chip8 screen to a RGBA buffer of the hi-res size, scaling up the low resolution.
+fn chip8_screen_to_rgba(screen: &Screen, rgba: &mut [u8]) {
+    let scale = Screen::HIRES_WIDTH / screen.width();
     for (i, pixel) in rgba.chunks_exact_mut(4).enumerate() {
-        let y = i / 64;
-        let x = i % 64;
-        pixel.copy_from_slice(if screen[y][x] {
-            &WHITE
-        } else {
-            &BLACK
-        });
+        let y = i / Screen::HIRES_WIDTH / scale;
+        let x = i % Screen::HIRES_WIDTH / scale;
+        pixel.copy_from_slice(&PALETTE[screen.pixel(x, y) as usize]);
     }
 }
 
@@ -230,12 +287,18 @@ pub fn gui_main(mut chip8: Chip8, chip8_in: Sender<Risp8Command>, chip8_out: Rec
         recv: chip8_out,
         is_playing: false,
         execution_method: ExecutionMethod::Interpreter,
+        trap: None,
+        rewinding: false,
+        resume_after_rewind: false,
+        stats: None,
+        stats_requested: Instant::now(),
 
         update_title: true, // To set the window title at the first event loop.
         window: None,
         pixels: None,
     };
 
+    chip8.set_frame_delivery(FrameDelivery::WhenDirty);
     thread::spawn(move || {
         chip8.run();
     });
@@ -244,19 +307,28 @@ pub fn gui_main(mut chip8: Chip8, chip8_in: Sender<Risp8Command>, chip8_out: Rec
 }
 
 fn print_usage_and_exit(exec: &str) -> ! {
-    println!("Usage: {exec} <ROM>");
+    println!("Usage: {exec} <ROM> [platform]");
+    let platforms: Vec<&str> = Platform::ALL.iter().map(|p| p.name()).collect();
+    println!("Platforms: {}", platforms.join(", "));
     std::process::exit(1);
 }
 
 fn main() {
     let mut args = std::env::args();
     let exec = args.next().unwrap();
-    if args.len() != 1 {
+    if args.len() != 1 && args.len() != 2 {
         print_usage_and_exit(&exec);
     }
 
     let rom_file = args.next().unwrap();
-    let (chip8, chip8_in, chip8_out) = Chip8::new(&rom_file)
+    let chip8 = match args.next() {
+        Some(name) => {
+            let platform = Platform::from_name(&name).unwrap_or_else(|| print_usage_and_exit(&exec));
+            Chip8::with_platform(&rom_file, platform)
+        },
+        None => Chip8::new(&rom_file),
+    };
+    let (chip8, chip8_in, chip8_out) = chip8
         .unwrap_or_else(|e| {
             eprintln!("{}", e);
             std::process::exit(1);
@@ -1,10 +1,11 @@
 use std::io::stdout;
+use std::time::{Duration, Instant};
 
 use crossterm::ExecutableCommand;
-use crossterm::event::{self, KeyCode::Char, KeyEventKind};
+use crossterm::event::{self, KeyCode, KeyCode::Char, KeyEventKind};
 use crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen, window_size};
 
-use risp8::{Chip8, ExecutionMethod, Receiver, Risp8Answer, Risp8Command, Screen, Sender, State, DEFAULT_SCREEN};
+use risp8::{Chip8, ExecutionMethod, FrameDelivery, Platform, Receiver, Risp8Answer, Risp8Command, Screen, Sender, Stats, Trap, DEFAULT_SCREEN};
 
 use ratatui::{Frame, Terminal, TerminalOptions, Viewport};
 use ratatui::backend::CrosstermBackend;
@@ -17,15 +18,29 @@ use ratatui::widgets::{Block, Widget};
 pub struct TuiApp {
     is_playing: bool,
     execution_method: ExecutionMethod,
+    /// The last trap raised by the program, until emulation is resumed.
+    trap: Option<Trap>,
+    /// The last performance counters received from the core.
+    stats: Option<Stats>,
+    /// When the performance counters have last been requested.
+    stats_requested: Instant,
 
     screen_widget: ScreenWidget,
 }
 
 impl TuiApp {
-    pub fn new() -> Self {
+    /// The number of frames rewound on each press or repeat of the rewind key.
+    const REWIND_FRAMES: u32 = 2;
+    /// The interval at which the performance counters are requested while emulation is running.
+    const STATS_INTERVAL: Duration = Duration::from_millis(500);
+
+    fn new() -> Self {
         Self {
             is_playing: false,
             execution_method: ExecutionMethod::Interpreter,
+            trap: None,
+            stats: None,
+            stats_requested: Instant::now(),
 
             screen_widget: ScreenWidget::default(),
         }
@@ -33,7 +48,7 @@ impl TuiApp {
 
     pub fn run(&mut self, mut chip8: Chip8, chip8_in: Sender<Risp8Command>, chip8_out: Receiver<Risp8Answer>) -> std::io::Result<()> {
         if let Ok(size) = window_size() { // Not supported on Windows
-            if size.columns < State::SCREEN_WIDTH as u16 || size.rows < State::SCREEN_HEIGHT as u16 {
+            if size.columns < Screen::LORES_WIDTH as u16 || size.rows < Screen::LORES_HEIGHT as u16 {
                 println!("Warning: terminal is smaller than Chip8 screen");
             }
         }
@@ -48,6 +63,7 @@ impl TuiApp {
         let mut terminal = Terminal::with_options(backend, terminal_options)?;
         terminal.clear()?;
 
+        chip8.set_frame_delivery(FrameDelivery::WhenDirty);
         std::thread::spawn(move || {
             chip8.run();
         });
@@ -55,12 +71,31 @@ impl TuiApp {
         loop {
             while let Ok(Some(answer)) = chip8_out.try_recv() {
                 match answer {
-                    Risp8Answer::Screen(s) => self.screen_widget.screen = s,
+                    Risp8Answer::Screen(s) | Risp8Answer::Frame(s) => self.screen_widget.screen = s,
                     Risp8Answer::PlaySound => (),
                     Risp8Answer::StopSound => (),
+                    Risp8Answer::State(_) => (),
+                    Risp8Answer::Stats(stats) => self.stats = Some(stats),
+                    Risp8Answer::SaveState(_) => (),
+                    Risp8Answer::StateLoaded(_) => (),
+                    Risp8Answer::MovieStarted(_) => (),
+                    Risp8Answer::Movie(_) => (),
+                    Risp8Answer::MovieEnded => (),
+                    Risp8Answer::MovieDesync { .. } => self.is_playing = false,
+                    Risp8Answer::Exited => self.is_playing = false,
+                    Risp8Answer::BreakpointHit(_) => self.is_playing = false,
+                    Risp8Answer::WatchpointHit(_) => self.is_playing = false,
+                    Risp8Answer::Log(_) => (),
+                    Risp8Answer::Trap(trap) => {
+                        self.is_playing = false;
+                        self.trap = Some(trap);
+                    },
                 }
             }
-            chip8_in.send(Risp8Command::GetScreen).unwrap();
+            if self.is_playing && self.stats_requested.elapsed() >= TuiApp::STATS_INTERVAL {
+                self.stats_requested = Instant::now();
+                chip8_in.send(Risp8Command::GetStats).unwrap();
+            }
 
             terminal.draw(|frame| self.ui(frame))?;
 
@@ -78,14 +113,14 @@ impl TuiApp {
         use Constraint::{Length, Min};
 
         let frame_area = frame.area();
-        let [title_area, screen_block_area] = Layout::vertical([Length(1), Min(0)]).areas(frame_area);
+        let [title_area, screen_block_area, status_area] = Layout::vertical([Length(1), Min(0), Length(1)]).areas(frame_area);
         let screen_block = Block::bordered();
         let screen_area = screen_block.inner(screen_block_area);
 
         let screen_title = self.get_title(screen_area);
         let screen_block = screen_block.title(screen_title);
 
-        let frame_title = format!("<q> Quit | <p> Play | <iklmj> Execution | {}x{}", frame_area.width, frame_area.height);
+        let frame_title = format!("<q> Quit | <p> Play | <backspace> Rewind | <iklmj> Execution | {}x{}", frame_area.width, frame_area.height);
         let frame_title = if frame_area.width > frame_title.len() as u16 { // Always show the important information.
             Text::from(frame_title).centered()
         } else {
@@ -95,6 +130,10 @@ impl TuiApp {
         frame.render_widget(frame_title, title_area);
         frame.render_widget(screen_block, screen_block_area);
         self.screen_widget.render(screen_area, frame.buffer_mut());
+
+        if let Some(stats) = self.stats {
+            frame.render_widget(Text::from(stats.to_string()).left_aligned(), status_area);
+        }
     }
 
     fn get_title(&self, screen_area: Rect) -> String {
@@ -106,13 +145,27 @@ impl TuiApp {
             ExecutionMethod::CachedInterpreter3 => "Cached Interpreter 3",
             ExecutionMethod::Jit => "JIT",
         };
-        format!("{playing} | {exec} | {}x{}", screen_area.width, screen_area.height)
+        match self.trap {
+            Some(trap) => format!("{playing} ({trap}) | {exec} | {}x{}", screen_area.width, screen_area.height),
+            None => format!("{playing} | {exec} | {}x{}", screen_area.width, screen_area.height),
+        }
     }
 
     /// Returns `Ok(true)` when exit is requested.
     fn handle_keyboard(&mut self, chip8_in: &Sender<Risp8Command>) -> Result<bool, std::io::Error> {
-        if event::poll(std::time::Duration::from_millis(16))? {
+        if event::poll(Duration::from_millis(16))? {
             if let event::Event::Key(key) = event::read()? {
+                if key.code == KeyCode::Backspace && key.kind != KeyEventKind::Release {
+                    // Most terminals do not report key releases, so rewinding pauses the emulation and each key press
+                    // or repeat steps back.
+                    if self.is_playing {
+                        chip8_in.send(Risp8Command::Pause).unwrap();
+                        self.is_playing = false;
+                    }
+                    chip8_in.send(Risp8Command::Rewind(TuiApp::REWIND_FRAMES)).unwrap();
+                    chip8_in.send(Risp8Command::GetScreen).unwrap();
+                }
+
                 if key.kind != KeyEventKind::Repeat {
                     let pressed = key.kind == KeyEventKind::Press;
 
@@ -148,8 +201,13 @@ impl TuiApp {
                             } else {
                                 chip8_in.send(Risp8Command::Play).unwrap();
                                 self.is_playing = true;
+                                self.trap = None;
+                            },
+                            Char('s') => {
+                                chip8_in.send(Risp8Command::SingleStep).unwrap();
+                                chip8_in.send(Risp8Command::GetScreen).unwrap();
+                                chip8_in.send(Risp8Command::GetStats).unwrap();
                             },
-                            Char('s') => chip8_in.send(Risp8Command::SingleStep).unwrap(),
                             Char('i') => {
                                 chip8_in.send(Risp8Command::SetExecutionMethod(ExecutionMethod::Interpreter)).unwrap();
                                 self.execution_method = ExecutionMethod::Interpreter;
@@ -181,6 +239,9 @@ impl TuiApp {
     }
 }
 
+/// The colors of the 4 XO-CHIP pixel values.
+const PALETTE: [Color; 4] = [Color::Black, Color::White, Color::Gray, Color::DarkGray];
+
 #[derive(Copy, Clone, Debug)]
 struct ScreenWidget {
     screen: Screen,
@@ -203,14 +264,14 @@ impl Widget for &ScreenWidget {
             return;
         }
 
-        let width_ratio = State::SCREEN_WIDTH as f32 / area.width as f32;
-        let height_ratio = State::SCREEN_HEIGHT as f32 / area.height as f32;
+        let width_ratio = self.screen.width() as f32 / area.width as f32;
+        let height_ratio = self.screen.height() as f32 / area.height as f32;
 
         for y in 0..area.height {
             for x in 0..area.width {
                 let yy = (y as f32 * height_ratio) as usize;
                 let xx = (x as f32 * width_ratio) as usize;
-                let color = if self.screen[yy][xx] { Color::White } else { Color::Black };
+                let color = PALETTE[self.screen.pixel(xx, yy) as usize];
 
                 let pos = (area.x + x, area.y + y);
                 buf[pos].set_fg(color).set_bg(color);
@@ -220,19 +281,28 @@ impl Widget for &ScreenWidget {
 }
 
 fn print_usage_and_exit(exec: &str) -> ! {
-    println!("Usage: {exec} <ROM>");
+    println!("Usage: {exec} <ROM> [platform]");
+    let platforms: Vec<&str> = Platform::ALL.iter().map(|p| p.name()).collect();
+    println!("Platforms: {}", platforms.join(", "));
     std::process::exit(1);
 }
 
 fn main() {
     let mut args = std::env::args();
     let exec = args.next().unwrap();
-    if args.len() != 1 {
+    if args.len() != 1 && args.len() != 2 {
         print_usage_and_exit(&exec);
     }
 
     let rom_file = args.next().unwrap();
-    let (chip8, chip8_in, chip8_out) = Chip8::new(&rom_file)
+    let chip8 = match args.next() {
+        Some(name) => {
+            let platform = Platform::from_name(&name).unwrap_or_else(|| print_usage_and_exit(&exec));
+            Chip8::with_platform(&rom_file, platform)
+        },
+        None => Chip8::new(&rom_file),
+    };
+    let (chip8, chip8_in, chip8_out) = chip8
         .unwrap_or_else(|e| {
             eprintln!("{}", e);
             std::process::exit(1);
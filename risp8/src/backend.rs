@@ -0,0 +1,95 @@
+use crate::State;
+
+use core::fmt;
+use core::time::Duration;
+
+/// A strategy to execute the instructions of a [State].
+///
+/// The five [ExecutionMethod](crate::ExecutionMethod)s are implementations of this trait. A custom backend can be used
+/// with [Chip8::set_backend](crate::Chip8::set_backend). The simplest implementation executes one instruction with
+/// [State::interpret_instruction] on each step.
+///
+/// The core always passes the same boxed state, so backends can rely on its address staying the same between calls.
+pub trait ExecutionBackend {
+    /// Executes at least one and at most `budget` instructions, starting at PC.
+    ///
+    /// Executed instructions must be counted in [State::cycles], which the core uses to tick the timers. Execution
+    /// stops when an instruction traps or exits, and caches invalidated by the executed instructions must be
+    /// invalidated by the backend itself.
+    fn step(&mut self, state: &mut State, budget: u64);
+
+    /// Invalidates the cached code in the given address range (`end_addr` inclusive), which has been modified from
+    /// outside of the backend.
+    fn invalidate(&mut self, beg_addr: u16, end_addr: u16);
+
+    /// Deletes every cached code, when the state has been replaced.
+    fn reset(&mut self);
+
+    /// Adds a breakpoint at `pc`. Only called when it is not already set.
+    ///
+    /// The core stops before executing the instruction at a breakpoint, so a block of instructions may start at a
+    /// breakpoint but has to end before any other one. The blocks containing `pc` after their first instruction must
+    /// be invalidated. Backends executing a single instruction per step do not have to implement it.
+    fn add_breakpoint(&mut self, _pc: u16) {}
+
+    /// Removes the breakpoint at `pc`. Only called when it is set.
+    ///
+    /// The blocks ending at `pc` can be invalidated so they are merged again with the following instructions.
+    fn remove_breakpoint(&mut self, _pc: u16) {}
+
+    /// Returns the performance counters of the backend since its creation.
+    ///
+    /// The core counts the retired instructions itself, so [Stats::instructions] is ignored.
+    fn stats(&self) -> Stats {
+        Stats::default()
+    }
+}
+
+/// Performance counters of an [ExecutionBackend].
+///
+/// A block is a sequence of cached or compiled instructions. The cached interpreter 3 caches each instruction
+/// individually, so its blocks are single instructions. The interpreter has no block.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// The number of instructions executed.
+    pub instructions: u64,
+    /// The number of cached or compiled blocks executed.
+    pub blocks_executed: u64,
+    /// The number of times the block at PC was already cached or compiled.
+    pub cache_hits: u64,
+    /// The number of times the block at PC had to be cached or compiled.
+    pub cache_misses: u64,
+    /// The number of blocks cached or compiled.
+    pub blocks_compiled: u64,
+    /// The time spent compiling blocks, only measured by the JIT.
+    pub compile_time: Duration,
+    /// The number of cache invalidations triggered by the memory writes of the program (Fx33 and Fx55).
+    pub invalidations: u64,
+}
+
+impl Stats {
+    /// Returns the percentage of cache lookups that found the block at PC, or None if there has been no lookup.
+    pub fn hit_rate(&self) -> Option<f64> {
+        let lookups = self.cache_hits + self.cache_misses;
+        if lookups > 0 {
+            Some(self.cache_hits as f64 * 100.0 / lookups as f64)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for Stats {
+    /// Formats the counters in a single line.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} instructions, {} blocks", self.instructions, self.blocks_executed)?;
+        if let Some(hit_rate) = self.hit_rate() {
+            write!(f, ", {hit_rate:.1}% hits")?;
+        }
+        write!(f, ", {} compiled", self.blocks_compiled)?;
+        if !self.compile_time.is_zero() {
+            write!(f, " in {:.1}ms", self.compile_time.as_secs_f64() * 1000.0)?;
+        }
+        write!(f, ", {} invalidations", self.invalidations)
+    }
+}
@@ -0,0 +1,47 @@
+//! Conditional breakpoints and tracepoints.
+
+use crate::{Expression, LogMessage, State};
+
+/// A breakpoint on the address of an instruction.
+///
+/// When execution reaches the address, the breakpoint is hit if its condition is true, and it triggers once it has been
+/// hit more than `ignore_count` times. A triggered breakpoint stops execution, unless it has a log message, in which
+/// case it is a tracepoint that logs the message and lets execution continue.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Breakpoint {
+    /// The breakpoint is only hit when this expression is not 0. Always hit if None.
+    pub condition: Option<Expression>,
+    /// The number of hits ignored before the breakpoint triggers.
+    pub ignore_count: u64,
+    /// The message logged when the breakpoint triggers, instead of stopping execution.
+    pub log_message: Option<LogMessage>,
+    /// The number of times the breakpoint has been hit.
+    pub hits: u64,
+}
+
+impl Breakpoint {
+    /// Creates a breakpoint that triggers every time it is reached.
+    pub const fn new() -> Self {
+        Self { condition: None, ignore_count: 0, log_message: None, hits: 0 }
+    }
+
+    /// Creates a breakpoint that triggers when the condition is true.
+    pub const fn conditional(condition: Expression) -> Self {
+        Self { condition: Some(condition), ignore_count: 0, log_message: None, hits: 0 }
+    }
+
+    /// Creates a tracepoint that logs the message every time it is reached.
+    pub const fn tracepoint(log_message: LogMessage) -> Self {
+        Self { condition: None, ignore_count: 0, log_message: Some(log_message), hits: 0 }
+    }
+
+    /// Evaluates the breakpoint when execution reaches its address, and returns true if it triggers.
+    pub(crate) fn hit(&mut self, state: &State) -> bool {
+        if self.condition.as_ref().is_some_and(|condition| condition.evaluate(state) == 0) {
+            return false;
+        }
+
+        self.hits += 1;
+        self.hits > self.ignore_count
+    }
+}
@@ -0,0 +1,242 @@
+use crate::{Chip8, ExecutionMethod, LoadError, Platform, State};
+use crate::watchpoint::NO_WATCH;
+#[cfg(feature = "std")]
+use crate::{FrameDelivery, Receiver, Risp8Answer, Risp8Command, Sender};
+#[cfg(feature = "std")]
+use crate::rewind::Rewind;
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+#[cfg(feature = "std")]
+use kanal::{bounded, unbounded};
+
+#[cfg(feature = "std")]
+use std::sync::OnceLock;
+#[cfg(feature = "std")]
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// Builder to create a [Chip8] core from a ROM in memory.
+///
+/// ```no_run
+/// # #[cfg(feature = "std")] {
+/// use risp8::{Chip8Builder, ExecutionMethod, Platform};
+///
+/// let rom = std::fs::read("game.ch8").unwrap();
+/// let (chip8, sender, receiver) = Chip8Builder::new(rom)
+///     .platform(Platform::Schip)
+///     .execution_method(ExecutionMethod::CachedInterpreter)
+///     .play(true)
+///     .build()
+///     .unwrap();
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Chip8Builder {
+    rom: Vec<u8>,
+    platform: Option<Platform>,
+    execution_method: ExecutionMethod,
+    #[cfg(feature = "std")]
+    play: bool,
+    #[cfg(feature = "std")]
+    channel_capacity: Option<usize>,
+    instructions_per_frame: Option<u32>,
+    pacing: bool,
+    time_source: Option<fn() -> u64>,
+    seed: Option<u64>,
+    #[cfg(feature = "std")]
+    rewind_capacity: usize,
+    #[cfg(feature = "std")]
+    rewind_interval: u32,
+    #[cfg(feature = "std")]
+    frame_delivery: FrameDelivery,
+}
+
+impl Chip8Builder {
+    /// Creates a new builder for the given ROM.
+    ///
+    /// By default, the core uses the default configuration of [State::new], executes using the interpreter, starts
+    /// paused, its channels are unbounded, frames are paced in real time, the random number generator is seeded from
+    /// the system time, [Chip8::DEFAULT_REWIND_CAPACITY] rewind snapshots are taken every frame and frames are not sent
+    /// through the channel.
+    ///
+    /// Without the `std` feature there is no default time source, and the random number generator is seeded from the
+    /// time source if any, or with 0.
+    pub fn new(rom: impl Into<Vec<u8>>) -> Self {
+        Self {
+            rom: rom.into(),
+            platform: None,
+            execution_method: ExecutionMethod::Interpreter,
+            #[cfg(feature = "std")]
+            play: false,
+            #[cfg(feature = "std")]
+            channel_capacity: None,
+            instructions_per_frame: None,
+            pacing: true,
+            time_source: None,
+            seed: None,
+            #[cfg(feature = "std")]
+            rewind_capacity: Chip8::DEFAULT_REWIND_CAPACITY,
+            #[cfg(feature = "std")]
+            rewind_interval: 1,
+            #[cfg(feature = "std")]
+            frame_delivery: FrameDelivery::Never,
+        }
+    }
+
+    /// Configures the core for the given platform.
+    pub fn platform(mut self, platform: Platform) -> Self {
+        self.platform = Some(platform);
+        self
+    }
+
+    /// Sets the initial execution method.
+    pub fn execution_method(mut self, execution_method: ExecutionMethod) -> Self {
+        self.execution_method = execution_method;
+        self
+    }
+
+    /// Starts the core playing if true, paused if false.
+    #[cfg(feature = "std")]
+    pub fn play(mut self, play: bool) -> Self {
+        self.play = play;
+        self
+    }
+
+    /// Sets the capacity of the command and answer channels.
+    ///
+    /// When the answer channel is full, the core blocks until answers are received.
+    #[cfg(feature = "std")]
+    pub fn channel_capacity(mut self, capacity: usize) -> Self {
+        self.channel_capacity = Some(capacity);
+        self
+    }
+
+    /// Sets the number of instructions executed between two 60Hz timer ticks, overriding the one of the platform.
+    /// 0 is treated as 1.
+    pub fn instructions_per_frame(mut self, instructions_per_frame: u32) -> Self {
+        self.instructions_per_frame = Some(instructions_per_frame.max(1));
+        self
+    }
+
+    /// Executes frames at 60Hz in real time if true, as fast as possible if false.
+    ///
+    /// This only affects [Chip8::run] and [Chip8::next_frame_delay], the synchronous API is never paced.
+    pub fn pacing(mut self, pacing: bool) -> Self {
+        self.pacing = pacing;
+        self
+    }
+
+    /// Sets the function returning the current time in microseconds, used to pace frames in real time.
+    ///
+    /// The time must never go backwards. With the `std` feature, the default time source uses [std::time::Instant].
+    pub fn time_source(mut self, time_source: fn() -> u64) -> Self {
+        self.time_source = Some(time_source);
+        self
+    }
+
+    /// Seeds the random number generator used by Cxkk, so runs can be reproduced.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Keeps up to `capacity` rewind snapshots, taken every `interval` frames by [Chip8::run].
+    ///
+    /// A capacity of 0 disables rewinding. An interval of 0 is treated as 1.
+    #[cfg(feature = "std")]
+    pub fn rewind(mut self, capacity: usize, interval: u32) -> Self {
+        self.rewind_capacity = capacity;
+        self.rewind_interval = interval;
+        self
+    }
+
+    /// Sets when the screen is sent in [Risp8Answer::Frame] at the end of the emulated frames.
+    #[cfg(feature = "std")]
+    pub fn frame_delivery(mut self, frame_delivery: FrameDelivery) -> Self {
+        self.frame_delivery = frame_delivery;
+        self
+    }
+
+    /// Creates the core, returning it with the channels to send commands to it and receive answers from it.
+    #[cfg(feature = "std")]
+    pub fn build(self) -> Result<(Chip8, Sender<Risp8Command>, Receiver<Risp8Answer>), LoadError> {
+        let ((channel_out, user_in), (user_out, channel_in)) = match self.channel_capacity {
+            Some(capacity) => (bounded(capacity), bounded(capacity)),
+            None => (unbounded(), unbounded()),
+        };
+
+        let mut core = self.build_sync()?;
+        core.channel_in = Some(channel_in);
+        core.channel_out = Some(channel_out);
+
+        Ok((core, user_out, user_in))
+    }
+
+    /// Creates the core without channels, to be driven with the synchronous API.
+    pub fn build_sync(self) -> Result<Chip8, LoadError> {
+        let (mut state, instructions_per_frame) = match self.platform {
+            Some(platform) => (State::with_platform(&self.rom, platform)?, platform.instructions_per_frame()),
+            None => (State::new(&self.rom)?, Chip8::DEFAULT_INSTRUCTIONS_PER_FRAME),
+        };
+
+        #[cfg(feature = "std")]
+        let time_source = self.time_source.or(Some(monotonic_time as fn() -> u64));
+        #[cfg(not(feature = "std"))]
+        let time_source = self.time_source;
+
+        #[cfg(feature = "std")]
+        let seed = self.seed.unwrap_or_else(time_seed);
+        #[cfg(not(feature = "std"))]
+        let seed = self.seed.unwrap_or_else(|| time_source.map_or(0, |now| now()));
+        state.set_seed(seed);
+
+        Ok(Chip8 {
+            state: Box::new(state),
+            program: self.rom,
+            platform: self.platform,
+            instructions_per_frame: self.instructions_per_frame.unwrap_or(instructions_per_frame),
+
+            pacing: self.pacing,
+            time_source,
+            next_frame: time_source.map_or(0, |now| now()),
+            frames: 0,
+            sound_playing: false,
+            #[cfg(feature = "std")]
+            rewind: Rewind::new(self.rewind_capacity, self.rewind_interval),
+            movie: None,
+            #[cfg(feature = "std")]
+            frame_delivery: self.frame_delivery,
+
+            #[cfg(feature = "std")]
+            channel_in: None,
+            #[cfg(feature = "std")]
+            channel_out: None,
+            #[cfg(feature = "std")]
+            play: self.play,
+            execution_method: Some(self.execution_method),
+            backend: self.execution_method.backend(),
+            instructions: 0,
+            breakpoints: BTreeMap::new(),
+            breakpoint_hit: None,
+            watchpoints: Vec::new(),
+            watch_ranges: (NO_WATCH, NO_WATCH),
+            watchpoint_hits: Vec::new(),
+            log_messages: Vec::new(),
+        })
+    }
+}
+
+/// Returns a seed that changes on every run.
+#[cfg(feature = "std")]
+fn time_seed() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_nanos() as u64)
+}
+
+/// The default time source, the number of microseconds since its first call.
+#[cfg(feature = "std")]
+fn monotonic_time() -> u64 {
+    static START: OnceLock<Instant> = OnceLock::new();
+    START.get_or_init(Instant::now).elapsed().as_micros() as u64
+}
@@ -4,13 +4,15 @@ pub struct Cache {
     pc: u16,
     /// The address of the instruction following the last instruction in this cache [pc, end_pc).
     end_pc: u16,
+    /// The maximum number of instructions executed by this cache.
+    len: u64,
     _called_buf: ExecutableBuffer, // Store it so its memory isn't freed.
     _caller_buf: ExecutableBuffer, // Store it so its memory isn't freed.
     caller: extern "win64" fn(&mut u64),
 }
 
 impl Cache {
-    pub fn new(pc: u16, end_pc: u16, called_buf: ExecutableBuffer) -> Self {
+    pub fn new(pc: u16, end_pc: u16, len: u64, called_buf: ExecutableBuffer) -> Self {
         let mut caller_asm = Assembler::new().expect("Failed to create new assembler");
         let called = called_buf.ptr(AssemblyOffset(0));
 
@@ -38,12 +40,18 @@ impl Cache {
         Self {
             pc,
             end_pc,
+            len,
             _called_buf: called_buf,
             _caller_buf,
             caller,
         }
     }
 
+    /// Returns the maximum number of instructions executed by this cache.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
     pub fn run(&self) -> u64 {
         // #[cfg(debug_assertions)] println!("Executing cache at {:#X}", self.pc);
         let mut ret = 0;
@@ -64,14 +72,29 @@ impl Caches {
         }
     }
 
-    pub fn add(&mut self, pc: u16, end_pc: u16, exec: ExecutableBuffer) {
-        self.caches.push(Cache::new(pc, end_pc, exec));
+    pub fn add(&mut self, pc: u16, end_pc: u16, len: u64, exec: ExecutableBuffer) {
+        self.caches.push(Cache::new(pc, end_pc, len, exec));
+    }
+
+    /// Deletes all the caches.
+    pub fn clear(&mut self) {
+        self.caches.clear();
     }
 
     pub fn get(&self, pc: u16) -> Option<&Cache> {
         self.caches.iter().find(|cache| cache.pc == pc)
     }
 
+    /// Deletes the caches that contain the given address after their first instruction.
+    pub fn split(&mut self, addr: u16) {
+        self.caches.retain(|cache| addr <= cache.pc || addr >= cache.end_pc);
+    }
+
+    /// Deletes the caches that end at the given address.
+    pub fn remove_ending_at(&mut self, addr: u16) {
+        self.caches.retain(|cache| cache.end_pc != addr);
+    }
+
     /// Deletes all the caches that contain the given address range (`end_addr` inclusive).
     pub fn invalidate(&mut self, beg_addr: u16, end_addr: u16) {
         assert!(beg_addr <= end_addr);
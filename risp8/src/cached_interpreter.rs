@@ -4,14 +4,19 @@
 //! - <https://emudev.org/2021/01/31/cached-interpreter.html>
 //! - <https://web.archive.org/web/20210301060701/https://ps1.asuramaru.com/emulator-development/cached-interpreters>
 //!
-//! This is the basic cached interpreter: each entry in [Chip8::interpreter_caches] is the cached instructions starting
+//! This is the basic cached interpreter: each entry in [CachedInterpreter::caches] is the cached instructions starting
 //! at this PC, index with PC - 0x200 because theoretically there is no code execution below 0x200.
 //!
 //! When cache needs to be invalidated I have to search though every entries, which can be very slow if a lot of self-
 //! modifying code is executed.
 //! See cached_interpreter_2 for a O(1) cache invalidation method.
 
-use crate::{Chip8, opcode::Opcode, State};
+use crate::{ExecutionBackend, opcode::Opcode, State, Stats};
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeSet;
+use alloc::vec;
+use alloc::vec::Vec;
 
 #[derive(Clone, Copy)]
 pub(super) struct CachedInstruction {
@@ -34,60 +39,65 @@ pub const fn addr_to_index(addr: u16) -> usize {
     addr as usize - State::INITIAL_PC
 }
 
-impl Chip8 {
-    /// Executes a block of instructions using the cached interpreter.
-    pub fn cached_interpreter(&mut self) {
-        let cache_index = addr_to_index(self.state.PC);
-        let cache = if let Some(cache) = &self.interpreter_caches[cache_index] {
-            cache
-        } else {
-            let cache = self.new_cache_block();
-            self.interpreter_caches[cache_index] = Some(cache);
-            self.interpreter_caches[cache_index].as_ref().unwrap()
-        };
+/// The basic cached interpreter, caching blocks of instructions.
+pub struct CachedInterpreter {
+    caches: Box<[Option<InstructionCache>]>,
+    stats: Stats,
+    /// Blocks end before these addresses.
+    breakpoints: BTreeSet<u16>,
+}
 
-        // Execute the cache.
-        let mut ret = 0;
-        for inst in &cache.instructions {
-            // #[cfg(debug_assertions)] println!("cached opcode {:04X} at {:#X}", inst.opcode, self.state.PC);
+impl CachedInterpreter {
+    const CACHES_LEN: usize = State::MAX_PROGRAM_LEN;
+    const EMPTY_CACHE: Option<InstructionCache> = None;
 
-            self.state.PC += 2;
-            let r = (inst.execute)(&mut self.state, inst.opcode);
-            if r != 0 {
-                ret = r;
-                break;
-            }
-        }
-
-        if ret > 1 {
-            self.invalidate_cache((ret >> 16) as u16, ret as u16);
+    /// Creates a new cached interpreter with empty caches.
+    pub fn new() -> Self {
+        Self {
+            caches: vec![Self::EMPTY_CACHE; Self::CACHES_LEN].into_boxed_slice(),
+            stats: Stats::default(),
+            breakpoints: BTreeSet::new(),
         }
-
-        self.handle_timers();
     }
 
-    /// Creates a new cache at the current PC. The state is not modified.
-    fn new_cache_block(&self) -> InstructionCache {
-        let block_pc = self.state.PC;
-        let mut pc = self.state.PC;
+    /// Creates a new cache at the current PC, ending before the given breakpoints. The state is not modified.
+    fn new_cache_block(state: &State, breakpoints: &BTreeSet<u16>) -> InstructionCache {
+        let block_pc = state.PC;
+        let mut pc = state.PC;
         let mut instructions = Vec::new();
 
         'outer: loop {
-            let opcode = Opcode((self.state.memory[pc as usize] as u16) << 8 | self.state.memory[pc as usize + 1] as u16);
+            if !state.is_cacheable(pc) || pc != block_pc && breakpoints.contains(&pc) {
+                break;
+            }
+
+            let opcode = Opcode((state.memory[pc as usize] as u16) << 8 | state.memory[pc as usize + 1] as u16);
             // #[cfg(debug_assertions)] println!("caching opcode {opcode:04X} at {pc:#X}");
             pc += 2;
 
             match opcode.0 >> 12 & 0xF {
                 0x0 => match opcode.0 {
+                    0x00C0..=0x00CF => instructions.push(CachedInstruction { opcode, execute: State::execute_00Cn }),
+                    0x00D0..=0x00DF => instructions.push(CachedInstruction { opcode, execute: State::execute_00Dn }),
                     0x00E0 => instructions.push(CachedInstruction {opcode, execute: State::execute_00E0 }),
                     0x00EE => { instructions.push(CachedInstruction { opcode, execute: State::execute_00EE }); break 'outer; },
+                    0x00FB => instructions.push(CachedInstruction { opcode, execute: State::execute_00FB }),
+                    0x00FC => instructions.push(CachedInstruction { opcode, execute: State::execute_00FC }),
+                    0x00FD => { instructions.push(CachedInstruction { opcode, execute: State::execute_00FD }); break 'outer; },
+                    0x00FE => instructions.push(CachedInstruction { opcode, execute: State::execute_00FE }),
+                    0x00FF => instructions.push(CachedInstruction { opcode, execute: State::execute_00FF }),
                     _ => break 'outer,
                 },
                 0x1 => { instructions.push(CachedInstruction { opcode, execute: State::execute_1nnn }); break 'outer; },
                 0x2 => { instructions.push(CachedInstruction { opcode, execute: State::execute_2nnn }); break 'outer; },
                 0x3 => instructions.push(CachedInstruction { opcode, execute: State::execute_3xkk }),
                 0x4 => instructions.push(CachedInstruction { opcode, execute: State::execute_4xkk }),
-                0x5 => instructions.push(CachedInstruction { opcode, execute: State::execute_5xy0 }),
+                0x5 => match opcode.0 & 0xF00F {
+                    0x5000 => instructions.push(CachedInstruction { opcode, execute: State::execute_5xy0 }),
+                    0x5002 => { instructions.push(CachedInstruction { opcode, execute: State::execute_5xy2 }); break 'outer },
+                    0x5003 => instructions.push(CachedInstruction { opcode, execute: State::execute_5xy3 }),
+                    _ => break 'outer,
+                },
                 0x6 => instructions.push(CachedInstruction { opcode, execute: State::execute_6xkk }),
                 0x7 => instructions.push(CachedInstruction { opcode, execute: State::execute_7xkk }),
                 0x8 => {
@@ -104,7 +114,10 @@ impl Chip8 {
                         _ => break 'outer,
                     }
                 },
-                0x9 => instructions.push(CachedInstruction { opcode, execute: State::execute_9xy0 }),
+                0x9 => match opcode.0 & 0xF00F {
+                    0x9000 => instructions.push(CachedInstruction { opcode, execute: State::execute_9xy0 }),
+                    _ => break 'outer,
+                },
                 0xA => instructions.push(CachedInstruction { opcode, execute: State::execute_Annn }),
                 0xB => { instructions.push(CachedInstruction { opcode, execute: State::execute_Bnnn }); break 'outer; },
                 0xC => instructions.push(CachedInstruction { opcode, execute: State::execute_Cxkk }),
@@ -115,6 +128,12 @@ impl Chip8 {
                     _ => break 'outer,
                 },
                 0xF => match opcode.0 & 0xF0FF {
+                    0xF000 if opcode.0 == 0xF000 => {
+                        instructions.push(CachedInstruction { opcode, execute: State::execute_F000 });
+                        pc += 2; // Skip the address word.
+                    },
+                    0xF001 => instructions.push(CachedInstruction { opcode, execute: State::execute_Fn01 }),
+                    0xF002 if opcode.0 == 0xF002 => instructions.push(CachedInstruction { opcode, execute: State::execute_F002 }),
                     0xF007 => instructions.push(CachedInstruction { opcode, execute: State::execute_Fx07 }),
                     // Wait Key: interrupt the current cache and go to a new cache starting at the wait key instruction.
                     0xF00A => { instructions.push(CachedInstruction { opcode, execute: State::execute_Fx0A }); break 'outer },
@@ -122,9 +141,13 @@ impl Chip8 {
                     0xF018 => instructions.push(CachedInstruction { opcode, execute: State::execute_Fx18 }),
                     0xF01E => instructions.push(CachedInstruction { opcode, execute: State::execute_Fx1E }),
                     0xF029 => instructions.push(CachedInstruction { opcode, execute: State::execute_Fx29 }),
+                    0xF030 => instructions.push(CachedInstruction { opcode, execute: State::execute_Fx30 }),
                     0xF033 => instructions.push(CachedInstruction { opcode, execute: State::execute_Fx33 }),
+                    0xF03A => instructions.push(CachedInstruction { opcode, execute: State::execute_Fx3A }),
                     0xF055 => { instructions.push(CachedInstruction { opcode, execute: State::execute_Fx55 }); break 'outer },
                     0xF065 => instructions.push(CachedInstruction { opcode, execute: State::execute_Fx65 }),
+                    0xF075 => instructions.push(CachedInstruction { opcode, execute: State::execute_Fx75 }),
+                    0xF085 => instructions.push(CachedInstruction { opcode, execute: State::execute_Fx85 }),
                     _ => break 'outer,
                 },
                 _ => break 'outer,
@@ -132,9 +155,9 @@ impl Chip8 {
         }
 
         if instructions.is_empty() {
-            pc -= 2;
-            let opcode = (self.state.memory[pc as usize] as u16) << 8 | self.state.memory[pc as usize + 1] as u16;
-            panic!("Unknown opcode {opcode:04X} at {pc:#X}");
+            // Invalid opcode at the start of the block, its execution traps.
+            let opcode = Opcode((state.memory[block_pc as usize] as u16) << 8 | state.memory[block_pc as usize + 1] as u16);
+            instructions.push(CachedInstruction { opcode, execute: State::execute_invalid });
         }
 
         InstructionCache {
@@ -144,16 +167,108 @@ impl Chip8 {
         }
     }
 
-    /// Returns true if the current cache has been invalidated.
+    /// Invalidates the caches containing code in the given address range.
     fn invalidate_cache(&mut self, beg_addr: u16, end_addr: u16) {
-        for i in 0..self.interpreter_caches.len() {
-            if let Some(cache) = &self.interpreter_caches[i] {
-                if beg_addr >= cache.pc && beg_addr < cache.end_pc ||
-                    end_addr >= cache.pc && end_addr < cache.end_pc
-                {
-                    self.interpreter_caches[i] = None;
+        for i in 0..self.caches.len() {
+            if let Some(cache) = &self.caches[i] {
+                if beg_addr < cache.end_pc && end_addr >= cache.pc {
+                    self.caches[i] = None;
                 }
             }
         }
     }
 }
+
+impl Default for CachedInterpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ExecutionBackend for CachedInterpreter {
+    /// Executes a block of instructions.
+    ///
+    /// If the block is longer than `budget` instructions, a single instruction is interpreted instead.
+    fn step(&mut self, state: &mut State, budget: u64) {
+        if !state.is_cacheable(state.PC) {
+            let ret = state.interpret_instruction();
+            if ret > 1 {
+                self.stats.invalidations += 1;
+                self.invalidate_cache((ret >> 16) as u16, ret as u16);
+            }
+            return;
+        }
+
+        let cache_index = addr_to_index(state.PC);
+        let cache = if let Some(cache) = &self.caches[cache_index] {
+            self.stats.cache_hits += 1;
+            cache
+        } else {
+            self.stats.cache_misses += 1;
+            self.stats.blocks_compiled += 1;
+            let cache = Self::new_cache_block(state, &self.breakpoints);
+            self.caches[cache_index] = Some(cache);
+            self.caches[cache_index].as_ref().unwrap()
+        };
+
+        if cache.instructions.len() as u64 > budget {
+            let ret = state.interpret_instruction();
+            if ret > 1 {
+                self.stats.invalidations += 1;
+                self.invalidate_cache((ret >> 16) as u16, ret as u16);
+            }
+            return;
+        }
+
+        self.stats.blocks_executed += 1;
+
+        // Execute the cache.
+        let mut ret = 0;
+        for inst in &cache.instructions {
+            // #[cfg(debug_assertions)] println!("cached opcode {:04X} at {:#X}", inst.opcode, state.PC);
+
+            state.PC += 2;
+            state.cycles += 1;
+            let r = (inst.execute)(state, inst.opcode);
+            if r != 0 {
+                ret = r;
+                break;
+            }
+        }
+
+        if ret > 1 {
+            self.stats.invalidations += 1;
+            self.invalidate_cache((ret >> 16) as u16, ret as u16);
+        }
+    }
+
+    fn invalidate(&mut self, beg_addr: u16, end_addr: u16) {
+        self.invalidate_cache(beg_addr, end_addr);
+    }
+
+    fn reset(&mut self) {
+        self.caches.fill(None);
+    }
+
+    fn add_breakpoint(&mut self, pc: u16) {
+        self.breakpoints.insert(pc);
+        for cache in self.caches.iter_mut() {
+            if cache.as_ref().is_some_and(|cache| cache.pc < pc && pc < cache.end_pc) {
+                *cache = None;
+            }
+        }
+    }
+
+    fn remove_breakpoint(&mut self, pc: u16) {
+        self.breakpoints.remove(&pc);
+        for cache in self.caches.iter_mut() {
+            if cache.as_ref().is_some_and(|cache| cache.end_pc == pc) {
+                *cache = None;
+            }
+        }
+    }
+
+    fn stats(&self) -> Stats {
+        self.stats
+    }
+}
@@ -3,22 +3,28 @@
 //! It implements the trie lookup explained here:
 //! <https://web.archive.org/web/20210301060701/https://ps1.asuramaru.com/emulator-development/cached-interpreters>
 //!
-//! The way the caches works is [Chip8::interpreter_caches_2]'s size is 224, indexed with (PC - 0x200) >> 4.
+//! The way the caches works is [CachedInterpreter2::pools]'s size is 4064, indexed with (PC - 0x200) >> 4.
 //! This returns a pool of 16 caches indexed with (PC - 0x200) & 0xF.
 //! Instructions are added to the cache of a pool as long as their address is in this pool, so
 //! while PC & 0xF != 0.
 //! This makes cache invalidation O(1) on every memory write.
 
 use crate::{
-    Chip8,
+    ExecutionBackend,
     opcode::Opcode,
     State,
+    Stats,
     cached_interpreter::{
         CachedInstruction,
         InstructionCache,
     },
 };
 
+use alloc::boxed::Box;
+use alloc::collections::BTreeSet;
+use alloc::vec;
+use alloc::vec::Vec;
+
 const SUBCACHE_SHIFT: u16 = 4;
 pub(super) const SUBCACHE_SIZE: usize = 1 << SUBCACHE_SHIFT as usize;
 const SUBCACHE_MASK: u16 = SUBCACHE_SIZE as u16 - 1;
@@ -26,82 +32,91 @@ const SUBCACHE_MASK: u16 = SUBCACHE_SIZE as u16 - 1;
 /// Converts the given Chip8 address to its instruction cache index.
 #[inline(always)]
 pub(super) const fn addr_to_index(addr: u16) -> usize {
-    addr as usize - State::INITIAL_PC >> SUBCACHE_SHIFT
+    (addr as usize - State::INITIAL_PC) >> SUBCACHE_SHIFT
 }
 
 /// Converts the given Chip8 address to its index in the cache.
 #[inline(always)]
 const fn index_in_subcache(addr: u16) -> usize {
-    addr as usize - State::INITIAL_PC & SUBCACHE_MASK as usize
+    (addr as usize - State::INITIAL_PC) & SUBCACHE_MASK as usize
 }
 
-impl Chip8 {
-    /// Executes a block of instructions using the cached interpreter variant 2.
-    pub fn cached_interpreter_2(&mut self) {
-        let pool_index = addr_to_index(self.state.PC);
-        let pool = if let Some(pool) = &mut self.interpreter_caches_2[pool_index] {
-            pool
-        } else {
-            let pool = [Chip8::EMPTY_INTERPRETER_CACHES; SUBCACHE_SIZE];
-            self.interpreter_caches_2[pool_index] = Some(pool);
-            self.interpreter_caches_2[pool_index].as_mut().unwrap()
-        };
+type Pool = [Option<InstructionCache>; SUBCACHE_SIZE];
 
-        let cache_index = index_in_subcache(self.state.PC);
-        let cache = if let Some(cache) = &pool[cache_index] {
-            cache
-        } else {
-            let cache = Self::new_cache_block_2(self.state.PC, &self.state.memory);
-            pool[cache_index] = Some(cache);
-            pool[cache_index].as_ref().unwrap()
-        };
+/// The cached interpreter variant 2, caching blocks of instructions in pools of 16 addresses.
+pub struct CachedInterpreter2 {
+    pools: Box<[Option<Pool>]>,
+    stats: Stats,
+    /// Blocks end before these addresses.
+    breakpoints: BTreeSet<u16>,
+}
 
-        // Execute the cache.
-        let mut ret = 0;
-        for inst in &cache.instructions {
-            // #[cfg(debug_assertions)] println!("cached 2 opcode {:04X} at {:#X}", inst.opcode, self.state.PC);
+impl CachedInterpreter2 {
+    const POOLS_LEN: usize = addr_to_index((State::MEMORY_SIZE - 1) as u16) + 1;
+    const EMPTY_CACHE: Option<InstructionCache> = None;
+    const EMPTY_POOL: Option<Pool> = None;
 
-            self.state.PC += 2;
-            let r = (inst.execute)(&mut self.state, inst.opcode);
-            if r != 0 {
-                ret = r;
-                break;
-            }
+    /// Creates a new cached interpreter variant 2 with empty caches.
+    pub fn new() -> Self {
+        Self {
+            pools: vec![Self::EMPTY_POOL; Self::POOLS_LEN].into_boxed_slice(),
+            stats: Stats::default(),
+            breakpoints: BTreeSet::new(),
         }
+    }
 
+    /// Invalidates the caches if requested by the return value of an execution method.
+    fn invalidate_cache_2(&mut self, ret: u32) {
         if ret > 1 {
-            // Invalidate caches.
-            let beg = addr_to_index((ret >> 16) as u16);
-            let end = addr_to_index(ret as u16);
-            for addr in beg..=end {
-                self.interpreter_caches_2[addr] = None;
+            // Invalidate caches, including the instruction overlapping the first byte. There is no cache below the initial PC.
+            let beg = ((ret >> 16) as u16).saturating_sub(1).max(State::INITIAL_PC as u16);
+            let end = ret as u16;
+            if beg <= end {
+                for addr in addr_to_index(beg)..=addr_to_index(end) {
+                    self.pools[addr] = None;
+                }
             }
         }
-
-        self.handle_timers();
     }
 
-    /// Creates a new cache at the current PC. The state is not modified.
-    fn new_cache_block_2(block_pc: u16, memory: &[u8]) -> InstructionCache {
+    /// Creates a new cache at the current PC, ending before the given breakpoints. The state is not modified.
+    fn new_cache_block_2(block_pc: u16, state: &State, breakpoints: &BTreeSet<u16>) -> InstructionCache {
+        let memory = &state.memory;
         let mut pc = block_pc;
         let mut instructions = Vec::new();
 
         'outer: loop {
+            if !state.is_cacheable(pc) || pc != block_pc && breakpoints.contains(&pc) {
+                break;
+            }
+
             let opcode = Opcode((memory[pc as usize] as u16) << 8 | memory[pc as usize + 1] as u16);
             // #[cfg(debug_assertions)] println!("caching 2 opcode {opcode:04X} at {pc:#X}");
             pc += 2;
 
             match opcode.0 >> 12 & 0xF {
                 0x0 => match opcode.0 {
+                    0x00C0..=0x00CF => instructions.push(CachedInstruction { opcode, execute: State::execute_00Cn }),
+                    0x00D0..=0x00DF => instructions.push(CachedInstruction { opcode, execute: State::execute_00Dn }),
                     0x00E0 => instructions.push(CachedInstruction {opcode, execute: State::execute_00E0 }),
                     0x00EE => { instructions.push(CachedInstruction { opcode, execute: State::execute_00EE }); break 'outer; },
+                    0x00FB => instructions.push(CachedInstruction { opcode, execute: State::execute_00FB }),
+                    0x00FC => instructions.push(CachedInstruction { opcode, execute: State::execute_00FC }),
+                    0x00FD => { instructions.push(CachedInstruction { opcode, execute: State::execute_00FD }); break 'outer; },
+                    0x00FE => instructions.push(CachedInstruction { opcode, execute: State::execute_00FE }),
+                    0x00FF => instructions.push(CachedInstruction { opcode, execute: State::execute_00FF }),
                     _ => break 'outer,
                 },
                 0x1 => { instructions.push(CachedInstruction { opcode, execute: State::execute_1nnn }); break 'outer; },
                 0x2 => { instructions.push(CachedInstruction { opcode, execute: State::execute_2nnn }); break 'outer; },
                 0x3 => instructions.push(CachedInstruction { opcode, execute: State::execute_3xkk }),
                 0x4 => instructions.push(CachedInstruction { opcode, execute: State::execute_4xkk }),
-                0x5 => instructions.push(CachedInstruction { opcode, execute: State::execute_5xy0 }),
+                0x5 => match opcode.0 & 0xF00F {
+                    0x5000 => instructions.push(CachedInstruction { opcode, execute: State::execute_5xy0 }),
+                    0x5002 => { instructions.push(CachedInstruction { opcode, execute: State::execute_5xy2 }); break 'outer },
+                    0x5003 => instructions.push(CachedInstruction { opcode, execute: State::execute_5xy3 }),
+                    _ => break 'outer,
+                },
                 0x6 => instructions.push(CachedInstruction { opcode, execute: State::execute_6xkk }),
                 0x7 => instructions.push(CachedInstruction { opcode, execute: State::execute_7xkk }),
                 0x8 => {
@@ -118,7 +133,10 @@ impl Chip8 {
                         _ => break 'outer,
                     }
                 },
-                0x9 => instructions.push(CachedInstruction { opcode, execute: State::execute_9xy0 }),
+                0x9 => match opcode.0 & 0xF00F {
+                    0x9000 => instructions.push(CachedInstruction { opcode, execute: State::execute_9xy0 }),
+                    _ => break 'outer,
+                },
                 0xA => instructions.push(CachedInstruction { opcode, execute: State::execute_Annn }),
                 0xB => { instructions.push(CachedInstruction { opcode, execute: State::execute_Bnnn }); break 'outer; },
                 0xC => instructions.push(CachedInstruction { opcode, execute: State::execute_Cxkk }),
@@ -129,6 +147,12 @@ impl Chip8 {
                     _ => break 'outer,
                 },
                 0xF => match opcode.0 & 0xF0FF {
+                    0xF000 if opcode.0 == 0xF000 => {
+                        instructions.push(CachedInstruction { opcode, execute: State::execute_F000 });
+                        pc += 2; // Skip the address word.
+                    },
+                    0xF001 => instructions.push(CachedInstruction { opcode, execute: State::execute_Fn01 }),
+                    0xF002 if opcode.0 == 0xF002 => instructions.push(CachedInstruction { opcode, execute: State::execute_F002 }),
                     0xF007 => instructions.push(CachedInstruction { opcode, execute: State::execute_Fx07 }),
                     // Wait Key: interrupt the current cache and go to a new cache starting at the wait key instruction.
                     0xF00A => { instructions.push(CachedInstruction { opcode, execute: State::execute_Fx0A }); break 'outer },
@@ -136,23 +160,28 @@ impl Chip8 {
                     0xF018 => instructions.push(CachedInstruction { opcode, execute: State::execute_Fx18 }),
                     0xF01E => instructions.push(CachedInstruction { opcode, execute: State::execute_Fx1E }),
                     0xF029 => instructions.push(CachedInstruction { opcode, execute: State::execute_Fx29 }),
+                    0xF030 => instructions.push(CachedInstruction { opcode, execute: State::execute_Fx30 }),
                     0xF033 => instructions.push(CachedInstruction { opcode, execute: State::execute_Fx33 }),
+                    0xF03A => instructions.push(CachedInstruction { opcode, execute: State::execute_Fx3A }),
                     0xF055 => { instructions.push(CachedInstruction { opcode, execute: State::execute_Fx55 }); break 'outer },
                     0xF065 => instructions.push(CachedInstruction { opcode, execute: State::execute_Fx65 }),
+                    0xF075 => instructions.push(CachedInstruction { opcode, execute: State::execute_Fx75 }),
+                    0xF085 => instructions.push(CachedInstruction { opcode, execute: State::execute_Fx85 }),
                     _ => break 'outer,
                 },
                 _ => break 'outer,
             };
 
-            if index_in_subcache(pc) == 0 {
+            // Stop at the end of the pool. F000 NNNN is 4 bytes long so it can step over the first address of the next pool.
+            if addr_to_index(pc) != addr_to_index(block_pc) {
                 break 'outer;
             }
         }
 
         if instructions.is_empty() {
-            pc -= 2;
-            let opcode = (memory[pc as usize] as u16) << 8 | memory[pc as usize + 1] as u16;
-            panic!("Unknown opcode {opcode:04X} at {pc:#X}");
+            // Invalid opcode at the start of the block, its execution traps.
+            let opcode = Opcode((memory[block_pc as usize] as u16) << 8 | memory[block_pc as usize + 1] as u16);
+            instructions.push(CachedInstruction { opcode, execute: State::execute_invalid });
         }
 
         InstructionCache {
@@ -162,3 +191,100 @@ impl Chip8 {
         }
     }
 }
+
+impl Default for CachedInterpreter2 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ExecutionBackend for CachedInterpreter2 {
+    /// Executes a block of instructions.
+    ///
+    /// If the block is longer than `budget` instructions, a single instruction is interpreted instead.
+    fn step(&mut self, state: &mut State, budget: u64) {
+        if !state.is_cacheable(state.PC) {
+            let ret = state.interpret_instruction();
+            self.stats.invalidations += (ret > 1) as u64;
+            self.invalidate_cache_2(ret);
+            return;
+        }
+
+        let pool_index = addr_to_index(state.PC);
+        let pool = if let Some(pool) = &mut self.pools[pool_index] {
+            pool
+        } else {
+            let pool = [Self::EMPTY_CACHE; SUBCACHE_SIZE];
+            self.pools[pool_index] = Some(pool);
+            self.pools[pool_index].as_mut().unwrap()
+        };
+
+        let cache_index = index_in_subcache(state.PC);
+        let cache = if let Some(cache) = &pool[cache_index] {
+            self.stats.cache_hits += 1;
+            cache
+        } else {
+            self.stats.cache_misses += 1;
+            self.stats.blocks_compiled += 1;
+            let cache = Self::new_cache_block_2(state.PC, state, &self.breakpoints);
+            pool[cache_index] = Some(cache);
+            pool[cache_index].as_ref().unwrap()
+        };
+
+        if cache.instructions.len() as u64 > budget {
+            let ret = state.interpret_instruction();
+            self.stats.invalidations += (ret > 1) as u64;
+            self.invalidate_cache_2(ret);
+            return;
+        }
+
+        self.stats.blocks_executed += 1;
+
+        // Execute the cache.
+        let mut ret = 0;
+        for inst in &cache.instructions {
+            // #[cfg(debug_assertions)] println!("cached 2 opcode {:04X} at {:#X}", inst.opcode, state.PC);
+
+            state.PC += 2;
+            state.cycles += 1;
+            let r = (inst.execute)(state, inst.opcode);
+            if r != 0 {
+                ret = r;
+                break;
+            }
+        }
+
+        self.stats.invalidations += (ret > 1) as u64;
+        self.invalidate_cache_2(ret);
+    }
+
+    fn invalidate(&mut self, beg_addr: u16, end_addr: u16) {
+        self.invalidate_cache_2((beg_addr as u32) << 16 | end_addr as u32);
+    }
+
+    fn reset(&mut self) {
+        self.pools.fill(None);
+    }
+
+    fn add_breakpoint(&mut self, pc: u16) {
+        self.breakpoints.insert(pc);
+        for cache in self.pools.iter_mut().flatten().flat_map(|pool| pool.iter_mut()) {
+            if cache.as_ref().is_some_and(|cache| cache.pc < pc && pc < cache.end_pc) {
+                *cache = None;
+            }
+        }
+    }
+
+    fn remove_breakpoint(&mut self, pc: u16) {
+        self.breakpoints.remove(&pc);
+        for cache in self.pools.iter_mut().flatten().flat_map(|pool| pool.iter_mut()) {
+            if cache.as_ref().is_some_and(|cache| cache.end_pc == pc) {
+                *cache = None;
+            }
+        }
+    }
+
+    fn stats(&self) -> Stats {
+        self.stats
+    }
+}
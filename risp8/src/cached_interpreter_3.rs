@@ -1,7 +1,7 @@
 //! Cached interpreter, idea 3.
 //!
-//! This cached interpreter is the simplest one: it maintains a look-up table [Chip8::interpreter_caches_3] indexed
-//! using the instuction's address `(self.state.PC - Self::INITIAL_PC) as usize >> 1` and executing what's there if
+//! This cached interpreter is the simplest one: it maintains a look-up table [CachedInterpreter3::caches] indexed
+//! using the instuction's address `state.PC - State::INITIAL_PC` and executing what's there if
 //! present, or decode and cache the instruction to be executed.
 //!
 //! The advantages are a O(1) instruction look-up and cache invalidation, as self-modifying code only invalidates
@@ -15,46 +15,98 @@
 //! slower than this array check.
 
 use crate::{
-    Chip8,
+    ExecutionBackend,
     opcode::Opcode,
     State,
+    Stats,
     cached_interpreter::{
         addr_to_index,
         CachedInstruction,
     },
 };
 
-impl Chip8 {
-    /// Executes an instruction using the cached interpreter variant 3.
-    pub fn cached_interpreter_3(&mut self) {
-        let cache_index = addr_to_index(self.state.PC);
-        let pc = self.state.PC;
-        self.state.PC += 2;
+use alloc::boxed::Box;
+use alloc::vec;
 
-        let ret = if let Some(inst) = self.interpreter_caches_3[cache_index] {
-            // #[cfg(debug_assertions)] println!("cached 3 opcode {:04X} at {pc:#X}", inst.opcode);
-            (inst.execute)(&mut self.state, inst.opcode)
+/// The cached interpreter variant 3, caching each instruction individually.
+pub struct CachedInterpreter3 {
+    caches: Box<[Option<CachedInstruction>]>,
+    stats: Stats,
+}
+
+impl CachedInterpreter3 {
+    /// Creates a new cached interpreter variant 3 with empty caches.
+    pub fn new() -> Self {
+        Self {
+            caches: vec![None; State::MAX_PROGRAM_LEN].into_boxed_slice(),
+            stats: Stats::default(),
+        }
+    }
+
+    /// Invalidates the cached instructions in the given address range.
+    fn invalidate_cache_3(&mut self, beg_addr: u16, end_addr: u16) {
+        // Include the instruction overlapping the first byte. There is no cache below the initial PC.
+        let beg = beg_addr.saturating_sub(1).max(State::INITIAL_PC as u16);
+        if beg <= end_addr {
+            for addr in addr_to_index(beg)..=addr_to_index(end_addr) {
+                self.caches[addr] = None;
+            }
+        }
+    }
+}
+
+impl Default for CachedInterpreter3 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ExecutionBackend for CachedInterpreter3 {
+    /// Executes a single instruction.
+    fn step(&mut self, state: &mut State, _: u64) {
+        let ret = if !state.is_cacheable(state.PC) {
+            state.interpret_instruction()
         } else {
-            let opcode = Opcode((self.state.memory[pc as usize] as u16) << 8 | self.state.memory[pc as usize + 1] as u16);
-            let execute = State::ILUT[opcode.0 as usize];
-            self.interpreter_caches_3[cache_index] = Some(CachedInstruction {
-                opcode,
-                execute,
-            });
-
-            // #[cfg(debug_assertions)] println!("caching 3 opcode {opcode:04X} at {pc:#X}");
-            (execute)(&mut self.state, opcode)
+            let cache_index = addr_to_index(state.PC);
+            let pc = state.PC;
+            state.PC += 2;
+            state.cycles += 1;
+            self.stats.blocks_executed += 1;
+
+            if let Some(inst) = self.caches[cache_index] {
+                self.stats.cache_hits += 1;
+                // #[cfg(debug_assertions)] println!("cached 3 opcode {:04X} at {pc:#X}", inst.opcode);
+                (inst.execute)(state, inst.opcode)
+            } else {
+                self.stats.cache_misses += 1;
+                self.stats.blocks_compiled += 1;
+                let opcode = Opcode((state.memory[pc as usize] as u16) << 8 | state.memory[pc as usize + 1] as u16);
+                let execute = State::ILUT[opcode.0 as usize];
+                self.caches[cache_index] = Some(CachedInstruction {
+                    opcode,
+                    execute,
+                });
+
+                // #[cfg(debug_assertions)] println!("caching 3 opcode {opcode:04X} at {pc:#X}");
+                (execute)(state, opcode)
+            }
         };
 
         if ret > 1 {
-            // Invalidate caches.
-            let beg = addr_to_index((ret >> 16) as u16);
-            let end = addr_to_index(ret as u16);
-            for addr in beg..=end {
-                self.interpreter_caches_3[addr] = None;
-            }
+            self.stats.invalidations += 1;
+            self.invalidate_cache_3((ret >> 16) as u16, ret as u16);
         }
+    }
+
+    fn invalidate(&mut self, beg_addr: u16, end_addr: u16) {
+        self.invalidate_cache_3(beg_addr, end_addr);
+    }
+
+    fn reset(&mut self) {
+        self.caches.fill(None);
+    }
 
-        self.handle_timers();
+    fn stats(&self) -> Stats {
+        self.stats
     }
 }
@@ -0,0 +1,417 @@
+//! Batched environment to run many cores in lockstep, for reinforcement learning.
+//!
+//! Every core runs headless with the synchronous API. Steps are executed in parallel on a fixed number of threads, the
+//! cores being split in as many groups. The calling thread executes the first group, and worker threads created with
+//! the environment execute the others.
+
+use crate::{Chip8, Chip8Builder, LoadError, Receiver, SaveStateError, Screen, Sender, State, StopReason};
+
+use kanal::bounded;
+
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+use std::thread;
+
+/// A big-endian value in the memory of the cores.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MemoryValue {
+    /// The byte at the given address.
+    U8(u16),
+    /// The 16-bit value at the given address.
+    U16(u16),
+}
+
+impl MemoryValue {
+    /// Reads the value in the given state. Bytes outside of memory read as 0.
+    pub fn read(self, state: &State) -> u32 {
+        let byte = |address: u16| state.memory().get(address as usize).copied().unwrap_or(0) as u32;
+        match self {
+            MemoryValue::U8(address) => byte(address),
+            MemoryValue::U16(address) => byte(address) << 8 | byte(address.wrapping_add(1)),
+        }
+    }
+}
+
+/// A condition that ends an episode.
+///
+/// Episodes also end when the program exits or traps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DoneCondition {
+    /// The value is equal to the given one.
+    Equal(MemoryValue, u32),
+    /// The value is less than or equal to the given one.
+    LessOrEqual(MemoryValue, u32),
+    /// The episode lasted the given number of steps.
+    MaxSteps(u64),
+}
+
+/// Builder to create a [VecEnv].
+#[derive(Clone, Debug)]
+pub struct VecEnvBuilder {
+    core: Chip8Builder,
+    instances: usize,
+    frames_per_step: u32,
+    rewards: Vec<(MemoryValue, f32)>,
+    done_conditions: Vec<DoneCondition>,
+    threads: Option<usize>,
+}
+
+impl VecEnvBuilder {
+    /// Creates a new builder for `instances` cores created by the given builder.
+    ///
+    /// By default, a step lasts one frame, the reward is always 0, episodes only end when the program exits or traps,
+    /// and steps use as many threads as the available parallelism.
+    pub fn new(core: Chip8Builder, instances: usize) -> Self {
+        Self {
+            core,
+            instances,
+            frames_per_step: 1,
+            rewards: Vec::new(),
+            done_conditions: Vec::new(),
+            threads: None,
+        }
+    }
+
+    /// Sets the number of frames executed by each step with the same keys. 0 is treated as 1.
+    pub fn frames_per_step(mut self, frames_per_step: u32) -> Self {
+        self.frames_per_step = frames_per_step.max(1);
+        self
+    }
+
+    /// Adds the variation of the given value during a step, multiplied by `scale`, to the reward of the step.
+    pub fn reward(mut self, value: MemoryValue, scale: f32) -> Self {
+        self.rewards.push((value, scale));
+        self
+    }
+
+    /// Adds a condition that ends the episodes.
+    pub fn done_condition(mut self, condition: DoneCondition) -> Self {
+        self.done_conditions.push(condition);
+        self
+    }
+
+    /// Sets the number of threads executing the steps. 0 is treated as 1.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = Some(threads.max(1));
+        self
+    }
+
+    /// Creates the environment, with every core reset to its power-on state.
+    pub fn build(self) -> Result<VecEnv, LoadError> {
+        let threads = self.threads.unwrap_or_else(|| thread::available_parallelism().map_or(1, NonZeroUsize::get));
+        let group_len = self.instances.div_ceil(threads).max(1);
+
+        let mut groups: Vec<Group> = Vec::with_capacity(self.instances.div_ceil(group_len));
+        for index in 0..self.instances {
+            if index % group_len == 0 {
+                groups.push(Group::default());
+            }
+
+            let core = self.core.clone().pacing(false).rewind(0, 1).build_sync()?;
+            let values = self.rewards.iter().map(|(value, _)| value.read(core.state())).collect();
+            let group = groups.last_mut().unwrap();
+            group.instances.push(Instance { core, values, steps: 0 });
+            group.observations.resize(group.instances.len() * VecEnv::OBSERVATION_SIZE, 0);
+            group.rewards.push(0.0);
+            group.dones.push(false);
+        }
+
+        let snapshot = groups.first().map_or_else(Vec::new, |group| group.instances[0].core.save_state());
+        let workers = (1..groups.len()).map(|_| Worker::new()).collect();
+
+        let mut env = VecEnv {
+            observations: vec![0; self.instances * VecEnv::OBSERVATION_SIZE],
+            rewards: vec![0.0; self.instances],
+            dones: vec![false; self.instances],
+            groups,
+            group_len,
+            workers,
+            config: Arc::new(StepConfig {
+                frames: self.frames_per_step,
+                rewards: self.rewards,
+                done_conditions: self.done_conditions,
+                snapshot,
+            }),
+        };
+        env.reset();
+
+        Ok(env)
+    }
+}
+
+/// A core of the environment.
+struct Instance {
+    core: Chip8,
+    /// The values of the reward at the end of the previous step.
+    values: Vec<u32>,
+    /// The number of steps since the start of the episode.
+    steps: u64,
+}
+
+/// The result of a step of a [VecEnv].
+#[derive(Clone, Copy, Debug)]
+pub struct Step<'a> {
+    /// The screens of the cores, see [VecEnv::observations].
+    pub observations: &'a [u8],
+    /// The reward of each core during the step.
+    pub rewards: &'a [f32],
+    /// True for the cores whose episode ended during the step. They have been reset from the snapshot.
+    pub dones: &'a [bool],
+}
+
+/// Many cores stepped together, with rewards and episode ends read from their memory.
+///
+/// ```no_run
+/// use risp8::{Chip8Builder, DoneCondition, MemoryValue, VecEnvBuilder};
+///
+/// let rom = std::fs::read("game.ch8").unwrap();
+/// let mut env = VecEnvBuilder::new(Chip8Builder::new(rom), 64)
+///     .frames_per_step(4)
+///     .reward(MemoryValue::U8(0x300), 1.0)
+///     .done_condition(DoneCondition::Equal(MemoryValue::U8(0x301), 0))
+///     .build()
+///     .unwrap();
+///
+/// let actions = vec![1 << 5; env.len()]; // Hold key 5 on every core.
+/// let step = env.step(&actions);
+/// ```
+pub struct VecEnv {
+    observations: Vec<u8>,
+    rewards: Vec<f32>,
+    dones: Vec<bool>,
+    /// The cores split in the groups executed by each thread.
+    groups: Vec<Group>,
+    /// The number of cores in each group, except the last one which may have less.
+    group_len: usize,
+    /// The threads executing every group but the first one.
+    workers: Vec<Worker>,
+    config: Arc<StepConfig>,
+}
+
+impl VecEnv {
+    /// The size in bytes of the observation of a core, a high resolution screen.
+    pub const OBSERVATION_SIZE: usize = Screen::HIRES_WIDTH * Screen::HIRES_HEIGHT;
+
+    /// Returns the number of cores.
+    pub fn len(&self) -> usize {
+        self.rewards.len()
+    }
+
+    /// Returns true if there is no core.
+    pub fn is_empty(&self) -> bool {
+        self.rewards.is_empty()
+    }
+
+    /// Returns the given core.
+    pub fn core(&self, index: usize) -> &Chip8 {
+        &self.groups[index / self.group_len].instances[index % self.group_len].core
+    }
+
+    /// Returns the given core to modify it.
+    pub fn core_mut(&mut self, index: usize) -> &mut Chip8 {
+        &mut self.groups[index / self.group_len].instances[index % self.group_len].core
+    }
+
+    /// Sets the save state the cores are reset to. See [Chip8::save_state].
+    pub fn set_snapshot(&mut self, snapshot: &[u8]) -> Result<(), SaveStateError> {
+        State::load(snapshot)?;
+        Arc::make_mut(&mut self.config).snapshot = snapshot.to_vec();
+        Ok(())
+    }
+
+    /// Resets every core from the snapshot and returns their observations.
+    pub fn reset(&mut self) -> &[u8] {
+        for index in 0..self.len() {
+            self.reset_instance(index);
+        }
+
+        &self.observations
+    }
+
+    /// Resets the given core from the snapshot.
+    pub fn reset_instance(&mut self, index: usize) {
+        let instance = &mut self.groups[index / self.group_len].instances[index % self.group_len];
+        let observation = &mut self.observations[index * Self::OBSERVATION_SIZE..(index + 1) * Self::OBSERVATION_SIZE];
+        reset(instance, &self.config.snapshot, &self.config.rewards, observation);
+        self.rewards[index] = 0.0;
+        self.dones[index] = false;
+    }
+
+    /// Returns the screens of the cores after the last step, [OBSERVATION_SIZE](Self::OBSERVATION_SIZE) bytes each.
+    ///
+    /// Each byte is the color index of a pixel, row by row. Low resolution screens are scaled 2x.
+    pub fn observations(&self) -> &[u8] {
+        &self.observations
+    }
+
+    /// Executes a step on every core with the given keys held, and returns the result.
+    ///
+    /// `actions` contains a key mask for each core, bit n being set if key n is held. The cores whose episode ended
+    /// are reset from the snapshot.
+    ///
+    /// Panics if the number of actions is not the number of cores.
+    pub fn step(&mut self, actions: &[u16]) -> Step<'_> {
+        assert_eq!(actions.len(), self.len(), "Expected one action per core");
+
+        for (group, actions) in self.groups.iter_mut().zip(actions.chunks(self.group_len)) {
+            group.actions.clear();
+            group.actions.extend_from_slice(actions);
+        }
+
+        if let Some((first, others)) = self.groups.split_first_mut() {
+            for (group, worker) in others.iter_mut().zip(&self.workers) {
+                let job = (core::mem::take(group), Arc::clone(&self.config));
+                worker.jobs.send(job).expect("A worker thread of the environment panicked");
+            }
+            first.step(&self.config);
+            for (group, worker) in others.iter_mut().zip(&self.workers) {
+                *group = worker.results.recv().expect("A worker thread of the environment panicked");
+            }
+        }
+
+        let observations = self.observations.chunks_mut(self.group_len * Self::OBSERVATION_SIZE);
+        let rewards = self.rewards.chunks_mut(self.group_len);
+        let dones = self.dones.chunks_mut(self.group_len);
+        for (((group, observations), rewards), dones) in self.groups.iter().zip(observations).zip(rewards).zip(dones) {
+            observations.copy_from_slice(&group.observations);
+            rewards.copy_from_slice(&group.rewards);
+            dones.copy_from_slice(&group.dones);
+        }
+
+        Step {
+            observations: &self.observations,
+            rewards: &self.rewards,
+            dones: &self.dones,
+        }
+    }
+}
+
+/// The cores executed by a thread with their actions, and their observations, rewards and dones after a step.
+#[derive(Default)]
+struct Group {
+    instances: Vec<Instance>,
+    actions: Vec<u16>,
+    observations: Vec<u8>,
+    rewards: Vec<f32>,
+    dones: Vec<bool>,
+}
+
+impl Group {
+    /// Executes a step on every core of the group.
+    fn step(&mut self, config: &StepConfig) {
+        let observations = self.observations.chunks_exact_mut(VecEnv::OBSERVATION_SIZE);
+        for (i, (instance, observation)) in self.instances.iter_mut().zip(observations).enumerate() {
+            (self.rewards[i], self.dones[i]) = config.step(instance, self.actions[i], observation);
+        }
+    }
+}
+
+/// A thread executing the steps of the groups it receives, until the environment is dropped.
+struct Worker {
+    jobs: Sender<(Group, Arc<StepConfig>)>,
+    results: Receiver<Group>,
+}
+
+impl Worker {
+    fn new() -> Self {
+        let (jobs, worker_jobs) = bounded::<(Group, Arc<StepConfig>)>(1);
+        let (worker_results, results) = bounded(1);
+
+        thread::spawn(move || {
+            while let Ok((mut group, config)) = worker_jobs.recv() {
+                group.step(&config);
+                if worker_results.send(group).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self { jobs, results }
+    }
+}
+
+/// The settings of the environment shared by the threads executing a step.
+#[derive(Clone)]
+struct StepConfig {
+    frames: u32,
+    rewards: Vec<(MemoryValue, f32)>,
+    /// The conditions that end the episodes.
+    done_conditions: Vec<DoneCondition>,
+    /// The save state the cores are reset to.
+    snapshot: Vec<u8>,
+}
+
+impl StepConfig {
+    /// Executes a step on the given core, writing its observation. Returns the reward and if the episode ended.
+    fn step(&self, instance: &mut Instance, action: u16, observation: &mut [u8]) -> (f32, bool) {
+        for key in 0..16 {
+            instance.core.set_key(key, action & 1 << key != 0);
+        }
+
+        let mut done = false;
+        for _ in 0..self.frames {
+            if matches!(instance.core.run_frame(), Some(StopReason::Exited | StopReason::Trap(_))) {
+                done = true;
+                break;
+            }
+        }
+        instance.steps += 1;
+
+        let state = instance.core.state();
+        let mut reward = 0.0;
+        for ((value, scale), previous) in self.rewards.iter().zip(&mut instance.values) {
+            let current = value.read(state);
+            reward += (current as f32 - *previous as f32) * scale;
+            *previous = current;
+        }
+
+        done |= self.done_conditions.iter().any(|condition| match *condition {
+            DoneCondition::Equal(value, expected) => value.read(state) == expected,
+            DoneCondition::LessOrEqual(value, max) => value.read(state) <= max,
+            DoneCondition::MaxSteps(steps) => instance.steps >= steps,
+        });
+
+        if done {
+            reset(instance, &self.snapshot, &self.rewards, observation);
+        } else {
+            write_observation(instance.core.screen(), observation);
+        }
+
+        (reward, done)
+    }
+}
+
+/// Resets the given core from the snapshot and writes its observation.
+fn reset(instance: &mut Instance, snapshot: &[u8], rewards: &[(MemoryValue, f32)], observation: &mut [u8]) {
+    // The snapshot has been validated when set.
+    let _ = instance.core.load_state(snapshot);
+    for key in 0..16 {
+        instance.core.set_key(key, false);
+    }
+
+    let state = instance.core.state();
+    for ((value, _), previous) in rewards.iter().zip(&mut instance.values) {
+        *previous = value.read(state);
+    }
+    instance.steps = 0;
+
+    write_observation(instance.core.screen(), observation);
+}
+
+/// Writes the screen in high resolution in the given observation.
+fn write_observation(screen: &Screen, observation: &mut [u8]) {
+    if screen.is_hires() {
+        for (dst, row) in observation.chunks_exact_mut(Screen::HIRES_WIDTH).zip(screen.rows()) {
+            dst.copy_from_slice(row);
+        }
+    } else {
+        for (dst, row) in observation.chunks_exact_mut(2 * Screen::HIRES_WIDTH).zip(screen.rows()) {
+            let (top, bottom) = dst.split_at_mut(Screen::HIRES_WIDTH);
+            for (x, &pixel) in row.iter().enumerate() {
+                top[2 * x] = pixel;
+                top[2 * x + 1] = pixel;
+            }
+            bottom.copy_from_slice(top);
+        }
+    }
+}
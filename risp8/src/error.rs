@@ -0,0 +1,229 @@
+use core::fmt;
+
+/// Errors that can occur when loading a ROM.
+#[derive(Debug)]
+pub enum LoadError {
+    /// The ROM file could not be read.
+    #[cfg(feature = "std")]
+    Io(std::io::Error),
+    /// The program does not fit in memory after its load address.
+    ProgramTooLarge {
+        /// The size of the program in bytes.
+        len: usize,
+        /// The maximum size of a program for the selected configuration.
+        max_len: usize,
+    },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            #[cfg(feature = "std")]
+            LoadError::Io(e) => write!(f, "Failed to read ROM: {e}"),
+            LoadError::ProgramTooLarge { len, max_len } => {
+                write!(f, "Input program ({len} bytes) exceeds memory size ({max_len} bytes)")
+            },
+        }
+    }
+}
+
+impl core::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            #[cfg(feature = "std")]
+            LoadError::Io(e) => Some(e),
+            LoadError::ProgramTooLarge { .. } => None,
+        }
+    }
+}
+
+/// Errors returned by the setters of [State](crate::State) when a value is out of range.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StateError {
+    /// The register or stack index is greater than 15.
+    InvalidIndex(usize),
+    /// The stack pointer is greater than 16.
+    InvalidStackPointer(usize),
+    /// The `len` bytes at `address` go past the end of memory.
+    AddressOutOfRange {
+        address: u16,
+        len: usize,
+    },
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            StateError::InvalidIndex(index) => write!(f, "Index {index} is greater than 15"),
+            StateError::InvalidStackPointer(sp) => write!(f, "Stack pointer {sp} is greater than 16"),
+            StateError::AddressOutOfRange { address, len } => {
+                write!(f, "Access of {len} bytes at {address:#X} goes past the end of memory")
+            },
+        }
+    }
+}
+
+impl core::error::Error for StateError {}
+
+/// Errors returned by [Font::with_addresses](crate::Font::with_addresses) for an invalid layout.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FontError {
+    /// One of the fonts does not fit below 0x200.
+    OutOfRange,
+    /// The small and big fonts overlap.
+    Overlapping,
+}
+
+impl fmt::Display for FontError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            FontError::OutOfRange => write!(f, "Fonts must be located below 0x200"),
+            FontError::Overlapping => write!(f, "Fonts must not overlap"),
+        }
+    }
+}
+
+impl core::error::Error for FontError {}
+
+/// Errors that can occur when loading a save state.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SaveStateError {
+    /// The data is not a risp8 save state.
+    InvalidFormat,
+    /// The save state has been created by an incompatible version of risp8.
+    UnsupportedVersion(u16),
+    /// The save state ends before all of its fields.
+    Truncated,
+    /// The save state contains invalid values.
+    Corrupted,
+}
+
+impl fmt::Display for SaveStateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            SaveStateError::InvalidFormat => write!(f, "Not a risp8 save state"),
+            SaveStateError::UnsupportedVersion(version) => write!(f, "Unsupported save state version {version}"),
+            SaveStateError::Truncated => write!(f, "Save state is truncated"),
+            SaveStateError::Corrupted => write!(f, "Save state is corrupted"),
+        }
+    }
+}
+
+impl core::error::Error for SaveStateError {}
+
+/// Errors that can occur when loading or playing a movie.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MovieError {
+    /// The given line of the movie is invalid.
+    InvalidFormat {
+        line: usize,
+    },
+    /// The movie does not have the given header field.
+    MissingField(&'static str),
+    /// The movie has been created by an incompatible version of risp8.
+    UnsupportedVersion(u32),
+    /// The movie has been recorded with another ROM.
+    RomMismatch {
+        /// The hash of the ROM of the movie.
+        expected: u64,
+        /// The hash of the loaded ROM.
+        actual: u64,
+    },
+    /// The program does not fit in memory with the platform of the movie.
+    ProgramTooLarge,
+}
+
+impl fmt::Display for MovieError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            MovieError::InvalidFormat { line } => write!(f, "Invalid movie at line {line}"),
+            MovieError::MissingField(field) => write!(f, "Movie has no {field} field"),
+            MovieError::UnsupportedVersion(version) => write!(f, "Unsupported movie version {version}"),
+            MovieError::RomMismatch { expected, actual } => {
+                write!(f, "Movie has been recorded with ROM {expected:016X}, loaded ROM is {actual:016X}")
+            },
+            MovieError::ProgramTooLarge => write!(f, "Program does not fit in memory with the platform of the movie"),
+        }
+    }
+}
+
+impl core::error::Error for MovieError {}
+
+/// Errors that can occur when parsing an [Expression](crate::Expression) or a [LogMessage](crate::LogMessage).
+///
+/// Positions are byte offsets in the parsed string.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExpressionError {
+    /// Unexpected character or operator.
+    UnexpectedToken {
+        position: usize,
+    },
+    /// The expression ends before it is complete.
+    UnexpectedEnd {
+        position: usize,
+    },
+    /// The identifier is not a register nor `mem`.
+    UnknownIdentifier {
+        position: usize,
+    },
+    /// The number is invalid or does not fit in 64 bits.
+    InvalidNumber {
+        position: usize,
+    },
+    /// A brace of a log message is not closed or not opened.
+    UnmatchedBrace {
+        position: usize,
+    },
+    /// The format of a log message value is not `d`, `x` or `X`.
+    InvalidFormat {
+        position: usize,
+    },
+}
+
+impl ExpressionError {
+    /// Returns the position of the error.
+    pub const fn position(self) -> usize {
+        match self {
+            ExpressionError::UnexpectedToken { position } |
+            ExpressionError::UnexpectedEnd { position } |
+            ExpressionError::UnknownIdentifier { position } |
+            ExpressionError::InvalidNumber { position } |
+            ExpressionError::UnmatchedBrace { position } |
+            ExpressionError::InvalidFormat { position } => position,
+        }
+    }
+
+    /// Moves the position of the error by `offset`, for expressions parsed inside a larger string.
+    pub(crate) const fn offset(self, offset: usize) -> Self {
+        match self {
+            ExpressionError::UnexpectedToken { position } => ExpressionError::UnexpectedToken { position: position + offset },
+            ExpressionError::UnexpectedEnd { position } => ExpressionError::UnexpectedEnd { position: position + offset },
+            ExpressionError::UnknownIdentifier { position } => ExpressionError::UnknownIdentifier { position: position + offset },
+            ExpressionError::InvalidNumber { position } => ExpressionError::InvalidNumber { position: position + offset },
+            ExpressionError::UnmatchedBrace { position } => ExpressionError::UnmatchedBrace { position: position + offset },
+            ExpressionError::InvalidFormat { position } => ExpressionError::InvalidFormat { position: position + offset },
+        }
+    }
+}
+
+impl fmt::Display for ExpressionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            ExpressionError::UnexpectedToken { position } => write!(f, "Unexpected token at position {position}"),
+            ExpressionError::UnexpectedEnd { position } => write!(f, "Unexpected end of expression at position {position}"),
+            ExpressionError::UnknownIdentifier { position } => write!(f, "Unknown identifier at position {position}"),
+            ExpressionError::InvalidNumber { position } => write!(f, "Invalid number at position {position}"),
+            ExpressionError::UnmatchedBrace { position } => write!(f, "Unmatched brace at position {position}"),
+            ExpressionError::InvalidFormat { position } => write!(f, "Invalid format at position {position}"),
+        }
+    }
+}
+
+impl core::error::Error for ExpressionError {}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for LoadError {
+    fn from(e: std::io::Error) -> Self {
+        LoadError::Io(e)
+    }
+}
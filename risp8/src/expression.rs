@@ -0,0 +1,565 @@
+//! Expressions evaluated against the state, used by the conditions and log messages of breakpoints.
+//!
+//! Expressions use the Rust operators, from the lowest to the highest precedence:
+//! - `||`
+//! - `&&`
+//! - `==`, `!=`, `<`, `<=`, `>`, `>=`
+//! - `|`
+//! - `^`
+//! - `&`
+//! - `<<`, `>>`
+//! - `+`, `-`
+//! - `*`, `/`, `%`
+//! - unary `-`, `!` and `~`
+//!
+//! The operands are numbers (decimal, hexadecimal with `0x` or binary with `0b`), the registers `V0` to `VF`, `I`,
+//! `PC`, `SP`, `DT` (delay timer) and `ST` (sound timer), bytes of memory `mem[address]` and parenthesized expressions.
+//! Register names are case insensitive.
+//!
+//! Values are 64-bit signed integers with wrapping arithmetic. Comparisons and logical operators evaluate to 1 when true
+//! and 0 when false, and any non-zero value is true. Divisions by zero and memory reads out of memory evaluate to 0.
+
+use crate::{ExpressionError, State};
+
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
+
+/// An expression evaluated against the state, parsed from a string.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Expression {
+    source: String,
+    node: Node,
+}
+
+impl Expression {
+    /// Parses an expression.
+    pub fn parse(source: &str) -> Result<Self, ExpressionError> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser { tokens: &tokens, index: 0, end: source.len() };
+        let node = parser.expression(0)?;
+        if let Some(token) = parser.tokens.get(parser.index) {
+            return Err(ExpressionError::UnexpectedToken { position: token.position });
+        }
+
+        Ok(Self { source: source.to_string(), node })
+    }
+
+    /// Evaluates the expression with the values of the given state.
+    pub fn evaluate(&self, state: &State) -> i64 {
+        self.node.evaluate(state)
+    }
+}
+
+impl FromStr for Expression {
+    type Err = ExpressionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// A message formatted with the values of the state, parsed from a string.
+///
+/// The expressions between braces are replaced by their value, in decimal by default, or in hexadecimal with `{expr:x}`
+/// and `{expr:X}`. Literal braces are written `{{` and `}}`. For example `V3 = {V3:X}, next byte {mem[I + 1]}`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LogMessage {
+    source: String,
+    parts: Vec<MessagePart>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum MessagePart {
+    Text(String),
+    Decimal(Expression),
+    LowerHex(Expression),
+    UpperHex(Expression),
+}
+
+impl LogMessage {
+    /// Parses a log message.
+    pub fn parse(source: &str) -> Result<Self, ExpressionError> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = source.char_indices().peekable();
+
+        while let Some((position, c)) = chars.next() {
+            match c {
+                '{' if chars.next_if(|&(_, c)| c == '{').is_some() => text.push('{'),
+                '}' if chars.next_if(|&(_, c)| c == '}').is_some() => text.push('}'),
+                '}' => return Err(ExpressionError::UnmatchedBrace { position }),
+                '{' => {
+                    let beg = position + 1;
+                    let end = source[beg..].find('}').map(|len| beg + len)
+                        .ok_or(ExpressionError::UnmatchedBrace { position })?;
+                    let (expression, format) = match source[beg..end].rsplit_once(':') {
+                        Some((expression, format)) => (expression, format.trim()),
+                        None => (&source[beg..end], ""),
+                    };
+                    let expression = Expression::parse(expression).map_err(|e| e.offset(beg))?;
+
+                    if !text.is_empty() {
+                        parts.push(MessagePart::Text(core::mem::take(&mut text)));
+                    }
+                    parts.push(match format {
+                        "" | "d" => MessagePart::Decimal(expression),
+                        "x" => MessagePart::LowerHex(expression),
+                        "X" => MessagePart::UpperHex(expression),
+                        _ => return Err(ExpressionError::InvalidFormat { position: beg + expression.source.len() + 1 }),
+                    });
+
+                    while chars.next_if(|&(position, _)| position <= end).is_some() {}
+                },
+                c => text.push(c),
+            }
+        }
+
+        if !text.is_empty() {
+            parts.push(MessagePart::Text(text));
+        }
+
+        Ok(Self { source: source.to_string(), parts })
+    }
+
+    /// Returns the message with the values of the given state.
+    pub fn format(&self, state: &State) -> String {
+        use fmt::Write;
+
+        let mut message = String::new();
+        for part in &self.parts {
+            let _ = match part {
+                MessagePart::Text(text) => message.write_str(text),
+                MessagePart::Decimal(expression) => write!(message, "{}", expression.evaluate(state)),
+                MessagePart::LowerHex(expression) => write!(message, "{:x}", expression.evaluate(state)),
+                MessagePart::UpperHex(expression) => write!(message, "{:X}", expression.evaluate(state)),
+            };
+        }
+        message
+    }
+}
+
+impl FromStr for LogMessage {
+    type Err = ExpressionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for LogMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Register {
+    V(usize),
+    I,
+    Pc,
+    Sp,
+    Dt,
+    St,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum UnaryOp {
+    Neg,
+    Not,
+    BitNot,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Shl,
+    Shr,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+/// The binary operators of each precedence level, from the lowest to the highest.
+const BINARY_OPS: [&[(&str, BinaryOp)]; 9] = [
+    &[("||", BinaryOp::Or)],
+    &[("&&", BinaryOp::And)],
+    &[("==", BinaryOp::Eq), ("!=", BinaryOp::Ne), ("<", BinaryOp::Lt), ("<=", BinaryOp::Le), (">", BinaryOp::Gt), (">=", BinaryOp::Ge)],
+    &[("|", BinaryOp::BitOr)],
+    &[("^", BinaryOp::BitXor)],
+    &[("&", BinaryOp::BitAnd)],
+    &[("<<", BinaryOp::Shl), (">>", BinaryOp::Shr)],
+    &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+    &[("*", BinaryOp::Mul), ("/", BinaryOp::Div), ("%", BinaryOp::Rem)],
+];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Node {
+    Number(i64),
+    Register(Register),
+    Memory(Box<Node>),
+    Unary(UnaryOp, Box<Node>),
+    Binary(BinaryOp, Box<Node>, Box<Node>),
+}
+
+impl Node {
+    fn evaluate(&self, state: &State) -> i64 {
+        match self {
+            Node::Number(value) => *value,
+            Node::Register(register) => match *register {
+                Register::V(x) => state.V[x] as i64,
+                Register::I => state.I as i64,
+                Register::Pc => state.PC as i64,
+                Register::Sp => state.SP as i64,
+                Register::Dt => state.delay as i64,
+                Register::St => state.sound as i64,
+            },
+            Node::Memory(address) => {
+                let address = address.evaluate(state);
+                usize::try_from(address).ok().and_then(|address| state.memory().get(address)).map_or(0, |&byte| byte as i64)
+            },
+            Node::Unary(op, operand) => {
+                let value = operand.evaluate(state);
+                match op {
+                    UnaryOp::Neg => value.wrapping_neg(),
+                    UnaryOp::Not => (value == 0) as i64,
+                    UnaryOp::BitNot => !value,
+                }
+            },
+            Node::Binary(BinaryOp::Or, lhs, rhs) => (lhs.evaluate(state) != 0 || rhs.evaluate(state) != 0) as i64,
+            Node::Binary(BinaryOp::And, lhs, rhs) => (lhs.evaluate(state) != 0 && rhs.evaluate(state) != 0) as i64,
+            Node::Binary(op, lhs, rhs) => {
+                let lhs = lhs.evaluate(state);
+                let rhs = rhs.evaluate(state);
+                match op {
+                    BinaryOp::Or | BinaryOp::And => unreachable!(),
+                    BinaryOp::Eq => (lhs == rhs) as i64,
+                    BinaryOp::Ne => (lhs != rhs) as i64,
+                    BinaryOp::Lt => (lhs < rhs) as i64,
+                    BinaryOp::Le => (lhs <= rhs) as i64,
+                    BinaryOp::Gt => (lhs > rhs) as i64,
+                    BinaryOp::Ge => (lhs >= rhs) as i64,
+                    BinaryOp::BitOr => lhs | rhs,
+                    BinaryOp::BitXor => lhs ^ rhs,
+                    BinaryOp::BitAnd => lhs & rhs,
+                    BinaryOp::Shl => lhs.wrapping_shl(rhs as u32),
+                    BinaryOp::Shr => lhs.wrapping_shr(rhs as u32),
+                    BinaryOp::Add => lhs.wrapping_add(rhs),
+                    BinaryOp::Sub => lhs.wrapping_sub(rhs),
+                    BinaryOp::Mul => lhs.wrapping_mul(rhs),
+                    BinaryOp::Div => lhs.checked_div(rhs).unwrap_or(0),
+                    BinaryOp::Rem => lhs.checked_rem(rhs).unwrap_or(0),
+                }
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TokenKind<'a> {
+    Number(i64),
+    Identifier(&'a str),
+    /// Operators and brackets.
+    Symbol(&'static str),
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Token<'a> {
+    kind: TokenKind<'a>,
+    /// The byte offset of the token in the source.
+    position: usize,
+}
+
+/// The operators and brackets, the longest first so they are matched greedily.
+const SYMBOLS: [&str; 24] = [
+    "||", "&&", "==", "!=", "<=", ">=", "<<", ">>",
+    "<", ">", "|", "^", "&", "+", "-", "*", "/", "%", "!", "~", "(", ")", "[", "]",
+];
+
+fn tokenize(source: &str) -> Result<Vec<Token<'_>>, ExpressionError> {
+    let mut tokens = Vec::new();
+    let mut position = 0;
+
+    while let Some(c) = source[position..].chars().next() {
+        if c.is_whitespace() {
+            position += c.len_utf8();
+        } else if c.is_ascii_alphanumeric() || c == '_' {
+            let len = source[position..].find(|c: char| !c.is_ascii_alphanumeric() && c != '_').unwrap_or(source.len() - position);
+            let word = &source[position..position + len];
+            let kind = if c.is_ascii_digit() {
+                TokenKind::Number(parse_number(word).ok_or(ExpressionError::InvalidNumber { position })?)
+            } else {
+                TokenKind::Identifier(word)
+            };
+            tokens.push(Token { kind, position });
+            position += len;
+        } else {
+            let symbol = SYMBOLS.iter().find(|symbol| source[position..].starts_with(*symbol))
+                .ok_or(ExpressionError::UnexpectedToken { position })?;
+            tokens.push(Token { kind: TokenKind::Symbol(symbol), position });
+            position += symbol.len();
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn parse_number(word: &str) -> Option<i64> {
+    let (digits, radix) = match word.get(..2) {
+        Some("0x" | "0X") => (&word[2..], 16),
+        Some("0b" | "0B") => (&word[2..], 2),
+        _ => (word, 10),
+    };
+    i64::from_str_radix(digits, radix).ok()
+}
+
+fn parse_register(name: &str) -> Option<Register> {
+    let name = name.to_ascii_uppercase();
+    match name.as_str() {
+        "I" => Some(Register::I),
+        "PC" => Some(Register::Pc),
+        "SP" => Some(Register::Sp),
+        "DT" => Some(Register::Dt),
+        "ST" => Some(Register::St),
+        _ => {
+            let x = name.strip_prefix('V')?;
+            if x.len() == 1 {
+                usize::from_str_radix(x, 16).ok().map(Register::V)
+            } else {
+                None
+            }
+        },
+    }
+}
+
+/// Recursive descent parser of the tokens.
+struct Parser<'a> {
+    tokens: &'a [Token<'a>],
+    index: usize,
+    /// The length of the source, where errors at the end are reported.
+    end: usize,
+}
+
+impl<'a> Parser<'a> {
+    /// Returns the next token, or an error at the end of the source.
+    fn next(&mut self) -> Result<Token<'a>, ExpressionError> {
+        let token = *self.tokens.get(self.index).ok_or(ExpressionError::UnexpectedEnd { position: self.end })?;
+        self.index += 1;
+        Ok(token)
+    }
+
+    /// Consumes the next token if it is the given symbol.
+    fn eat(&mut self, symbol: &str) -> bool {
+        let matches = self.tokens.get(self.index).is_some_and(|token| matches!(token.kind, TokenKind::Symbol(s) if s == symbol));
+        self.index += matches as usize;
+        matches
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), ExpressionError> {
+        let position = self.tokens.get(self.index).map_or(self.end, |token| token.position);
+        if self.eat(symbol) {
+            Ok(())
+        } else if position == self.end {
+            Err(ExpressionError::UnexpectedEnd { position })
+        } else {
+            Err(ExpressionError::UnexpectedToken { position })
+        }
+    }
+
+    /// Parses the binary operators of the given precedence level and above.
+    fn expression(&mut self, level: usize) -> Result<Node, ExpressionError> {
+        let Some(ops) = BINARY_OPS.get(level) else {
+            return self.unary();
+        };
+
+        let mut lhs = self.expression(level + 1)?;
+        'outer: loop {
+            for &(symbol, op) in ops.iter() {
+                if self.eat(symbol) {
+                    let rhs = self.expression(level + 1)?;
+                    lhs = Node::Binary(op, Box::new(lhs), Box::new(rhs));
+                    continue 'outer;
+                }
+            }
+            return Ok(lhs);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Node, ExpressionError> {
+        for (symbol, op) in [("-", UnaryOp::Neg), ("!", UnaryOp::Not), ("~", UnaryOp::BitNot)] {
+            if self.eat(symbol) {
+                return Ok(Node::Unary(op, Box::new(self.unary()?)));
+            }
+        }
+
+        let token = self.next()?;
+        match token.kind {
+            TokenKind::Number(value) => Ok(Node::Number(value)),
+            TokenKind::Symbol("(") => {
+                let node = self.expression(0)?;
+                self.expect(")")?;
+                Ok(node)
+            },
+            TokenKind::Identifier(name) if name.eq_ignore_ascii_case("mem") => {
+                self.expect("[")?;
+                let address = self.expression(0)?;
+                self.expect("]")?;
+                Ok(Node::Memory(Box::new(address)))
+            },
+            TokenKind::Identifier(name) => {
+                parse_register(name).map(Node::Register).ok_or(ExpressionError::UnknownIdentifier { position: token.position })
+            },
+            TokenKind::Symbol(_) => Err(ExpressionError::UnexpectedToken { position: token.position }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state() -> State {
+        let mut state = State::new(&[0x12, 0x00]).unwrap();
+        state.V = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 0xFF];
+        state.I = 0x300;
+        state.SP = 2;
+        state.delay = 60;
+        state.sound = 30;
+        state.memory[0x300..0x303].copy_from_slice(&[0xAB, 0xCD, 0x02]);
+        state
+    }
+
+    fn eval(source: &str) -> i64 {
+        Expression::parse(source).unwrap().evaluate(&state())
+    }
+
+    fn error(source: &str) -> ExpressionError {
+        Expression::parse(source).unwrap_err()
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(eval("1 + 2 * 3"), 7);
+        assert_eq!(eval("10 - 4 - 3"), 3);
+        assert_eq!(eval("100 / 10 / 5"), 2);
+        assert_eq!(eval("7 % 4 * 2"), 6);
+        assert_eq!(eval("1 << 2 + 1"), 8);
+        assert_eq!(eval("6 & 3 << 1"), 6);
+        assert_eq!(eval("1 | 6 ^ 3 & 1"), 7);
+        assert_eq!(eval("1 | 2 == 3"), 1);
+        assert_eq!(eval("2 < 3 == 1"), 1);
+        assert_eq!(eval("0 || 1 && 0"), 0);
+        assert_eq!(eval("1 || 0 && 0"), 1);
+        assert_eq!(eval("-2 * -3"), 6);
+        assert_eq!(eval("!0 + !5"), 1);
+        assert_eq!(eval("~0 & 0xF"), 0xF);
+        assert_eq!(eval("- -1"), 1);
+    }
+
+    #[test]
+    fn parentheses() {
+        assert_eq!(eval("(1 + 2) * 3"), 9);
+        assert_eq!(eval("((((4))))"), 4);
+        assert_eq!(eval("(1 || 0) && (2 > 1)"), 1);
+        assert_eq!(eval("-(3 - 5)"), 2);
+    }
+
+    #[test]
+    fn operators() {
+        assert_eq!(eval("3 != 3"), 0);
+        assert_eq!(eval("3 <= 3"), 1);
+        assert_eq!(eval("3 >= 4"), 0);
+        assert_eq!(eval("3 > 2"), 1);
+        assert_eq!(eval("-16 >> 2"), -4);
+        assert_eq!(eval("5 / 0"), 0);
+        assert_eq!(eval("5 % 0"), 0);
+        assert_eq!(eval("0x7FFFFFFFFFFFFFFF + 1"), i64::MIN);
+        assert_eq!(eval("-9223372036854775807 - 2"), i64::MAX);
+        assert_eq!(eval("(-9223372036854775807 - 1) / -1"), 0);
+    }
+
+    #[test]
+    fn operands() {
+        assert_eq!(eval("V0 + V1 + Va + vF"), 1 + 10 + 0xFF);
+        assert_eq!(eval("I"), 0x300);
+        assert_eq!(eval("pc"), 0x200);
+        assert_eq!(eval("SP"), 2);
+        assert_eq!(eval("DT"), 60);
+        assert_eq!(eval("st"), 30);
+        assert_eq!(eval("mem[I]"), 0xAB);
+        assert_eq!(eval("MEM[I + 1] << 8 | mem[0x300]"), 0xCDAB);
+        assert_eq!(eval("mem[I + mem[0x302]]"), 0x02);
+        assert_eq!(eval("mem[PC]"), 0x12);
+        assert_eq!(eval("mem[-1]"), 0);
+        assert_eq!(eval("mem[0x10000]"), 0);
+    }
+
+    #[test]
+    fn literals() {
+        assert_eq!(eval("42"), 42);
+        assert_eq!(eval("0x2A"), 42);
+        assert_eq!(eval("0X2a"), 42);
+        assert_eq!(eval("0b101010"), 42);
+        assert_eq!(eval("0B101010"), 42);
+        assert_eq!(eval("007"), 7);
+        assert_eq!(eval("9223372036854775807"), i64::MAX);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(error("1 $ 2"), ExpressionError::UnexpectedToken { position: 2 });
+        assert_eq!(error("1 2"), ExpressionError::UnexpectedToken { position: 2 });
+        assert_eq!(error("(1 + 2]"), ExpressionError::UnexpectedToken { position: 6 });
+        assert_eq!(error("mem(1)"), ExpressionError::UnexpectedToken { position: 3 });
+        assert_eq!(error("* 2"), ExpressionError::UnexpectedToken { position: 0 });
+        assert_eq!(error(""), ExpressionError::UnexpectedEnd { position: 0 });
+        assert_eq!(error("1 +"), ExpressionError::UnexpectedEnd { position: 3 });
+        assert_eq!(error("(1 + 2"), ExpressionError::UnexpectedEnd { position: 6 });
+        assert_eq!(error("mem[I"), ExpressionError::UnexpectedEnd { position: 5 });
+        assert_eq!(error("V0 + VG"), ExpressionError::UnknownIdentifier { position: 5 });
+        assert_eq!(error("V10"), ExpressionError::UnknownIdentifier { position: 0 });
+        assert_eq!(error("1 + foo"), ExpressionError::UnknownIdentifier { position: 4 });
+        assert_eq!(error("1 + 0x"), ExpressionError::InvalidNumber { position: 4 });
+        assert_eq!(error("0b102"), ExpressionError::InvalidNumber { position: 0 });
+        assert_eq!(error("12ab"), ExpressionError::InvalidNumber { position: 0 });
+        assert_eq!(error("9223372036854775808"), ExpressionError::InvalidNumber { position: 0 });
+        assert_eq!(error("1 + 0x").position(), 4);
+    }
+
+    #[test]
+    fn log_messages() {
+        let format = |source: &str| LogMessage::parse(source).unwrap().format(&state());
+        assert_eq!(format("V3 = {V3}, VF = {VF:x}/{VF:X}, I = {I:d}"), "V3 = 3, VF = ff/FF, I = 768");
+        assert_eq!(format("{{literal}} {mem[I]:X}"), "{literal} AB");
+        assert_eq!(format("{V1 + V2}{V3}"), "33");
+        assert_eq!(format("no values"), "no values");
+        assert_eq!(format(""), "");
+
+        let error = |source: &str| LogMessage::parse(source).unwrap_err();
+        assert_eq!(error("V0 = {V0"), ExpressionError::UnmatchedBrace { position: 5 });
+        assert_eq!(error("V0 = }"), ExpressionError::UnmatchedBrace { position: 5 });
+        assert_eq!(error("V0 = {V0:b}"), ExpressionError::InvalidFormat { position: 9 });
+        assert_eq!(error("V0 = {V0 +}"), ExpressionError::UnexpectedEnd { position: 10 });
+        assert_eq!(error("V0 = {V0 + VG}"), ExpressionError::UnknownIdentifier { position: 11 });
+    }
+}
@@ -0,0 +1,203 @@
+//! Glyph data of the fonts of the various Chip8 interpreters.
+//!
+//! Small fonts are the 16 hexadecimal digits in 4x5 pixels (5 bytes each).
+//! Big fonts are the 16 hexadecimal digits in 8x10 pixels (10 bytes each).
+
+use crate::FontError;
+
+/// Size in bytes of a small font.
+const SMALL_FONT_LEN: usize = 80;
+/// Size in bytes of a big font.
+const BIG_FONT_LEN: usize = 160;
+/// Fonts are stored in the interpreter area, below this address.
+const FONT_AREA_END: usize = 0x200;
+
+/// A font set, loaded in the interpreter memory below 0x200.
+///
+/// It is made of a small font, pointed to by Fx29, and of a big font, pointed to by the SUPER-CHIP Fx30 instruction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Font {
+    small: [u8; SMALL_FONT_LEN],
+    big: [u8; BIG_FONT_LEN],
+    small_address: u16,
+    big_address: u16,
+}
+
+impl Font {
+    /// Size in bytes of a small font.
+    pub const SMALL_LEN: usize = SMALL_FONT_LEN;
+    /// Size in bytes of a big font.
+    pub const BIG_LEN: usize = BIG_FONT_LEN;
+
+    /// The original COSMAC VIP font.
+    pub const VIP: Font = Font::new(VIP_SMALL, SCHIP_BIG);
+    /// The CHIP-48 font, also used by SUPER-CHIP and most modern interpreters.
+    pub const CHIP48: Font = Font::new(CHIP48_SMALL, SCHIP_BIG);
+    /// The DREAM 6800 CHIPOS font.
+    pub const DREAM6800: Font = Font::new(DREAM6800_SMALL, SCHIP_BIG);
+    /// The ETI-660 font.
+    pub const ETI660: Font = Font::new(ETI660_SMALL, SCHIP_BIG);
+    /// The SUPER-CHIP 1.1 big font, used by every named font set. Useful to build a custom font set.
+    pub const SCHIP_BIG: [u8; BIG_FONT_LEN] = SCHIP_BIG;
+
+    /// Creates a font set from custom glyph data, with the small font at address 0 and the big font right after it.
+    pub const fn new(small: [u8; SMALL_FONT_LEN], big: [u8; BIG_FONT_LEN]) -> Self {
+        Self {
+            small,
+            big,
+            small_address: 0,
+            big_address: SMALL_FONT_LEN as u16,
+        }
+    }
+
+    /// Returns this font set loaded at the given addresses.
+    ///
+    /// Returns an error if one of the fonts does not fit below 0x200 or if they overlap.
+    pub const fn with_addresses(self, small_address: u16, big_address: u16) -> Result<Self, FontError> {
+        let small_end = small_address as usize + SMALL_FONT_LEN;
+        let big_end = big_address as usize + BIG_FONT_LEN;
+        if small_end > FONT_AREA_END || big_end > FONT_AREA_END {
+            return Err(FontError::OutOfRange);
+        }
+        if small_end > big_address as usize && big_end > small_address as usize {
+            return Err(FontError::Overlapping);
+        }
+
+        Ok(self.with_addresses_unchecked(small_address, big_address))
+    }
+
+    /// Returns this font set loaded at the given addresses, which may overlap.
+    pub(crate) const fn with_addresses_unchecked(self, small_address: u16, big_address: u16) -> Self {
+        Self {
+            small_address,
+            big_address,
+            ..self
+        }
+    }
+
+    /// Returns the small font glyphs.
+    pub const fn small(&self) -> &[u8; SMALL_FONT_LEN] {
+        &self.small
+    }
+
+    /// Returns the big font glyphs.
+    pub const fn big(&self) -> &[u8; BIG_FONT_LEN] {
+        &self.big
+    }
+
+    /// Returns the address of the small font in memory.
+    pub const fn small_address(&self) -> u16 {
+        self.small_address
+    }
+
+    /// Returns the address of the big font in memory.
+    pub const fn big_address(&self) -> u16 {
+        self.big_address
+    }
+}
+
+impl Default for Font {
+    fn default() -> Self {
+        Self::CHIP48
+    }
+}
+
+/// The original COSMAC VIP font.
+const VIP_SMALL: [u8; SMALL_FONT_LEN] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x60, 0x20, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0xA0, 0xA0, 0xF0, 0x20, 0x20, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x10, 0x10, 0x10, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xF0, 0x50, 0x70, 0x50, 0xF0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xF0, 0x50, 0x50, 0x50, 0xF0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+/// The CHIP-48 font.
+const CHIP48_SMALL: [u8; SMALL_FONT_LEN] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+/// The DREAM 6800 CHIPOS font.
+const DREAM6800_SMALL: [u8; SMALL_FONT_LEN] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+    0x40, 0x40, 0x40, 0x40, 0x40, // 1
+    0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+    0xE0, 0x20, 0xE0, 0x20, 0xE0, // 3
+    0x80, 0xA0, 0xA0, 0xE0, 0x20, // 4
+    0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+    0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+    0xE0, 0x20, 0x20, 0x20, 0x20, // 7
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+    0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+    0xE0, 0xA0, 0xE0, 0xA0, 0xA0, // A
+    0xC0, 0xA0, 0xE0, 0xA0, 0xC0, // B
+    0xE0, 0x80, 0x80, 0x80, 0xE0, // C
+    0xC0, 0xA0, 0xA0, 0xA0, 0xC0, // D
+    0xE0, 0x80, 0xE0, 0x80, 0xE0, // E
+    0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+];
+
+/// The ETI-660 font.
+const ETI660_SMALL: [u8; SMALL_FONT_LEN] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+    0x20, 0x20, 0x20, 0x20, 0x20, // 1
+    0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+    0xE0, 0x20, 0xE0, 0x20, 0xE0, // 3
+    0xA0, 0xA0, 0xE0, 0x20, 0x20, // 4
+    0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+    0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+    0xE0, 0x20, 0x20, 0x20, 0x20, // 7
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+    0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+    0xE0, 0xA0, 0xE0, 0xA0, 0xA0, // A
+    0x80, 0x80, 0xE0, 0xA0, 0xE0, // B
+    0xE0, 0x80, 0x80, 0x80, 0xE0, // C
+    0x20, 0x20, 0xE0, 0xA0, 0xE0, // D
+    0xE0, 0x80, 0xE0, 0x80, 0xE0, // E
+    0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+];
+
+/// The SUPER-CHIP 1.1 big font, with the A to F digits of Octo as SUPER-CHIP only has 0 to 9.
+const SCHIP_BIG: [u8; BIG_FONT_LEN] = [
+    0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C, // 0
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C, // 1
+    0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF, // 2
+    0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C, // 3
+    0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C, // 5
+    0x3E, 0x7C, 0xC0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C, // 6
+    0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60, // 7
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C, // 8
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
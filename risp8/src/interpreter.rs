@@ -1,22 +1,43 @@
-use crate::{Chip8, State};
+use crate::{ExecutionBackend, Screen, State, Trap};
 use crate::opcode::Opcode;
 
-use rand::Rng;
+/// Interprets the instructions one by one using a look-up table of the opcodes.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Interpreter;
 
-impl Chip8 {
-    /// Executes a single instruction using the interpreter.
-    pub fn interpreter(&mut self) {
-        let opcode = Opcode((self.state.memory[self.state.PC as usize] as u16) << 8 | self.state.memory[self.state.PC as usize + 1] as u16);
-        // #[cfg(debug_assertions)] println!("opcode {opcode:04X} at {:#X}", self.state.PC);
-        self.state.PC += 2;
+impl ExecutionBackend for Interpreter {
+    /// Executes a single instruction.
+    fn step(&mut self, state: &mut State, _: u64) {
+        state.interpret_instruction();
+    }
+
+    fn invalidate(&mut self, _: u16, _: u16) {}
+
+    fn reset(&mut self) {}
+}
+
+impl State {
+    /// Fetches and executes the instruction at PC, returning the value of the execution method.
+    ///
+    /// The return value is 1 if the instruction modified the program counter or trapped, `> 1` if it wrote in memory
+    /// in the range [beg, end] inclusive, with beg in the high order word and end in the low order word, and 0
+    /// otherwise.
+    pub fn interpret_instruction(&mut self) -> u32 {
+        let pc = self.PC;
+        if pc as usize + 2 > self.memory_size {
+            return self.trap(Trap::MemoryOutOfRange { pc, address: pc, len: 2 });
+        }
 
-        self.state.execute_instruction(opcode);
+        let opcode = Opcode((self.memory[pc as usize] as u16) << 8 | self.memory[pc as usize + 1] as u16);
+        // #[cfg(debug_assertions)] println!("opcode {opcode:04X} at {pc:#X}");
+        self.PC = pc.wrapping_add(2);
+        self.cycles += 1;
 
-        self.handle_timers();
+        self.execute_instruction(opcode)
     }
 }
 
-/// The execution methods returns 1 if the instruction modified the program counter,
+/// The execution methods returns 1 if the instruction modified the program counter or trapped,
 /// `> 1` to request a cache invalidation of [beg, end] inclusive with the beg in the high order word,
 /// 0 if everything is good to continue.
 #[allow(non_snake_case)]
@@ -28,6 +49,18 @@ impl State {
 
     pub(super) const ILUT: [fn(&mut State, Opcode) -> u32; 1 << 16] = generate_decoder();
 
+    pub(super) fn execute_00Cn(&mut self, opcode: Opcode) -> u32 {
+        self.screen.scroll(self.planes, 0, opcode.n() as isize);
+        self.screen_dirty = true;
+        0
+    }
+
+    pub(super) fn execute_00Dn(&mut self, opcode: Opcode) -> u32 {
+        self.screen.scroll(self.planes, 0, -(opcode.n() as isize));
+        self.screen_dirty = true;
+        0
+    }
+
     pub(super) fn execute_00E0(&mut self, _: Opcode) -> u32 {
         self.clear_screen();
         0
@@ -37,32 +70,63 @@ impl State {
         if self.SP > 0 {
             self.SP -= 1;
             self.PC = self.stack[self.SP];
+            1
         } else {
-            println!("Stack underflow (RET 0x00EE)");
+            self.trap(Trap::StackUnderflow { pc: self.PC.wrapping_sub(2) })
         }
+    }
+
+    pub(super) fn execute_00FB(&mut self, _: Opcode) -> u32 {
+        self.screen.scroll(self.planes, 4, 0);
+        self.screen_dirty = true;
+        0
+    }
+
+    pub(super) fn execute_00FC(&mut self, _: Opcode) -> u32 {
+        self.screen.scroll(self.planes, -4, 0);
+        self.screen_dirty = true;
+        0
+    }
+
+    pub(super) fn execute_00FD(&mut self, _: Opcode) -> u32 {
+        // Loop over this instruction so execution does not continue past the exit.
+        self.PC = self.PC.wrapping_sub(2);
+        self.exited = true;
         1
     }
 
+    pub(super) fn execute_00FE(&mut self, _: Opcode) -> u32 {
+        self.screen.set_hires(false);
+        self.screen_dirty = true;
+        0
+    }
+
+    pub(super) fn execute_00FF(&mut self, _: Opcode) -> u32 {
+        self.screen.set_hires(true);
+        self.screen_dirty = true;
+        0
+    }
+
     pub(super) fn execute_1nnn(&mut self, opcode: Opcode) -> u32 {
         self.PC = opcode.nnn();
         1
     }
 
     pub(super) fn execute_2nnn(&mut self, opcode: Opcode) -> u32 {
-        if self.SP < 0xF {
+        if self.SP < self.stack.len() {
             self.stack[self.SP] = self.PC;
             self.SP += 1;
             self.PC = opcode.nnn();
+            1
         } else {
-            println!("Stack overflow (CALL 0x2nnn)");
+            self.trap(Trap::StackOverflow { pc: self.PC.wrapping_sub(2) })
         }
-        1
     }
 
     pub(super) fn execute_3xkk(&mut self, opcode: Opcode) -> u32 {
         let (x, kk) = opcode.xkk();
         if self.V[x] == kk {
-            self.PC += 2;
+            self.skip_next_instruction();
             1
         } else {
             0
@@ -72,7 +136,7 @@ impl State {
     pub(super) fn execute_4xkk(&mut self, opcode: Opcode) -> u32 {
         let (x, kk) = opcode.xkk();
         if self.V[x] != kk {
-            self.PC += 2;
+            self.skip_next_instruction();
             1
         } else {
             0
@@ -82,13 +146,42 @@ impl State {
     pub(super) fn execute_5xy0(&mut self, opcode: Opcode) -> u32 {
         let (x, y) = opcode.xy();
         if self.V[x] == self.V[y] {
-            self.PC += 2;
+            self.skip_next_instruction();
             1
         } else {
             0
         }
     }
 
+    pub(super) fn execute_5xy2(&mut self, opcode: Opcode) -> u32 {
+        let (x, y) = opcode.xy();
+        let count = x.abs_diff(y);
+        if !self.check_range(self.I, count + 1) {
+            return 1;
+        }
+        self.watch_write(self.I, count + 1);
+        for i in 0..=count {
+            let reg = if x <= y { x + i } else { x - i };
+            self.memory[self.I as usize + i] = self.V[reg];
+        }
+        ((self.I as u32) << 16) | (self.I as u32 + count as u32)
+    }
+
+    pub(super) fn execute_5xy3(&mut self, opcode: Opcode) -> u32 {
+        let (x, y) = opcode.xy();
+        let count = x.abs_diff(y);
+        if !self.check_range(self.I, count + 1) {
+            return 1;
+        }
+        // Stop the block on a watched access so the core reports it.
+        let watched = self.watch_read(self.I, count + 1);
+        for i in 0..=count {
+            let reg = if x <= y { x + i } else { x - i };
+            self.V[reg] = self.memory[self.I as usize + i];
+        }
+        watched as u32
+    }
+
     pub(super) fn execute_6xkk(&mut self, opcode: Opcode) -> u32 {
         let (x, kk) = opcode.xkk();
         self.V[x] = kk;
@@ -110,21 +203,27 @@ impl State {
     pub(super) fn execute_8xy1(&mut self, opcode: Opcode) -> u32 {
         let (x, y) = opcode.xy();
         self.V[x] |= self.V[y];
-        self.V[0xF] = 0; // https://games.gulrak.net/cadmium/chip8-opcode-table.html#quirk1
+        if self.quirks.vf_reset {
+            self.V[0xF] = 0; // https://games.gulrak.net/cadmium/chip8-opcode-table.html#quirk1
+        }
         0
     }
 
     pub(super) fn execute_8xy2(&mut self, opcode: Opcode) -> u32 {
         let (x, y) = opcode.xy();
         self.V[x] &= self.V[y];
-        self.V[0xF] = 0; // https://games.gulrak.net/cadmium/chip8-opcode-table.html#quirk1
+        if self.quirks.vf_reset {
+            self.V[0xF] = 0; // https://games.gulrak.net/cadmium/chip8-opcode-table.html#quirk1
+        }
         0
     }
 
     pub(super) fn execute_8xy3(&mut self, opcode: Opcode) -> u32 {
         let (x, y) = opcode.xy();
         self.V[x] ^= self.V[y];
-        self.V[0xF] = 0; // https://games.gulrak.net/cadmium/chip8-opcode-table.html#quirk1
+        if self.quirks.vf_reset {
+            self.V[0xF] = 0; // https://games.gulrak.net/cadmium/chip8-opcode-table.html#quirk1
+        }
         0
     }
 
@@ -145,10 +244,10 @@ impl State {
     }
 
     pub(super) fn execute_8xy6(&mut self, opcode: Opcode) -> u32 {
-        let x = opcode.x();
-        // let y = opcode.y();
-        let c = self.V[x] & 1;
-        self.V[x] >>= 1;
+        let (x, y) = opcode.xy();
+        let src = if self.quirks.shift_vx { self.V[x] } else { self.V[y] };
+        let c = src & 1;
+        self.V[x] = src >> 1;
         self.V[0xF] = c;
         0
     }
@@ -162,10 +261,10 @@ impl State {
     }
 
     pub(super) fn execute_8xyE(&mut self, opcode: Opcode) -> u32 {
-        let x = opcode.x();
-        // let y = opcode.y();
-        let c = self.V[x] >> 7 & 1;
-        self.V[x] <<= 1;
+        let (x, y) = opcode.xy();
+        let src = if self.quirks.shift_vx { self.V[x] } else { self.V[y] };
+        let c = src >> 7 & 1;
+        self.V[x] = src << 1;
         self.V[0xF] = c;
         0
     }
@@ -173,7 +272,7 @@ impl State {
     pub(super) fn execute_9xy0(&mut self, opcode: Opcode) -> u32 {
         let (x, y) = opcode.xy();
         if self.V[x] != self.V[y] {
-            self.PC += 2;
+            self.skip_next_instruction();
             1
         } else {
             0
@@ -186,27 +285,37 @@ impl State {
     }
 
     pub(super) fn execute_Bnnn(&mut self, opcode: Opcode) -> u32 {
-        self.PC = opcode.nnn() + self.V[0] as u16;
+        let x = if self.quirks.jump_vx { opcode.x() } else { 0 };
+        self.PC = opcode.nnn() + self.V[x] as u16;
         1
     }
 
     pub(super) fn execute_Cxkk(&mut self, opcode: Opcode) -> u32 {
         let (x, kk) = opcode.xkk();
-        self.V[x] = rand::thread_rng().gen_range(0, 256) as u8 & kk;
+        self.V[x] = self.rng.next_byte() & kk;
         0
     }
 
     pub(super) fn execute_Dxyn(&mut self, opcode: Opcode) -> u32 {
         let (x, y) = opcode.xy();
         let n = opcode.n();
+        let len = match n {
+            0 if self.screen.is_hires() || self.quirks.lores_big_sprites => 32,
+            0 => 16,
+            n => n as usize,
+        } * self.planes.count_ones() as usize;
+        if !self.check_range(self.I, len) {
+            return 1;
+        }
+        let watched = self.watch_read(self.I, len);
         self.draw(x, y, n);
-        0
+        watched as u32
     }
 
     pub(super) fn execute_Ex9E(&mut self, opcode: Opcode) -> u32 {
         let x = opcode.x();
-        if self.keys[self.V[x] as usize] {
-            self.PC += 2;
+        if self.keys[self.V[x] as usize & 0xF] {
+            self.skip_next_instruction();
             1
         } else {
             0
@@ -215,14 +324,38 @@ impl State {
 
     pub(super) fn execute_ExA1(&mut self, opcode: Opcode) -> u32 {
         let x = opcode.x();
-        if !self.keys[self.V[x] as usize] {
-            self.PC += 2;
+        if !self.keys[self.V[x] as usize & 0xF] {
+            self.skip_next_instruction();
             1
         } else {
             0
         }
     }
 
+    pub(super) fn execute_F000(&mut self, _: Opcode) -> u32 {
+        if !self.check_range(self.PC, 2) {
+            return 1;
+        }
+        self.I = (self.memory[self.PC as usize] as u16) << 8 | self.memory[self.PC as usize + 1] as u16;
+        self.PC = self.PC.wrapping_add(2);
+        0
+    }
+
+    pub(super) fn execute_Fn01(&mut self, opcode: Opcode) -> u32 {
+        self.planes = opcode.x() as u8 & Screen::ALL_PLANES;
+        0
+    }
+
+    pub(super) fn execute_F002(&mut self, _: Opcode) -> u32 {
+        if !self.check_range(self.I, 16) {
+            return 1;
+        }
+        let watched = self.watch_read(self.I, 16);
+        let i = self.I as usize;
+        self.audio_pattern.copy_from_slice(&self.memory[i..i + 16]);
+        watched as u32
+    }
+
     pub(super) fn execute_Fx07(&mut self, opcode: Opcode) -> u32 {
         let x = opcode.x();
         self.V[x] = self.delay;
@@ -233,7 +366,7 @@ impl State {
         let x = opcode.x();
         if !self.wait_key(x) {
             // If it is still waiting for a key, decrement PC to make it loop over this instruction.
-            self.PC -= 2;
+            self.PC = self.PC.wrapping_sub(2);
             1
         } else {
             0
@@ -254,44 +387,85 @@ impl State {
 
     pub(super) fn execute_Fx1E(&mut self, opcode: Opcode) -> u32 {
         let x = opcode.x();
-        self.I += self.V[x] as u16;
+        self.I = self.I.wrapping_add(self.V[x] as u16);
         0
     }
 
     pub(super) fn execute_Fx29(&mut self, opcode: Opcode) -> u32 {
         let x = opcode.x();
-        self.I = self.V[x] as u16 * 5;
+        self.I = self.font_address + (self.V[x] & 0xF) as u16 * 5;
+        0
+    }
+
+    pub(super) fn execute_Fx30(&mut self, opcode: Opcode) -> u32 {
+        let x = opcode.x();
+        self.I = self.big_font_address + (self.V[x] & 0xF) as u16 * 10;
         0
     }
 
     pub(super) fn execute_Fx33(&mut self, opcode: Opcode) -> u32 {
         let x = opcode.x();
+        if !self.check_range(self.I, 3) {
+            return 1;
+        }
+        self.watch_write(self.I, 3);
         self.memory[self.I as usize] = self.V[x] / 100;
         self.memory[self.I as usize + 1] = (self.V[x] / 10) % 10;
         self.memory[self.I as usize + 2] = self.V[x] % 10;
+        (self.I as u32) << 16 | (self.I as u32 + 2)
+    }
+
+    pub(super) fn execute_Fx3A(&mut self, opcode: Opcode) -> u32 {
+        let x = opcode.x();
+        self.pitch = self.V[x];
         0
     }
 
     pub(super) fn execute_Fx55(&mut self, opcode: Opcode) -> u32 {
         let x = opcode.x();
+        if !self.check_range(self.I, x + 1) {
+            return 1;
+        }
+        let beg = self.I;
+        self.watch_write(beg, x + 1);
         for i in 0..=x {
             self.memory[self.I as usize + i] = self.V[i];
         }
-        self.I += x as u16 + 1; // https://tobiasvl.github.io/blog/write-a-chip-8-emulator/#fx55-and-fx65-store-and-load-memory
-        (self.I as u32) << 16 | self.I as u32 + x as u32
+        if self.quirks.increment_i {
+            self.I = self.I.wrapping_add(x as u16 + 1); // https://tobiasvl.github.io/blog/write-a-chip-8-emulator/#fx55-and-fx65-store-and-load-memory
+        }
+        ((beg as u32) << 16) | (beg as u32 + x as u32)
     }
 
     pub(super) fn execute_Fx65(&mut self, opcode: Opcode) -> u32 {
         let x = opcode.x();
+        if !self.check_range(self.I, x + 1) {
+            return 1;
+        }
+        let watched = self.watch_read(self.I, x + 1);
         for i in 0..=x {
             self.V[i] = self.memory[self.I as usize + i];
         }
-        self.I += x as u16 + 1; // https://tobiasvl.github.io/blog/write-a-chip-8-emulator/#fx55-and-fx65-store-and-load-memory
+        if self.quirks.increment_i {
+            self.I = self.I.wrapping_add(x as u16 + 1); // https://tobiasvl.github.io/blog/write-a-chip-8-emulator/#fx55-and-fx65-store-and-load-memory
+        }
+        watched as u32
+    }
+
+    pub(super) fn execute_Fx75(&mut self, opcode: Opcode) -> u32 {
+        let x = opcode.x();
+        self.rpl[..=x].copy_from_slice(&self.V[..=x]);
+        0
+    }
+
+    pub(super) fn execute_Fx85(&mut self, opcode: Opcode) -> u32 {
+        let x = opcode.x();
+        self.V[..=x].copy_from_slice(&self.rpl[..=x]);
         0
     }
 
-    fn execute_invalid(&mut self, opcode: Opcode) -> u32 {
-        panic!("invalid opcode {:04X} at {:#X}", opcode, self.PC - 2);
+    pub(super) fn execute_invalid(&mut self, opcode: Opcode) -> u32 {
+        self.trap(Trap::InvalidOpcode { pc: self.PC.wrapping_sub(2), opcode: opcode.0 })
     }
 }
 
@@ -353,14 +527,24 @@ const fn slice_to_usize(bytes: &[u8]) -> usize {
     b0 << 12 | b1 << 8 | b2 << 4 | b3
 }
 
-const INSTRUCTION_FORMATS: [(&str, fn(&mut State, Opcode) -> u32); 34] = [
+#[allow(clippy::type_complexity)]
+const INSTRUCTION_FORMATS: [(&str, fn(&mut State, Opcode) -> u32); 50] = [
+    ("00Cn", State::execute_00Cn),
+    ("00Dn", State::execute_00Dn),
     ("00E0", State::execute_00E0),
     ("00EE", State::execute_00EE),
+    ("00FB", State::execute_00FB),
+    ("00FC", State::execute_00FC),
+    ("00FD", State::execute_00FD),
+    ("00FE", State::execute_00FE),
+    ("00FF", State::execute_00FF),
     ("1nnn", State::execute_1nnn),
     ("2nnn", State::execute_2nnn),
     ("3xkk", State::execute_3xkk),
     ("4xkk", State::execute_4xkk),
     ("5xy0", State::execute_5xy0),
+    ("5xy2", State::execute_5xy2),
+    ("5xy3", State::execute_5xy3),
     ("6xkk", State::execute_6xkk),
     ("7xkk", State::execute_7xkk),
     ("8xy0", State::execute_8xy0),
@@ -379,13 +563,20 @@ const INSTRUCTION_FORMATS: [(&str, fn(&mut State, Opcode) -> u32); 34] = [
     ("Dxyn", State::execute_Dxyn),
     ("Ex9E", State::execute_Ex9E),
     ("ExA1", State::execute_ExA1),
+    ("F000", State::execute_F000),
+    ("Fn01", State::execute_Fn01),
+    ("F002", State::execute_F002),
     ("Fx07", State::execute_Fx07),
     ("Fx0A", State::execute_Fx0A),
     ("Fx15", State::execute_Fx15),
     ("Fx18", State::execute_Fx18),
     ("Fx1E", State::execute_Fx1E),
     ("Fx29", State::execute_Fx29),
+    ("Fx30", State::execute_Fx30),
     ("Fx33", State::execute_Fx33),
+    ("Fx3A", State::execute_Fx3A),
     ("Fx55", State::execute_Fx55),
     ("Fx65", State::execute_Fx65),
+    ("Fx75", State::execute_Fx75),
+    ("Fx85", State::execute_Fx85),
 ];
@@ -1,9 +1,13 @@
-use crate::Chip8;
+use crate::{ExecutionBackend, Screen, State, Stats};
+use crate::cache::Caches;
 use crate::opcode::Opcode;
 use crate::Address;
 
 use dynasmrt::{dynasm, DynasmApi, DynasmLabelApi, x64::Assembler};
 
+use std::collections::BTreeSet;
+use std::time::Instant;
+
 #[derive(Debug)]
 pub enum Interrupts {
     UseInterpreter = 1,
@@ -36,64 +40,70 @@ impl From<u64> for Interrupts {
     }
 }
 
-impl Chip8 {
-    /// Executes a block of instructions using the JIT compiler.
-    pub fn jit(&mut self) {
-        if self.jit_caches.get(self.state.PC).is_none() {
-            self.compile_block(self.state.PC);
-        }
-
-        let ret = self.jit_caches.get(self.state.PC).unwrap().run();
-        match Interrupts::from(ret >> 16 & 0xFFFF) {
-            Interrupts::UseInterpreter => {
-                self.state.PC = ret as u16;
-                self.interpreter();
-            },
-            Interrupts::Jump => self.state.PC = ret as u16,
-            Interrupts::InvalidateCache => {
-                self.state.PC = ret as u16;
-                let beg_addr = (ret >> 48) as u16;
-                let end_addr = (ret >> 32) as u16;
+/// The x86_64 JIT compiler, compiling blocks of instructions to native code.
+///
+/// The compiled code accesses the fields of the state at their address, so the caches are deleted when it is used with
+/// another state.
+pub struct Jit {
+    caches: Caches,
+    /// The address of the state the caches have been compiled for.
+    state_address: usize,
+    stats: Stats,
+    /// Blocks end before these addresses.
+    breakpoints: BTreeSet<u16>,
+}
 
-                self.jit_caches.invalidate(beg_addr, end_addr);
-            },
+impl Jit {
+    /// Creates a new JIT compiler with empty caches.
+    pub fn new() -> Self {
+        Self {
+            caches: Caches::new(),
+            state_address: 0,
+            stats: Stats::default(),
+            breakpoints: BTreeSet::new(),
         }
-
-        self.handle_timers();
     }
 
     /// Uses the RAX, RCX and RDX (caller-saved) registers.
     ///
     /// RAX contains the return value of the block. RAX, RCX and RDX are used internally by the compiled code.
-    fn compile_block(&mut self, addr: u16) {
+    fn compile_block(&mut self, state: &State, addr: u16) {
         let block_pc = addr;
         let mut asm = Assembler::new().expect("Failed to create new assembler");
 
-        let timer = handle_timers as *const ();
-        let this = self as *mut Chip8;
-
-        #[cfg(target_os = "windows")]
-        dynasm!(asm
-            ; .arch x64
-            ; mov rax, QWORD timer as i64
-            ; mov rcx, QWORD this as i64
-            ; call rax
-        );
-
-        #[cfg(not(target_os = "windows"))]
-        dynasm!(asm
-            ; .arch x64
-            ; mov rax, QWORD timer as i64
-            ; push rdi
-            ; mov rdi, QWORD this as i64
-            ; call rax
-            ; pop rdi
-        );
+        let addr_cycles = state.cycles.address(0) as i64;
+        let mut len = 0;
 
         let mut next_pc = addr;
         'outer: loop {
             let current_pc = next_pc;
-            let opcode = Opcode::from((self.state.memory[current_pc as usize] as u16) << 8 | self.state.memory[current_pc as usize + 1] as u16);
+
+            if current_pc != block_pc && self.breakpoints.contains(&current_pc) {
+                // Exit before the breakpoint so the core stops on it.
+                dynasm!(asm
+                    ; .arch x64
+                    ; mov rax, QWORD Interrupts::jump(current_pc)
+                );
+                break 'outer;
+            }
+
+            // Count the instruction. Instructions that fall back to the interpreter are counted here too.
+            len += 1;
+            dynasm!(asm
+                ; .arch x64
+                ; mov rdx, QWORD addr_cycles
+                ; inc QWORD [rdx]
+            );
+
+            if !state.is_cacheable(current_pc) {
+                dynasm!(asm
+                    ; .arch x64
+                    ; mov rax, QWORD Interrupts::use_interpreter(current_pc)
+                );
+                break 'outer;
+            }
+
+            let opcode = Opcode::from((state.memory[current_pc as usize] as u16) << 8 | state.memory[current_pc as usize + 1] as u16);
             next_pc += 2;
 
             // #[cfg(debug_assertions)] println!("Compiling opcode {opcode:#04X} at {current_pc:#X}");
@@ -101,22 +111,31 @@ impl Chip8 {
             match opcode.0 >> 12 & 0xF {
                 0x0 => match opcode.0 {
                     0x00E0 => {
-                        let addr_screen = self.state.screen.address(0) as i64;
+                        let addr_screen = state.screen.pixels.address(0) as i64;
+                        let addr_planes = state.planes.address(0) as i64;
+                        let addr_dirty = state.screen_dirty.address(0) as i64;
                         dynasm!(asm
                             ; .arch x64
+                            ; mov rdx, QWORD addr_dirty
+                            ; mov BYTE [rdx], 1
+                            ; mov rdx, QWORD addr_planes // Build the mask of the unselected planes in every byte of rax.
+                            ; movzx rax, BYTE [rdx]
+                            ; mov rcx, QWORD 0x0101010101010101
+                            ; imul rax, rcx
+                            ; not rax
                             ; mov rdx, QWORD addr_screen
-                            ; mov rax, rdx
-                            ; add rax, 64 * 32
+                            ; mov rcx, rdx
+                            ; add rcx, (Screen::HIRES_WIDTH * Screen::HIRES_HEIGHT) as i32
                             ; lbl:
-                            ; mov QWORD [rdx], 0
+                            ; and QWORD [rdx], rax
                             ; add rdx, 8
-                            ; cmp rdx, rax
+                            ; cmp rdx, rcx
                             ; jb <lbl
                         );
                     },
                     0x00EE => {
-                        let sp = self.state.SP.address(0);
-                        let stack = self.state.stack.address(0);
+                        let sp = state.SP.address(0);
+                        let stack = state.stack.address(0);
                         dynasm!(asm
                             ; .arch x64
                             ; mov rdx, QWORD sp as i64
@@ -135,7 +154,22 @@ impl Chip8 {
                         );
                         break 'outer;
                     },
-                    _ => panic!("Unknown opcode {opcode:04X}"),
+                    // SUPER-CHIP screen and exit instructions.
+                    0x00C0..=0x00DF | 0x00FB..=0x00FF => {
+                        dynasm!(asm
+                            ; .arch x64
+                            ; mov rax, QWORD Interrupts::use_interpreter(current_pc)
+                        );
+                        break 'outer;
+                    },
+                    _ => {
+                        // Invalid opcode, the interpreter traps.
+                        dynasm!(asm
+                            ; .arch x64
+                            ; mov rax, QWORD Interrupts::use_interpreter(current_pc)
+                        );
+                        break 'outer;
+                    },
                 },
                 0x1 => {
                     dynasm!(asm
@@ -145,14 +179,14 @@ impl Chip8 {
                     break 'outer;
                 },
                 0x2 => {
-                    let sp = self.state.SP.address(0);
-                    let stack = self.state.stack.address(0);
+                    let sp = state.SP.address(0);
+                    let stack = state.stack.address(0);
                     let nnn = opcode.nnn();
                     dynasm!(asm
                         ; .arch x64
                         ; mov rdx, QWORD sp as i64
                         ; mov rax, QWORD [rdx]
-                        ; cmp rax, 15
+                        ; cmp rax, 16
                         ; jb >lbl
                         ; mov rax, QWORD Interrupts::use_interpreter(current_pc)
                         ; ret
@@ -169,51 +203,75 @@ impl Chip8 {
                 },
                 0x3 => {
                     let (x, kk) = opcode.xkk();
-                    let addrx = self.state.V.address(x) as i64;
+                    let addrx = state.V.address(x) as i64;
                     dynasm!(asm
                         ; .arch x64
                         ; mov rdx, QWORD addrx
                         ; mov al, BYTE [rdx]
                         ; cmp al, kk as i8
                         ; jne >lbl
-                        ; mov rax, QWORD Interrupts::jump(current_pc + 4)
-                        ; ret
+                    );
+                    skip(&mut asm, state, next_pc);
+                    dynasm!(asm
+                        ; .arch x64
                         ; lbl:
                     );
                 },
                 0x4 => {
                     let (x, kk) = opcode.xkk();
-                    let addrx = self.state.V.address(x) as i64;
+                    let addrx = state.V.address(x) as i64;
                     dynasm!(asm
                         ; .arch x64
                         ; mov rdx, QWORD addrx
                         ; mov al, BYTE [rdx]
                         ; cmp al, kk as i8
                         ; je >lbl
-                        ; mov rax, QWORD Interrupts::jump(current_pc + 4)
-                        ; ret
-                        ; lbl:
                     );
-                },
-                0x5 => {
-                    let (x, y) = opcode.xy();
-                    let addrx = self.state.V.address(x) as i64;
-                    let addry = self.state.V.address(y) as i64;
+                    skip(&mut asm, state, next_pc);
                     dynasm!(asm
                         ; .arch x64
-                        ; mov rdx, QWORD addry
-                        ; mov al, BYTE [rdx]
-                        ; mov rdx, QWORD addrx
-                        ; cmp BYTE [rdx], al
-                        ; jne >lbl
-                        ; mov rax, QWORD Interrupts::jump(current_pc + 4)
-                        ; ret
                         ; lbl:
                     );
                 },
+                0x5 => match opcode.0 & 0xF00F {
+                    0x5000 => {
+                        let (x, y) = opcode.xy();
+                        let addrx = state.V.address(x) as i64;
+                        let addry = state.V.address(y) as i64;
+                        dynasm!(asm
+                            ; .arch x64
+                            ; mov rdx, QWORD addry
+                            ; mov al, BYTE [rdx]
+                            ; mov rdx, QWORD addrx
+                            ; cmp BYTE [rdx], al
+                            ; jne >lbl
+                        );
+                        skip(&mut asm, state, next_pc);
+                        dynasm!(asm
+                            ; .arch x64
+                            ; lbl:
+                        );
+                    },
+                    // XO-CHIP register range save and load.
+                    0x5002 | 0x5003 => {
+                        dynasm!(asm
+                            ; .arch x64
+                            ; mov rax, QWORD Interrupts::use_interpreter(current_pc)
+                        );
+                        break 'outer;
+                    },
+                    _ => {
+                        // Invalid opcode, the interpreter traps.
+                        dynasm!(asm
+                            ; .arch x64
+                            ; mov rax, QWORD Interrupts::use_interpreter(current_pc)
+                        );
+                        break 'outer;
+                    },
+                },
                 0x6 => {
                     let (x, kk) = opcode.xkk();
-                    let addr = self.state.V.address(x) as i64;
+                    let addr = state.V.address(x) as i64;
                     dynasm!(asm
                         ; .arch x64
                         ; mov rdx, QWORD addr
@@ -222,7 +280,7 @@ impl Chip8 {
                 },
                 0x7 => {
                     let (x, kk) = opcode.xkk();
-                    let addr = self.state.V.address(x) as i64;
+                    let addr = state.V.address(x) as i64;
                     dynasm!(asm
                         ; .arch x64
                         ; mov rdx, QWORD addr
@@ -233,8 +291,8 @@ impl Chip8 {
                     match opcode.0 & 0xF00F {
                         0x8000 => {
                             let (x, y) = opcode.xy();
-                            let addrx = self.state.V.address(x) as i64;
-                            let addry = self.state.V.address(y) as i64;
+                            let addrx = state.V.address(x) as i64;
+                            let addry = state.V.address(y) as i64;
                             dynasm!(asm
                                 ; .arch x64
                                 ; mov rdx, QWORD addry
@@ -245,8 +303,8 @@ impl Chip8 {
                         },
                         0x8001 => {
                             let (x, y) = opcode.xy();
-                            let addrx = self.state.V.address(x) as i64;
-                            let addry = self.state.V.address(y) as i64;
+                            let addrx = state.V.address(x) as i64;
+                            let addry = state.V.address(y) as i64;
                             dynasm!(asm
                                 ; .arch x64
                                 ; mov rdx, QWORD addry
@@ -254,11 +312,19 @@ impl Chip8 {
                                 ; mov rdx, QWORD addrx
                                 ; or BYTE [rdx], al
                             );
+                            if state.quirks.vf_reset {
+                                let addrf = state.V.address(0xF) as i64;
+                                dynasm!(asm
+                                    ; .arch x64
+                                    ; mov rdx, QWORD addrf
+                                    ; mov BYTE [rdx], 0
+                                );
+                            }
                         },
                         0x8002 => {
                             let (x, y) = opcode.xy();
-                            let addrx = self.state.V.address(x) as i64;
-                            let addry = self.state.V.address(y) as i64;
+                            let addrx = state.V.address(x) as i64;
+                            let addry = state.V.address(y) as i64;
                             dynasm!(asm
                                 ; .arch x64
                                 ; mov rdx, QWORD addry
@@ -266,11 +332,19 @@ impl Chip8 {
                                 ; mov rdx, QWORD addrx
                                 ; and BYTE [rdx], al
                             );
+                            if state.quirks.vf_reset {
+                                let addrf = state.V.address(0xF) as i64;
+                                dynasm!(asm
+                                    ; .arch x64
+                                    ; mov rdx, QWORD addrf
+                                    ; mov BYTE [rdx], 0
+                                );
+                            }
                         },
                         0x8003 => {
                             let (x, y) = opcode.xy();
-                            let addrx = self.state.V.address(x) as i64;
-                            let addry = self.state.V.address(y) as i64;
+                            let addrx = state.V.address(x) as i64;
+                            let addry = state.V.address(y) as i64;
                             dynasm!(asm
                                 ; .arch x64
                                 ; mov rdx, QWORD addry
@@ -278,12 +352,20 @@ impl Chip8 {
                                 ; mov rdx, QWORD addrx
                                 ; xor BYTE [rdx], al
                             );
+                            if state.quirks.vf_reset {
+                                let addrf = state.V.address(0xF) as i64;
+                                dynasm!(asm
+                                    ; .arch x64
+                                    ; mov rdx, QWORD addrf
+                                    ; mov BYTE [rdx], 0
+                                );
+                            }
                         },
                         0x8004 => {
                             let (x, y) = opcode.xy();
-                            let addrx = self.state.V.address(x) as i64;
-                            let addry = self.state.V.address(y) as i64;
-                            let addrf = self.state.V.address(0xF) as i64;
+                            let addrx = state.V.address(x) as i64;
+                            let addry = state.V.address(y) as i64;
+                            let addrf = state.V.address(0xF) as i64;
                             dynasm!(asm
                                 ; .arch x64
                                 ; mov rdx, QWORD addry
@@ -296,9 +378,9 @@ impl Chip8 {
                         },
                         0x8005 => {
                             let (x, y) = opcode.xy();
-                            let addrx = self.state.V.address(x) as i64;
-                            let addry = self.state.V.address(y) as i64;
-                            let addrf = self.state.V.address(0xF) as i64;
+                            let addrx = state.V.address(x) as i64;
+                            let addry = state.V.address(y) as i64;
+                            let addrf = state.V.address(0xF) as i64;
                             dynasm!(asm
                                 ; .arch x64
                                 ; mov rdx, QWORD addry
@@ -310,24 +392,27 @@ impl Chip8 {
                             );
                         },
                         0x8006 => {
-                            let x = opcode.x();
-                            // let y = opcode.y();
-                            let addrx = self.state.V.address(x) as i64;
-                            // let addry = self.state.V.address(y) as i64;
-                            let addrf = self.state.V.address(0xF) as i64;
+                            let (x, y) = opcode.xy();
+                            let src = if state.quirks.shift_vx { x } else { y };
+                            let addrx = state.V.address(x) as i64;
+                            let addrsrc = state.V.address(src) as i64;
+                            let addrf = state.V.address(0xF) as i64;
                             dynasm!(asm
                                 ; .arch x64
+                                ; mov rdx, QWORD addrsrc
+                                ; mov al, BYTE [rdx]
+                                ; shr al, 1
                                 ; mov rdx, QWORD addrx
-                                ; shr BYTE [rdx], 1
+                                ; mov BYTE [rdx], al // mov does not modify the carry flag.
                                 ; mov rdx, QWORD addrf
                                 ; setc BYTE [rdx]
                             );
                         },
                         0x8007 => {
                             let (x, y) = opcode.xy();
-                            let addrx = self.state.V.address(x) as i64;
-                            let addry = self.state.V.address(y) as i64;
-                            let addrf = self.state.V.address(0xF) as i64;
+                            let addrx = state.V.address(x) as i64;
+                            let addry = state.V.address(y) as i64;
+                            let addrf = state.V.address(0xF) as i64;
                             dynasm!(asm
                                 ; .arch x64
                                 ; mov rdx, QWORD addry
@@ -341,41 +426,63 @@ impl Chip8 {
                             );
                         },
                         0x800E => {
-                            let x = opcode.x();
-                            // let y = opcode.y();
-                            let addrx = self.state.V.address(x) as i64;
-                            // let addry = self.state.V.address(y) as i64;
-                            let addrf = self.state.V.address(0xF) as i64;
+                            let (x, y) = opcode.xy();
+                            let src = if state.quirks.shift_vx { x } else { y };
+                            let addrx = state.V.address(x) as i64;
+                            let addrsrc = state.V.address(src) as i64;
+                            let addrf = state.V.address(0xF) as i64;
                             dynasm!(asm
                                 ; .arch x64
+                                ; mov rdx, QWORD addrsrc
+                                ; mov al, BYTE [rdx]
+                                ; shl al, 1
                                 ; mov rdx, QWORD addrx
-                                ; shl BYTE [rdx], 1
+                                ; mov BYTE [rdx], al // mov does not modify the carry flag.
                                 ; mov rdx, QWORD addrf
                                 ; setc BYTE [rdx]
                             );
                         },
-                        _ => panic!("Unknown opcode {opcode:04X}"),
+                        _ => {
+                            // Invalid opcode, the interpreter traps.
+                            dynasm!(asm
+                                ; .arch x64
+                                ; mov rax, QWORD Interrupts::use_interpreter(current_pc)
+                            );
+                            break 'outer;
+                        },
                     }
                 },
-                0x9 => {
-                    let (x, y) = opcode.xy();
-                    let addrx = self.state.V.address(x) as i64;
-                    let addry = self.state.V.address(y) as i64;
-                    dynasm!(asm
-                        ; .arch x64
-                        ; mov rdx, QWORD addry
-                        ; mov al, BYTE [rdx]
-                        ; mov rdx, QWORD addrx
-                        ; cmp BYTE [rdx], al
-                        ; je >lbl
-                        ; mov rax, QWORD Interrupts::jump(current_pc + 4)
-                        ; ret
-                        ; lbl:
-                    );
+                0x9 => match opcode.0 & 0xF00F {
+                    0x9000 => {
+                        let (x, y) = opcode.xy();
+                        let addrx = state.V.address(x) as i64;
+                        let addry = state.V.address(y) as i64;
+                        dynasm!(asm
+                            ; .arch x64
+                            ; mov rdx, QWORD addry
+                            ; mov al, BYTE [rdx]
+                            ; mov rdx, QWORD addrx
+                            ; cmp BYTE [rdx], al
+                            ; je >lbl
+                        );
+                        skip(&mut asm, state, next_pc);
+                        dynasm!(asm
+                            ; .arch x64
+                            ; lbl:
+                        );
+                    },
+                    _ => {
+                        // Invalid opcode, the interpreter traps.
+                        dynasm!(asm
+                            ; .arch x64
+                            ; mov rax, QWORD Interrupts::use_interpreter(current_pc)
+                        );
+                        break 'outer;
+                    },
                 },
                 0xA => {
                     let nnn = opcode.nnn();
-                    let addr = self.state.I.address(0) as i64;
+                    let addr = state.I.address(0) as i64;
                     dynasm!(asm
                         ; .arch x64
                         ; mov rdx, QWORD addr
@@ -384,22 +491,41 @@ impl Chip8 {
                 },
                 0xB => {
                     let nnn = opcode.nnn();
-                    let addr0 = self.state.V.address(0) as i64;
+                    let x = if state.quirks.jump_vx { opcode.x() } else { 0 };
+                    let addrx = state.V.address(x) as i64;
                     dynasm!(asm
                         ; .arch x64
                         ; mov rax, QWORD Interrupts::jump(nnn)
-                        ; mov rdx, QWORD addr0
+                        ; mov rdx, QWORD addrx
                         ; movzx dx, BYTE [rdx]
                         ; add ax, dx
                     );
                     break 'outer;
                 },
                 0xC => {
+                    // Inlined xorshift64 step of Rng::next_byte.
+                    let (x, kk) = opcode.xkk();
+                    let addr_rng = state.rng.state.address(0) as i64;
+                    let addrx = state.V.address(x) as i64;
                     dynasm!(asm
                         ; .arch x64
-                        ; mov rax, QWORD Interrupts::use_interpreter(current_pc)
+                        ; mov rdx, QWORD addr_rng
+                        ; mov rax, QWORD [rdx]
+                        ; mov rcx, rax
+                        ; shl rcx, 13
+                        ; xor rax, rcx
+                        ; mov rcx, rax
+                        ; shr rcx, 7
+                        ; xor rax, rcx
+                        ; mov rcx, rax
+                        ; shl rcx, 17
+                        ; xor rax, rcx
+                        ; mov QWORD [rdx], rax
+                        ; shr rax, 56
+                        ; and al, kk as i8
+                        ; mov rdx, QWORD addrx
+                        ; mov BYTE [rdx], al
                     );
-                    break 'outer;
                 },
                 0xD => {
                     dynasm!(asm
@@ -412,49 +538,88 @@ impl Chip8 {
                     match opcode.0 & 0xF0FF {
                         0xE09E => {
                             let x = opcode.x();
-                            let addrx = self.state.V.address(x) as i64;
-                            let addr_keys = self.state.keys.address(0) as i64;
+                            let addrx = state.V.address(x) as i64;
+                            let addr_keys = state.keys.address(0) as i64;
                             dynasm!(asm
                                 ; .arch x64
                                 ; mov rdx, QWORD addrx
                                 ; movzx rax, BYTE [rdx]
+                                ; and rax, 0xF
                                 ; mov rdx, QWORD addr_keys
                                 ; add rdx, rax
                                 ; mov al, BYTE [rdx]
                                 ; cmp al, 0
                                 ; je >lbl
-                                ; mov rax, QWORD Interrupts::jump(current_pc + 4)
-                                ; ret
+                            );
+                            skip(&mut asm, state, next_pc);
+                            dynasm!(asm
+                                ; .arch x64
                                 ; lbl:
                             );
                         },
                         0xE0A1 => {
                             let x = opcode.x();
-                            let addrx = self.state.V.address(x) as i64;
-                            let addr_keys = self.state.keys.address(0) as i64;
+                            let addrx = state.V.address(x) as i64;
+                            let addr_keys = state.keys.address(0) as i64;
                             dynasm!(asm
                                 ; .arch x64
                                 ; mov rdx, QWORD addrx
                                 ; movzx rax, BYTE [rdx]
+                                ; and rax, 0xF
                                 ; mov rdx, QWORD addr_keys
                                 ; add rdx, rax
                                 ; mov al, BYTE [rdx]
                                 ; cmp al, 0
                                 ; jne >lbl
-                                ; mov rax, QWORD Interrupts::jump(current_pc + 4)
-                                ; ret
+                            );
+                            skip(&mut asm, state, next_pc);
+                            dynasm!(asm
+                                ; .arch x64
                                 ; lbl:
                             );
                         },
-                        _ => panic!("Unknown opcode {opcode:04X}"),
+                        _ => {
+                            // Invalid opcode, the interpreter traps.
+                            dynasm!(asm
+                                ; .arch x64
+                                ; mov rax, QWORD Interrupts::use_interpreter(current_pc)
+                            );
+                            break 'outer;
+                        },
                     }
                 },
                 0xF => {
                     match opcode.0 & 0xF0FF {
+                        0xF000 if opcode.0 == 0xF000 => {
+                            let nnnn = (state.memory[next_pc as usize] as u16) << 8 | state.memory[next_pc as usize + 1] as u16;
+                            let addri = state.I.address(0) as i64;
+                            dynasm!(asm
+                                ; .arch x64
+                                ; mov rdx, QWORD addri
+                                ; mov WORD [rdx], nnnn as i16
+                            );
+                            next_pc += 2;
+                        },
+                        0xF001 => {
+                            let planes = opcode.x() as u8 & Screen::ALL_PLANES;
+                            let addr_planes = state.planes.address(0) as i64;
+                            dynasm!(asm
+                                ; .arch x64
+                                ; mov rdx, QWORD addr_planes
+                                ; mov BYTE [rdx], planes as i8
+                            );
+                        },
+                        0xF002 if opcode.0 == 0xF002 => {
+                            dynasm!(asm
+                                ; .arch x64
+                                ; mov rax, QWORD Interrupts::use_interpreter(current_pc)
+                            );
+                            break 'outer;
+                        },
                         0xF007 => {
                             let x = opcode.x();
-                            let addrx = self.state.V.address(x) as i64;
-                            let addrdt = self.state.delay.address(0) as i64;
+                            let addrx = state.V.address(x) as i64;
+                            let addrdt = state.delay.address(0) as i64;
                             dynasm!(asm
                                 ; .arch x64
                                 ; mov rdx, QWORD addrdt
@@ -472,8 +637,8 @@ impl Chip8 {
                         },
                         0xF015 => {
                             let x = opcode.x();
-                            let addrx = self.state.V.address(x) as i64;
-                            let addrdt = self.state.delay.address(0) as i64;
+                            let addrx = state.V.address(x) as i64;
+                            let addrdt = state.delay.address(0) as i64;
                             dynasm!(asm
                                 ; .arch x64
                                 ; mov rdx, QWORD addrx
@@ -484,8 +649,8 @@ impl Chip8 {
                         },
                         0xF018 => {
                             let x = opcode.x();
-                            let addrx = self.state.V.address(x) as i64;
-                            let addrsound = self.state.sound.address(0) as i64;
+                            let addrx = state.V.address(x) as i64;
+                            let addrsound = state.sound.address(0) as i64;
                             dynasm!(asm
                                 ; .arch x64
                                 ; mov rdx, QWORD addrx
@@ -496,8 +661,8 @@ impl Chip8 {
                         },
                         0xF01E => {
                             let x = opcode.x();
-                            let addrx = self.state.V.address(x) as i64;
-                            let addri = self.state.I.address(0) as i64;
+                            let addrx = state.V.address(x) as i64;
+                            let addri = state.I.address(0) as i64;
                             dynasm!(asm
                                 ; .arch x64
                                 ; mov rdx, QWORD addrx
@@ -508,25 +673,70 @@ impl Chip8 {
                         },
                         0xF029 => {
                             let x = opcode.x();
-                            let addrx = self.state.V.address(x) as i64;
-                            let addri = self.state.I.address(0) as i64;
+                            let addrx = state.V.address(x) as i64;
+                            let addri = state.I.address(0) as i64;
+                            let font_address = state.font_address;
                             dynasm!(asm
                                 ; .arch x64
                                 ; mov rdx, QWORD addrx
                                 ; mov al, BYTE [rdx]
+                                ; and al, 0xF
                                 ; mov dl, 5
                                 ; mul dl
+                                ; add ax, font_address as i16
                                 ; mov rdx, QWORD addri
                                 ; mov WORD [rdx], ax
                             );
                         },
+                        0xF030 => {
+                            let x = opcode.x();
+                            let addrx = state.V.address(x) as i64;
+                            let addri = state.I.address(0) as i64;
+                            let big_font_address = state.big_font_address;
+                            dynasm!(asm
+                                ; .arch x64
+                                ; mov rdx, QWORD addrx
+                                ; mov al, BYTE [rdx]
+                                ; and al, 0xF
+                                ; mov dl, 10
+                                ; mul dl
+                                ; add ax, big_font_address as i16
+                                ; mov rdx, QWORD addri
+                                ; mov WORD [rdx], ax
+                            );
+                        },
+                        0xF03A => {
+                            let x = opcode.x();
+                            let addrx = state.V.address(x) as i64;
+                            let addrpitch = state.pitch.address(0) as i64;
+                            dynasm!(asm
+                                ; .arch x64
+                                ; mov rdx, QWORD addrx
+                                ; mov al, BYTE [rdx]
+                                ; mov rdx, QWORD addrpitch
+                                ; mov BYTE [rdx], al
+                            );
+                        },
                         0xF033 => {
                             let x = opcode.x();
-                            let addrx = self.state.V.address(x) as i64;
-                            let addri = self.state.I.address(0) as i64;
-                            let addrmem = self.state.memory.address(0) as i64;
+                            let addrx = state.V.address(x) as i64;
+                            let addri = state.I.address(0) as i64;
+                            let addrmem = state.memory.address(0) as i64;
                             let int_invalidate = Interrupts::invalidate as *const ();
 
+                            // Let the interpreter trap if the access goes past the end of memory.
+                            let max_i = (state.memory_size - 3) as i16;
+                            dynasm!(asm
+                                ; .arch x64
+                                ; mov rdx, QWORD addri
+                                ; cmp WORD [rdx], max_i
+                                ; jbe >in_range
+                                ; mov rax, QWORD Interrupts::use_interpreter(current_pc)
+                                ; ret
+                                ; in_range:
+                            );
+                            emit_watch_check(&mut asm, state, &state.watch_writes, 3, current_pc);
+
                             dynasm!(asm
                                 ; .arch x64
                                 ; mov rdx, QWORD addri
@@ -578,12 +788,25 @@ impl Chip8 {
                         },
                         0xF055 => {
                             let x = opcode.x();
-                            let addr0 = self.state.V.address(0) as i64;
-                            let addrlast = self.state.V.address(x) as i64;
-                            let addri = self.state.I.address(0) as i64;
-                            let addrmem = self.state.memory.address(0) as i64;
+                            let addr0 = state.V.address(0) as i64;
+                            let addrlast = state.V.address(x) as i64;
+                            let addri = state.I.address(0) as i64;
+                            let addrmem = state.memory.address(0) as i64;
                             let int_invalidate = Interrupts::invalidate as *const ();
 
+                            // Let the interpreter trap if the access goes past the end of memory.
+                            let max_i = (state.memory_size - (x + 1)) as i16;
+                            dynasm!(asm
+                                ; .arch x64
+                                ; mov rdx, QWORD addri
+                                ; cmp WORD [rdx], max_i
+                                ; jbe >in_range
+                                ; mov rax, QWORD Interrupts::use_interpreter(current_pc)
+                                ; ret
+                                ; in_range:
+                            );
+                            emit_watch_check(&mut asm, state, &state.watch_writes, x + 1, current_pc);
+
                             dynasm!(asm
                                 ; .arch x64
                                 ; mov rdx, QWORD addri
@@ -632,14 +855,36 @@ impl Chip8 {
                                 ; pop rsi
                                 ; pop rdi
                             );
+
+                            if state.quirks.increment_i {
+                                dynasm!(asm
+                                    ; .arch x64
+                                    ; mov rdx, QWORD addri
+                                    ; add WORD [rdx], (x + 1) as i16
+                                );
+                            }
                             break 'outer;
                         },
                         0xF065 => {
                             let x = opcode.x();
-                            let addr0 = self.state.V.address(0) as i64;
-                            let addrlast = self.state.V.address(x) as i64;
-                            let addri = self.state.I.address(0) as i64;
-                            let addrmem = self.state.memory.address(0) as i64;
+                            let addr0 = state.V.address(0) as i64;
+                            let addrlast = state.V.address(x) as i64;
+                            let addri = state.I.address(0) as i64;
+                            let addrmem = state.memory.address(0) as i64;
+
+                            // Let the interpreter trap if the access goes past the end of memory.
+                            let max_i = (state.memory_size - (x + 1)) as i16;
+                            dynasm!(asm
+                                ; .arch x64
+                                ; mov rdx, QWORD addri
+                                ; cmp WORD [rdx], max_i
+                                ; jbe >in_range
+                                ; mov rax, QWORD Interrupts::use_interpreter(current_pc)
+                                ; ret
+                                ; in_range:
+                            );
+                            emit_watch_check(&mut asm, state, &state.watch_reads, x + 1, current_pc);
+
                             dynasm!(asm
                                 ; .arch x64
                                 ; mov rdx, QWORD addri
@@ -658,11 +903,40 @@ impl Chip8 {
                                 ; jmp <lbl
                                 ; end:
                             );
+
+                            if state.quirks.increment_i {
+                                dynasm!(asm
+                                    ; .arch x64
+                                    ; mov rdx, QWORD addri
+                                    ; add WORD [rdx], (x + 1) as i16
+                                );
+                            }
+                        },
+                        0xF075 | 0xF085 => {
+                            dynasm!(asm
+                                ; .arch x64
+                                ; mov rax, QWORD Interrupts::use_interpreter(current_pc)
+                            );
+                            break 'outer;
+                        },
+                        _ => {
+                            // Invalid opcode, the interpreter traps.
+                            dynasm!(asm
+                                ; .arch x64
+                                ; mov rax, QWORD Interrupts::use_interpreter(current_pc)
+                            );
+                            break 'outer;
                         },
-                        _ => panic!("Unknown opcode {opcode:04X}"),
                     }
                 },
-                _ => panic!("Unknown opcode {opcode:04X}"),
+                _ => {
+                    // Invalid opcode, the interpreter traps.
+                    dynasm!(asm
+                        ; .arch x64
+                        ; mov rax, QWORD Interrupts::use_interpreter(current_pc)
+                    );
+                    break 'outer;
+                },
             };
         }
 
@@ -671,16 +945,136 @@ impl Chip8 {
             ; ret
         );
 
-        self.jit_caches.add(block_pc, next_pc, asm.finalize().unwrap());
+        self.caches.add(block_pc, next_pc, len, asm.finalize().unwrap());
+    }
+}
+
+impl Default for Jit {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ExecutionBackend for Jit {
+    /// Executes a compiled block of instructions.
+    ///
+    /// If the block is longer than `budget` instructions, a single instruction is interpreted instead.
+    fn step(&mut self, state: &mut State, budget: u64) {
+        // The compiled code accesses the state at its address.
+        let state_address = state as *const State as usize;
+        if self.state_address != state_address {
+            self.caches.clear();
+            self.state_address = state_address;
+        }
+
+        if self.caches.get(state.PC).is_some() {
+            self.stats.cache_hits += 1;
+        } else {
+            let start = Instant::now();
+            self.compile_block(state, state.PC);
+            self.stats.compile_time += start.elapsed();
+            self.stats.cache_misses += 1;
+            self.stats.blocks_compiled += 1;
+        }
+
+        let cache = self.caches.get(state.PC).unwrap();
+        if cache.len() > budget {
+            let ret = state.interpret_instruction();
+            if ret > 1 {
+                self.stats.invalidations += 1;
+                self.caches.invalidate((ret >> 16) as u16, ret as u16);
+            }
+            return;
+        }
+
+        self.stats.blocks_executed += 1;
+        let ret = cache.run();
+        match Interrupts::from(ret >> 16 & 0xFFFF) {
+            Interrupts::UseInterpreter => {
+                state.PC = ret as u16;
+                // The compiled code already counted the instruction, and the interpreter counts it again.
+                state.cycles -= 1;
+                let ret = state.interpret_instruction();
+                if ret > 1 {
+                    self.stats.invalidations += 1;
+                    self.caches.invalidate((ret >> 16) as u16, ret as u16);
+                }
+            },
+            Interrupts::Jump => state.PC = ret as u16,
+            Interrupts::InvalidateCache => {
+                state.PC = ret as u16;
+                let beg_addr = (ret >> 48) as u16;
+                let end_addr = (ret >> 32) as u16;
+
+                self.stats.invalidations += 1;
+                self.caches.invalidate(beg_addr, end_addr);
+            },
+        }
+    }
+
+    fn invalidate(&mut self, beg_addr: u16, end_addr: u16) {
+        self.caches.invalidate(beg_addr, end_addr);
+    }
+
+    fn reset(&mut self) {
+        self.caches.clear();
+    }
+
+    fn add_breakpoint(&mut self, pc: u16) {
+        self.breakpoints.insert(pc);
+        self.caches.split(pc);
+    }
+
+    fn remove_breakpoint(&mut self, pc: u16) {
+        self.breakpoints.remove(&pc);
+        self.caches.remove_ending_at(pc);
+    }
+
+    fn stats(&self) -> Stats {
+        self.stats
     }
 }
 
-#[cfg(all(target_os = "windows", target_arch = "x86_64"))]
-extern "win64" fn handle_timers(this: &mut Chip8) {
-    this.handle_timers();
+/// Emits the check of the `len` bytes at I against the given watched range of the state. When they overlap, the
+/// compiled code exits to the interpreter which executes the instruction and records the access.
+fn emit_watch_check(asm: &mut Assembler, state: &State, range: &(u16, u16), len: usize, current_pc: u16) {
+    let addri = state.I.address(0) as i64;
+    let addr_beg = range.0.address(0) as i64;
+    let addr_end = range.1.address(0) as i64;
+    dynasm!(asm
+        ; .arch x64
+        ; mov rdx, QWORD addri
+        ; movzx rax, WORD [rdx]
+        ; mov rdx, QWORD addr_end
+        ; movzx rcx, WORD [rdx]
+        ; cmp rax, rcx
+        ; ja >not_watched
+        ; add rax, (len - 1) as i32
+        ; mov rdx, QWORD addr_beg
+        ; movzx rcx, WORD [rdx]
+        ; cmp rax, rcx
+        ; jb >not_watched
+        ; mov rax, QWORD Interrupts::use_interpreter(current_pc)
+        ; ret
+        ; not_watched:
+    );
 }
 
-#[cfg(all(not(target_os = "windows"), target_arch = "x86_64"))]
-extern "sysv64" fn handle_timers(this: &mut Chip8) {
-    this.handle_timers();
+/// Emits the exit of a block that skips the instruction at `next_pc`.
+///
+/// The XO-CHIP long I load F000 NNNN is 4 bytes long. Its length is read at run time like the interpreter does, as the
+/// skipped instruction is outside of the block when the skip is its last instruction, and may then be modified without
+/// invalidating the block. The skip being cacheable, the skipped instruction is in memory.
+fn skip(asm: &mut Assembler, state: &State, next_pc: u16) {
+    let addr_next = state.memory.address(next_pc as usize) as i64;
+    dynasm!(asm
+        ; .arch x64
+        ; mov rax, QWORD Interrupts::jump(next_pc.wrapping_add(2))
+        ; mov rdx, QWORD addr_next
+        ; cmp WORD [rdx], 0x00F0 // The bytes F0 00 read as a little-endian word.
+        ; jne >not_long
+        ; mov rax, QWORD Interrupts::jump(next_pc.wrapping_add(4))
+        ; not_long:
+        ; ret
+    );
 }
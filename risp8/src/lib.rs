@@ -1,31 +1,82 @@
 //! Experimental Chip8 interpreter, cached interpreter and Just-In-Time compiler.
+//!
+//! The `std` feature, enabled by default, provides the channel-based [Chip8::run] loop, loading ROM files, the rewind
+//! buffer and the JIT. Without it the crate only requires `alloc`, and the core is driven with the synchronous API.
 
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
 pub use kanal::{Receiver, Sender};
-use kanal::unbounded;
 
-#[cfg(target_arch = "x86_64")]
+mod backend;
+mod breakpoint;
+mod builder;
+#[cfg(all(feature = "std", target_arch = "x86_64"))]
 mod cache;
 mod cached_interpreter;
 mod cached_interpreter_2;
 mod cached_interpreter_3;
+#[cfg(feature = "std")]
+mod env;
+mod error;
+mod expression;
+mod font;
 mod interpreter;
-#[cfg(target_arch = "x86_64")]
+#[cfg(all(feature = "std", target_arch = "x86_64"))]
 mod jit;
+mod movie;
 mod opcode;
+mod platform;
+#[cfg(feature = "std")]
+mod rewind;
+mod rng;
+mod savestate;
+mod screen;
+mod trap;
+mod watchpoint;
+
+pub use backend::{ExecutionBackend, Stats};
+pub use breakpoint::Breakpoint;
+pub use builder::Chip8Builder;
+pub use cached_interpreter::CachedInterpreter;
+pub use cached_interpreter_2::CachedInterpreter2;
+pub use cached_interpreter_3::CachedInterpreter3;
+#[cfg(feature = "std")]
+pub use env::{DoneCondition, MemoryValue, Step, VecEnv, VecEnvBuilder};
+pub use error::{ExpressionError, FontError, LoadError, MovieError, SaveStateError, StateError};
+pub use expression::{Expression, LogMessage};
+pub use font::Font;
+pub use interpreter::Interpreter;
+#[cfg(all(feature = "std", target_arch = "x86_64"))]
+pub use jit::Jit;
+pub use movie::{Movie, MovieChecksum, MovieInput, MovieMode};
+pub use platform::Platform;
+pub use screen::Screen;
+pub use trap::Trap;
+pub use watchpoint::{Watchpoint, WatchpointHit, WatchKind};
 
-#[cfg(target_arch = "x86_64")]
-use cache::Caches;
+use movie::ActiveMovie;
+#[cfg(feature = "std")]
+use rewind::Rewind;
+use rng::Rng;
+use watchpoint::{WatchedAccess, NO_WATCH};
 
-use cached_interpreter::{InstructionCache, CachedInstruction};
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
 
+#[cfg(feature = "std")]
 use std::fs::read;
-use std::io::Error;
-use std::time::{Duration, Instant};
+#[cfg(feature = "std")]
+use std::thread::sleep;
+#[cfg(feature = "std")]
+use std::time::Duration;
 
-/// The underlying type that represents the Chip8 screen.
-pub type Screen = [[bool; State::SCREEN_WIDTH]; State::SCREEN_HEIGHT];
 /// The default value of the screen.
-pub const DEFAULT_SCREEN: Screen = [[false; State::SCREEN_WIDTH]; State::SCREEN_HEIGHT];
+pub const DEFAULT_SCREEN: Screen = Screen::new();
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 enum WaitKey {
@@ -44,102 +95,397 @@ pub struct State {
     stack: [u16; 16],
     V: [u8; 16],
     memory: [u8; Self::MEMORY_SIZE],
+    /// The size of the memory available to the program, at most [State::MEMORY_SIZE].
+    memory_size: usize,
     delay: u8,
     sound: u8,
     screen: Screen,
+    /// Set when the screen is modified, cleared when it is sent in [Risp8Answer::Frame].
+    screen_dirty: bool,
     keys: [bool; 16],
+    /// SUPER-CHIP RPL user flags.
+    rpl: [u8; 16],
+    /// XO-CHIP bitplanes selected for drawing, as a mask of [Screen::ALL_PLANES].
+    planes: u8,
+    /// XO-CHIP audio pattern buffer.
+    audio_pattern: [u8; 16],
+    /// XO-CHIP audio pitch register.
+    pitch: u8,
+    /// Address of the small font, used by Fx29.
+    font_address: u16,
+    /// Address of the SUPER-CHIP big font, used by Fx30.
+    big_font_address: u16,
+    /// Random number generator of Cxkk.
+    rng: Rng,
 
     wait_key: WaitKey,
+    quirks: Quirks,
+    /// Set by 00FD, the program requested to exit.
+    exited: bool,
+    /// Set when an instruction faulted.
+    trap: Option<Trap>,
+    /// Number of instructions executed.
+    cycles: u64,
+    /// Number of instructions executed since the last 60Hz timer tick.
+    timer_phase: u32,
+    /// Address range modified through the public API, whose caches have to be invalidated before executing.
+    invalidation: Option<(u16, u16)>,
+    /// Smallest address ranges containing every read and write watchpoint, set by the core before executing.
+    watch_reads: (u16, u16),
+    watch_writes: (u16, u16),
+    /// The access of the last executed instruction to the watched ranges.
+    watched_access: Option<WatchedAccess>,
 }
 
 impl State {
-    pub const SCREEN_WIDTH: usize = 64;
-    pub const SCREEN_HEIGHT: usize = 32;
+    /// The lowest address programs are loaded at. No code is cached below it.
     const INITIAL_PC: usize = 0x200; // 512.
-    const MEMORY_SIZE: usize = 0x1000; // 4096.
+    const MEMORY_SIZE: usize = 0x10000; // 65536, the XO-CHIP address space.
     pub const MAX_PROGRAM_LEN: usize = Self::MEMORY_SIZE - Self::INITIAL_PC;
 
     /// Returns a new chip-8 state with the given program loaded.
-    pub fn new(program: &[u8]) -> Self {
-        Self {
+    ///
+    /// It uses the default [Quirks], 64KiB of memory, loads the program at 0x200 and uses the [CHIP-48 font](Font::CHIP48).
+    /// The random number generator is seeded with 0.
+    pub fn new(program: &[u8]) -> Result<Self, LoadError> {
+        Self::with_config(program, Quirks::default(), Self::MEMORY_SIZE, Self::INITIAL_PC, &Font::CHIP48)
+    }
+
+    /// Returns a new chip-8 state with the given program loaded, configured for the given platform.
+    pub fn with_platform(program: &[u8], platform: Platform) -> Result<Self, LoadError> {
+        Self::with_config(program, platform.quirks(), platform.memory_size(), platform.load_address() as usize, &platform.font())
+    }
+
+    fn with_config(program: &[u8], quirks: Quirks, memory_size: usize, load_address: usize, font: &Font) -> Result<Self, LoadError> {
+        let mut state = Self {
             SP: 0,
-            PC: Self::INITIAL_PC as u16,
+            PC: load_address as u16,
             I: 0,
             stack: [0; 16],
             V: [0; 16],
-            memory: Self::new_memory(program),
+            memory: Self::new_memory(program, memory_size, load_address)?,
+            memory_size,
             delay: 0,
             sound: 0,
             screen: DEFAULT_SCREEN,
+            screen_dirty: false,
             keys: [false; 16],
+            rpl: [0; 16],
+            planes: 1,
+            audio_pattern: [0; 16],
+            pitch: 64,
+            font_address: 0,
+            big_font_address: 0,
+            rng: Rng::new(0),
 
             wait_key: WaitKey::NotWaiting,
+            quirks,
+            exited: false,
+            trap: None,
+            cycles: 0,
+            timer_phase: 0,
+            invalidation: None,
+            watch_reads: NO_WATCH,
+            watch_writes: NO_WATCH,
+            watched_access: None,
+        };
+        state.set_font(font);
+
+        Ok(state)
+    }
+
+    /// Returns the quirks currently used to execute instructions.
+    pub const fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    /// Sets the quirks used to execute instructions.
+    pub const fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+        // The JIT generates code for the quirks active at compile time.
+        self.invalidate(0, u16::MAX);
+    }
+
+    /// Returns the font set currently loaded in memory.
+    pub fn font(&self) -> Font {
+        let small_address = self.font_address as usize;
+        let big_address = self.big_font_address as usize;
+        let mut small = [0; Font::SMALL_LEN];
+        let mut big = [0; Font::BIG_LEN];
+        small.copy_from_slice(&self.memory[small_address..small_address + Font::SMALL_LEN]);
+        big.copy_from_slice(&self.memory[big_address..big_address + Font::BIG_LEN]);
+
+        // Save states may place the fonts over each other.
+        Font::new(small, big).with_addresses_unchecked(self.font_address, self.big_font_address)
+    }
+
+    /// Loads the given font set in memory. Fx29 and Fx30 then point into it.
+    pub fn set_font(&mut self, font: &Font) {
+        let small_address = font.small_address() as usize;
+        let big_address = font.big_address() as usize;
+        self.memory[small_address..small_address + Font::SMALL_LEN].copy_from_slice(font.small());
+        self.memory[big_address..big_address + Font::BIG_LEN].copy_from_slice(font.big());
+        self.font_address = font.small_address();
+        self.big_font_address = font.big_address();
+        // The JIT generates code for the font addresses active at compile time.
+        self.invalidate(0, u16::MAX);
+    }
+
+    /// Returns the program counter.
+    pub const fn pc(&self) -> u16 {
+        self.PC
+    }
+
+    /// Sets the program counter. The instruction at `pc` must be in memory.
+    pub fn set_pc(&mut self, pc: u16) -> Result<(), StateError> {
+        if pc as usize + 2 > self.memory_size {
+            return Err(StateError::AddressOutOfRange { address: pc, len: 2 });
+        }
+        self.PC = pc;
+        Ok(())
+    }
+
+    /// Returns the I register.
+    pub const fn i(&self) -> u16 {
+        self.I
+    }
+
+    /// Sets the I register.
+    pub const fn set_i(&mut self, i: u16) {
+        self.I = i;
+    }
+
+    /// Returns the stack pointer, which is the number of addresses on the stack.
+    pub const fn sp(&self) -> usize {
+        self.SP
+    }
+
+    /// Sets the stack pointer, at most 16.
+    pub fn set_sp(&mut self, sp: usize) -> Result<(), StateError> {
+        if sp > self.stack.len() {
+            return Err(StateError::InvalidStackPointer(sp));
         }
+        self.SP = sp;
+        Ok(())
+    }
+
+    /// Returns the stack. Only the first [sp](Self::sp) addresses are in use.
+    pub const fn stack(&self) -> &[u16; 16] {
+        &self.stack
+    }
+
+    /// Sets the address at the given index of the stack.
+    pub fn set_stack(&mut self, index: usize, address: u16) -> Result<(), StateError> {
+        let entry = self.stack.get_mut(index).ok_or(StateError::InvalidIndex(index))?;
+        *entry = address;
+        Ok(())
+    }
+
+    /// Returns the V registers.
+    pub const fn v(&self) -> &[u8; 16] {
+        &self.V
+    }
+
+    /// Sets the register VX.
+    pub fn set_v(&mut self, x: usize, value: u8) -> Result<(), StateError> {
+        let register = self.V.get_mut(x).ok_or(StateError::InvalidIndex(x))?;
+        *register = value;
+        Ok(())
+    }
+
+    /// Returns the memory available to the program.
+    pub fn memory(&self) -> &[u8] {
+        &self.memory[..self.memory_size]
+    }
+
+    /// Returns the size of the memory available to the program.
+    pub const fn memory_size(&self) -> usize {
+        self.memory_size
+    }
+
+    /// Writes `data` in memory at `address`.
+    ///
+    /// The caches of the modified code are invalidated before the next instruction is executed.
+    pub fn write_memory(&mut self, address: u16, data: &[u8]) -> Result<(), StateError> {
+        let beg = address as usize;
+        let end = beg + data.len();
+        if end > self.memory_size {
+            return Err(StateError::AddressOutOfRange { address, len: data.len() });
+        }
+
+        if !data.is_empty() {
+            self.memory[beg..end].copy_from_slice(data);
+            self.invalidate(address, (end - 1) as u16);
+        }
+        Ok(())
+    }
+
+    /// Returns the delay timer.
+    pub const fn delay(&self) -> u8 {
+        self.delay
+    }
+
+    /// Sets the delay timer.
+    pub const fn set_delay(&mut self, delay: u8) {
+        self.delay = delay;
+    }
+
+    /// Returns the sound timer.
+    pub const fn sound(&self) -> u8 {
+        self.sound
+    }
+
+    /// Sets the sound timer.
+    pub const fn set_sound(&mut self, sound: u8) {
+        self.sound = sound;
+    }
+
+    /// Returns the state of the keys, true if pressed.
+    pub const fn keys(&self) -> &[bool; 16] {
+        &self.keys
+    }
+
+    /// Returns the screen.
+    pub const fn screen(&self) -> &Screen {
+        &self.screen
+    }
+
+    /// Returns the number of instructions executed since the start.
+    pub const fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Marks the given address range as modified, so the caches are invalidated before executing.
+    const fn invalidate(&mut self, beg_addr: u16, end_addr: u16) {
+        self.invalidation = Some(match self.invalidation {
+            Some((beg, end)) => (if beg < beg_addr { beg } else { beg_addr }, if end > end_addr { end } else { end_addr }),
+            None => (beg_addr, end_addr),
+        });
+    }
+
+    /// Seeds the random number generator used by Cxkk.
+    ///
+    /// The same seed always produces the same sequence of random numbers, whatever the execution method.
+    pub const fn set_seed(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
     }
 
     /// Returns the memory in its initial state with the given program loaded.
     ///
-    /// `program` must not be greater than State::MAX_PROGRAM_LEN bytes.
-    fn new_memory(program: &[u8]) -> [u8; State::MEMORY_SIZE] {
-        assert!(program.len() <= Self::MAX_PROGRAM_LEN, "Input program ({} bytes) exceeds memory size ({} bytes)", program.len(), Self::MAX_PROGRAM_LEN);
+    /// `program` must fit in memory after `load_address`.
+    fn new_memory(program: &[u8], memory_size: usize, load_address: usize) -> Result<[u8; State::MEMORY_SIZE], LoadError> {
+        let max_len = memory_size - load_address;
+        if program.len() > max_len {
+            return Err(LoadError::ProgramTooLarge { len: program.len(), max_len });
+        }
 
         let mut memory = [0; State::MEMORY_SIZE];
 
-        // Load font.
-        memory[0..80].copy_from_slice(&[
-            0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
-            0x20, 0x60, 0x20, 0x20, 0x70, // 1
-            0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
-            0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
-            0x90, 0x90, 0xF0, 0x10, 0x10, // 4
-            0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
-            0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
-            0xF0, 0x10, 0x20, 0x40, 0x40, // 7
-            0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
-            0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
-            0xF0, 0x90, 0xF0, 0x90, 0x90, // A
-            0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
-            0xF0, 0x80, 0x80, 0x80, 0xF0, // C
-            0xE0, 0x90, 0x90, 0x90, 0xE0, // D
-            0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
-            0xF0, 0x80, 0xF0, 0x80, 0x80, // F
-        ]);
-
         // Load program.
-        let end = Self::INITIAL_PC + program.len();
-        memory[Self::INITIAL_PC..end].copy_from_slice(program);
+        let end = load_address + program.len();
+        memory[load_address..end].copy_from_slice(program);
 
-        memory
+        Ok(memory)
     }
 
-    const fn clear_screen(&mut self) {
-        self.screen = DEFAULT_SCREEN;
+    fn clear_screen(&mut self) {
+        self.screen.clear(self.planes);
+        self.screen_dirty = true;
     }
 
+    /// Draws a sprite of `n` lines at (VX, VY). When `n` is 0, draws a 16x16 SUPER-CHIP sprite, or an 8x16 sprite in
+    /// lores mode without the [lores_big_sprites](Quirks::lores_big_sprites) quirk.
+    ///
+    /// Each selected XO-CHIP bitplane is drawn in order, the sprite data of each plane following the previous one.
     fn draw(&mut self, x: usize, y: usize, n: u8) {
         self.V[0xF] = 0;
-        let x = self.V[x] as usize % State::SCREEN_WIDTH;
-        let y = self.V[y] as usize % State::SCREEN_HEIGHT;
-
-        for mut j in 0..n as usize {
-            let line = self.memory[self.I as usize + j];
-            j += y;
-
-            for mut i in 0..8 {
-                let mask = 0x80 >> i;
-                i += x;
-                if line & mask != 0 && i < State::SCREEN_WIDTH && j < State::SCREEN_HEIGHT {
-                    if self.screen[j][i] {
-                        self.screen[j][i] = false;
-                        self.V[0xF] = 1;
-                    } else {
-                        self.screen[j][i] = true;
+        self.screen_dirty = true;
+        let width = self.screen.width();
+        let height = self.screen.height();
+        let x = self.V[x] as usize % width;
+        let y = self.V[y] as usize % height;
+
+        let (lines, sprite_width) = match n {
+            0 if self.screen.is_hires() || self.quirks.lores_big_sprites => (16, 16),
+            0 => (16, 8),
+            n => (n as usize, 8),
+        };
+        let bytes_per_line = sprite_width / 8;
+        let mut addr = self.I as usize;
+
+        for plane in [1, 2] {
+            if self.planes & plane == 0 {
+                continue;
+            }
+
+            for mut j in 0..lines {
+                let line = if bytes_per_line == 2 {
+                    (self.memory[addr] as u16) << 8 | self.memory[addr + 1] as u16
+                } else {
+                    (self.memory[addr] as u16) << 8
+                };
+                addr += bytes_per_line;
+                j += y;
+
+                if self.quirks.wrap_sprites {
+                    j %= height;
+                }
+
+                for mut i in 0..sprite_width {
+                    let mask = 0x8000 >> i;
+                    i += x;
+                    if self.quirks.wrap_sprites {
+                        i %= width;
+                    }
+                    if line & mask != 0 && i < width && j < height {
+                        let pixel = &mut self.screen.pixels[j][i];
+                        if *pixel & plane != 0 {
+                            self.V[0xF] = 1;
+                        }
+                        *pixel ^= plane;
                     }
                 }
             }
         }
     }
 
+    /// Skips the next instruction, which is 4 bytes long if it is the XO-CHIP long I load F000 NNNN.
+    fn skip_next_instruction(&mut self) {
+        let pc = self.PC as usize;
+        let long = pc + 1 < self.memory_size && self.memory[pc] == 0xF0 && self.memory[pc + 1] == 0x00;
+        self.PC = self.PC.wrapping_add(if long { 4 } else { 2 });
+    }
+
+    /// Stops the execution of the current instruction with the given trap, moving PC back to the faulting instruction.
+    ///
+    /// Returns the value the execution methods have to return.
+    fn trap(&mut self, trap: Trap) -> u32 {
+        self.PC = trap.pc();
+        self.trap = Some(trap);
+        1
+    }
+
+    /// Returns true if the `len` bytes at `address` are in memory.
+    /// Otherwise traps with [Trap::MemoryOutOfRange] and returns false.
+    ///
+    /// Must be called by the execution methods, as the faulting instruction is at PC - 2.
+    fn check_range(&mut self, address: u16, len: usize) -> bool {
+        if address as usize + len <= self.memory_size {
+            true
+        } else {
+            self.trap(Trap::MemoryOutOfRange { pc: self.PC.wrapping_sub(2), address, len: len as u16 });
+            false
+        }
+    }
+
+    /// Returns true if the code at the given address can be cached or compiled.
+    ///
+    /// No code is cached below the initial PC, nor in the last 4 bytes of memory so the address of the end of blocks
+    /// never overflows. Code outside of this range is always interpreted.
+    const fn is_cacheable(&self, addr: u16) -> bool {
+        addr as usize >= Self::INITIAL_PC && addr as usize + 4 < self.memory_size
+    }
+
     /// Sets a key as pressed or unpressed.
     ///
     /// `key` is the key number to set (0 to 9 for keys 0 to 9, and 10 to 15 for keys A to F).
@@ -170,71 +516,224 @@ impl State {
     }
 }
 
+/// Behaviours that differ between the Chip8 implementations.
+///
+/// The default value matches the behaviour risp8 always had.
+/// See <https://games.gulrak.net/cadmium/chip8-opcode-table.html> for the details of each quirk.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Quirks {
+    /// 8xy1, 8xy2 and 8xy3 reset VF to 0.
+    pub vf_reset: bool,
+    /// 8xy6 and 8xyE shift VX in place and ignore VY. When false, VY is shifted and stored in VX.
+    pub shift_vx: bool,
+    /// Fx55 and Fx65 increment I by X + 1.
+    pub increment_i: bool,
+    /// Dxyn wraps the sprites around the edges of the screen instead of clipping them.
+    pub wrap_sprites: bool,
+    /// Bnnn jumps to nnn + VX (with X the highest nibble of nnn) instead of nnn + V0.
+    pub jump_vx: bool,
+    /// Dxy0 draws a 16x16 sprite in lores mode, as XO-CHIP. When false, it draws an 8x16 sprite as SUPER-CHIP 1.1.
+    /// Dxy0 always draws a 16x16 sprite in hires mode.
+    pub lores_big_sprites: bool,
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Self {
+            vf_reset: true,
+            shift_vx: true,
+            increment_i: true,
+            wrap_sprites: false,
+            jump_vx: false,
+            lores_big_sprites: true,
+        }
+    }
+}
+
 /// Chip8 core.
+///
+/// It can be driven either with the channel-based [run](Chip8::run) method in its own thread, or synchronously with
+/// [run_frame](Chip8::run_frame) and [run_instructions](Chip8::run_instructions) when the host owns the main loop.
 pub struct Chip8 {
-    state: State,
+    /// Boxed so its address does not change when the core is moved, as the JIT accesses it directly.
+    state: Box<State>,
+    /// Kept to reset the state when the platform changes.
+    program: Vec<u8>,
+    /// The platform the core is configured for, if any.
+    platform: Option<Platform>,
+    /// Number of instructions executed between two 60Hz timer ticks.
+    instructions_per_frame: u32,
 
-    timer: Instant,
+    /// When true, [run](Chip8::run) waits for the real-time duration of each frame.
+    pacing: bool,
+    /// Returns the current time in microseconds.
+    time_source: Option<fn() -> u64>,
+    /// The time in microseconds at which the next frame is due when pacing.
+    next_frame: u64,
+    /// Number of 60Hz timer ticks since the start.
+    frames: u64,
+    /// True if the sound timer was active during the last timer tick.
+    sound_playing: bool,
+    /// Snapshots taken by [run](Chip8::run) to rewind execution.
+    #[cfg(feature = "std")]
+    rewind: Rewind,
+    /// The movie being recorded or played.
+    movie: Option<ActiveMovie>,
+    /// When [Risp8Answer::Frame] is sent.
+    #[cfg(feature = "std")]
+    frame_delivery: FrameDelivery,
 
-    channel_in: Receiver<Risp8Command>,
-    channel_out: Sender<Risp8Answer>,
+    /// The channels of the cores created with [Chip8Builder::build], None for [Chip8Builder::build_sync].
+    #[cfg(feature = "std")]
+    channel_in: Option<Receiver<Risp8Command>>,
+    #[cfg(feature = "std")]
+    channel_out: Option<Sender<Risp8Answer>>,
+    #[cfg(feature = "std")]
     play: bool,
-    execution_method: ExecutionMethod,
-
-    interpreter_caches: Box<[Option<InstructionCache>]>,
-    interpreter_caches_2: Box<[Option<[Option<InstructionCache>; cached_interpreter_2::SUBCACHE_SIZE]>]>,
-    interpreter_caches_3: Box<[Option<CachedInstruction>]>,
-
-    #[cfg(target_arch = "x86_64")]
-    jit_caches: Caches,
+    /// The execution method of the backend, None for a custom backend.
+    execution_method: Option<ExecutionMethod>,
+    backend: Box<dyn ExecutionBackend + Send>,
+    /// Number of instructions executed by the current backend.
+    instructions: u64,
+    /// Execution stops before executing the instructions at these addresses.
+    breakpoints: BTreeMap<u16, Breakpoint>,
+    /// The breakpoint execution stopped on, which does not stop execution again when it is resumed.
+    breakpoint_hit: Option<u16>,
+    watchpoints: Vec<Watchpoint>,
+    /// Smallest address ranges containing every read and write watchpoint.
+    watch_ranges: ((u16, u16), (u16, u16)),
+    /// The watched accesses of the last instruction that hit a watchpoint.
+    watchpoint_hits: Vec<WatchpointHit>,
+    /// The messages logged by tracepoints and not taken yet.
+    log_messages: Vec<String>,
 }
 
 impl Chip8 {
-    const INTERPRETER_CACHES_LEN: usize = State::MAX_PROGRAM_LEN;
-    const INTERPRETER_CACHES_LEN_2: usize = cached_interpreter_2::addr_to_index(State::MEMORY_SIZE as u16);
+    /// The number of instructions executed per frame when no platform is selected.
+    pub const DEFAULT_INSTRUCTIONS_PER_FRAME: u32 = 30;
 
-    const EMPTY_INTERPRETER_CACHES: Option<InstructionCache> = None;
-    const EMPTY_INTERPRETER_CACHES_2: Option<[Option<InstructionCache>; cached_interpreter_2::SUBCACHE_SIZE]> = None;
-    const EMPTY_INTERPRETER_CACHES_3: Option<CachedInstruction> = None;
+    /// The default number of rewind snapshots, 10 seconds of snapshots taken every frame.
+    pub const DEFAULT_REWIND_CAPACITY: usize = 600;
+
+    /// The real-time duration of a 60Hz frame in microseconds.
+    const FRAME_DURATION: u64 = 16_667;
 
     /// Creates a new Chip8 context.
     ///
+    /// `rom` is the path to the ROM to open. Use [Chip8Builder] to create a context from a ROM in memory.
+    #[cfg(feature = "std")]
+    pub fn new(rom: &str) -> Result<(Self, Sender<Risp8Command>, Receiver<Risp8Answer>), LoadError> {
+        Chip8Builder::new(read(rom)?).build()
+    }
+
+    /// Creates a new Chip8 context configured for the given platform.
+    ///
     /// `rom` is the path to the ROM to open.
-    pub fn new(rom: &str) -> Result<(Self, Sender<Risp8Command>, Receiver<Risp8Answer>), Error> {
-        let (channel_out, user_in) = unbounded();
-        let (user_out, channel_in) = unbounded();
+    #[cfg(feature = "std")]
+    pub fn with_platform(rom: &str, platform: Platform) -> Result<(Self, Sender<Risp8Command>, Receiver<Risp8Answer>), LoadError> {
+        Chip8Builder::new(read(rom)?).platform(platform).build()
+    }
 
-        let core = Self {
-            state: Self::new_state(rom)?,
+    /// Resets the state with the configuration of the given platform, restarting the program.
+    ///
+    /// The state is left unchanged if the program does not fit in the memory of the platform.
+    pub fn set_platform(&mut self, platform: Platform) -> Result<(), LoadError> {
+        *self.state = State::with_platform(&self.program, platform)?;
+        self.platform = Some(platform);
+        self.instructions_per_frame = platform.instructions_per_frame();
+        self.reset_caches();
+        #[cfg(feature = "std")]
+        self.rewind.clear();
+        self.movie = None;
 
-            timer: Instant::now(),
+        Ok(())
+    }
 
-            channel_in,
-            channel_out,
-            play: false,
-            execution_method: ExecutionMethod::Interpreter,
+    /// Restarts the program from power-on with the given configuration, as movies start.
+    fn power_on(&mut self, platform: Option<Platform>, quirks: Quirks, instructions_per_frame: u32, seed: u64) -> Result<(), LoadError> {
+        *self.state = match platform {
+            Some(platform) => State::with_platform(&self.program, platform)?,
+            None => State::new(&self.program)?,
+        };
+        self.state.set_quirks(quirks);
+        self.state.set_seed(seed);
+        self.platform = platform;
+        self.instructions_per_frame = instructions_per_frame;
+        self.reset_caches();
+        #[cfg(feature = "std")]
+        self.rewind.clear();
 
-            interpreter_caches: vec![Self::EMPTY_INTERPRETER_CACHES; Self::INTERPRETER_CACHES_LEN].into_boxed_slice(),
-            interpreter_caches_2: vec![Self::EMPTY_INTERPRETER_CACHES_2; Self::INTERPRETER_CACHES_LEN_2].into_boxed_slice(),
-            interpreter_caches_3: vec![Self::EMPTY_INTERPRETER_CACHES_3; Self::INTERPRETER_CACHES_LEN].into_boxed_slice(),
+        Ok(())
+    }
 
-            #[cfg(target_arch = "x86_64")]
-            jit_caches: Caches::new(),
+    /// Restarts the program from power-on and starts recording a movie of the inputs.
+    ///
+    /// The movie uses the current platform, quirks and instructions per frame, and the given seed.
+    pub fn record_movie(&mut self, seed: u64) -> Result<(), MovieError> {
+        let movie = Movie {
+            rom_hash: Movie::rom_hash(&self.program),
+            platform: self.platform,
+            quirks: self.state.quirks,
+            instructions_per_frame: self.instructions_per_frame,
+            seed,
+            rerecords: 0,
+            inputs: Vec::new(),
+            checksums: Vec::new(),
         };
+        self.power_on(movie.platform, movie.quirks, movie.instructions_per_frame, seed)
+            .map_err(|_| MovieError::ProgramTooLarge)?;
+        self.movie = Some(ActiveMovie::new(movie, MovieMode::Recording));
+
+        Ok(())
+    }
+
+    /// Restarts the program from power-on with the settings of the given movie and replays its inputs.
+    ///
+    /// In read-only mode the inputs of the user are ignored. Otherwise an input of the user truncates the movie and
+    /// starts recording from there.
+    pub fn play_movie(&mut self, movie: Movie, read_only: bool) -> Result<(), MovieError> {
+        let rom_hash = Movie::rom_hash(&self.program);
+        if movie.rom_hash != rom_hash {
+            return Err(MovieError::RomMismatch { expected: movie.rom_hash, actual: rom_hash });
+        }
+
+        self.power_on(movie.platform, movie.quirks, movie.instructions_per_frame, movie.seed)
+            .map_err(|_| MovieError::ProgramTooLarge)?;
+        self.movie = Some(ActiveMovie::new(movie, MovieMode::Playing { read_only }));
+
+        Ok(())
+    }
+
+    /// Stops recording or playing the current movie and returns it.
+    pub fn stop_movie(&mut self) -> Option<Movie> {
+        self.movie.take().map(|active| active.movie)
+    }
+
+    /// Returns the movie being recorded or played.
+    pub fn movie(&self) -> Option<&Movie> {
+        self.movie.as_ref().map(|active| &active.movie)
+    }
 
-        Ok((core, user_out, user_in))
+    /// Returns the way the current movie is used.
+    pub fn movie_mode(&self) -> Option<MovieMode> {
+        self.movie.as_ref().map(|active| active.mode)
     }
 
-    fn new_state(filename: &str) -> Result<State, Error> {
-        let program = read(filename)?;
+    /// Deletes every cache of the backend.
+    fn reset_caches(&mut self) {
+        self.backend.reset();
+    }
 
-        Ok(State::new(&program))
+    /// Invalidates the caches of the backend in the given address range.
+    fn invalidate_caches(&mut self, beg_addr: u16, end_addr: u16) {
+        self.backend.invalidate(beg_addr, end_addr);
     }
 
     /// Starts emulation in an infinite loop.
     ///
     /// This method is meant to run concurrently with the rest of the program (GUI, ...).
     /// Use the channels to send commands to control the core and receive answers from it.
+    #[cfg(feature = "std")]
     pub fn run(&mut self) {
         loop {
             if self.handle_channels() {
@@ -242,66 +741,560 @@ impl Chip8 {
             }
 
             if self.play {
+                let frames = self.frames;
                 self.single_step();
+
+                if self.frames != frames {
+                    self.rewind.end_frame(&self.state);
+
+                    if self.pacing {
+                        self.wait_next_frame();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Sleeps until the next frame is due.
+    #[cfg(feature = "std")]
+    fn wait_next_frame(&mut self) {
+        sleep(Duration::from_micros(self.next_frame_delay()));
+    }
+
+    /// Schedules the next frame and returns the number of microseconds to wait before executing it, according to the
+    /// time source.
+    ///
+    /// Hosts driving the core with [run_frame](Self::run_frame) can call it after each frame to pace the emulation in
+    /// real time. When the core is late it does not try to catch up, the next frame is scheduled from now.
+    /// Returns 0 if pacing is disabled or if there is no time source.
+    pub fn next_frame_delay(&mut self) -> u64 {
+        let Some(now) = self.time_source.filter(|_| self.pacing).map(|now| now()) else {
+            return 0;
+        };
+
+        self.next_frame += Self::FRAME_DURATION;
+        if self.next_frame > now {
+            self.next_frame - now
+        } else {
+            self.next_frame = now;
+            0
+        }
+    }
+
+    /// Loads the given font set in memory. Fx29 and Fx30 then point into it.
+    ///
+    /// Ignored while a movie is recorded or played, as movies do not store font changes.
+    pub fn set_font(&mut self, font: &Font) {
+        if self.movie.is_none() {
+            self.state.set_font(font);
+        }
+    }
+
+    /// Seeds the random number generator used by Cxkk.
+    ///
+    /// Ignored while a movie is recorded or played, as movies replay with the seed of their header.
+    pub fn set_seed(&mut self, seed: u64) {
+        if self.movie.is_none() {
+            self.state.set_seed(seed);
+        }
+    }
+
+    /// Sets the quirks used by every execution method.
+    ///
+    /// Ignored while a movie is recorded or played, as movies replay with the quirks of their header.
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        if self.movie.is_none() {
+            self.state.set_quirks(quirks);
+        }
+    }
+
+    /// Executes instructions until the next 60Hz timer tick, which is
+    /// [instructions_per_frame](Self::instructions_per_frame) instructions when called at the start of a frame.
+    ///
+    /// Returns the reason why execution stopped before the end of the frame, if any.
+    pub fn run_frame(&mut self) -> Option<StopReason> {
+        let remaining = self.instructions_per_frame.saturating_sub(self.state.timer_phase).max(1);
+        self.run_instructions(remaining as u64)
+    }
+
+    /// Executes `count` instructions with the current execution method.
+    ///
+    /// Returns the reason why execution stopped before `count` instructions have been executed, if any.
+    pub fn run_instructions(&mut self, count: u64) -> Option<StopReason> {
+        let end = self.state.cycles.saturating_add(count);
+        while self.state.cycles < end {
+            if let Some(reason) = self.step(end - self.state.cycles) {
+                return Some(reason);
+            }
+        }
+
+        None
+    }
+
+    /// Sets a key as pressed or unpressed.
+    ///
+    /// `key` is the key number to set (0 to 9 for keys 0 to 9, and 10 to 15 for keys A to F).
+    /// `pressed` = true if pressed, false if released.
+    ///
+    /// When a movie is recorded the input is added to it, and when a read-only movie is played it is ignored.
+    pub fn set_key(&mut self, key: usize, pressed: bool) {
+        if let Some(movie) = &mut self.movie {
+            if !movie.user_input(&self.state, key, pressed) {
+                return;
             }
         }
+
+        self.state.set_key(key, pressed);
     }
 
+    /// Returns the screen.
+    pub fn screen(&self) -> &Screen {
+        &self.state.screen
+    }
+
+    /// Returns the state of the virtual machine.
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
+    /// Returns the current state serialized in the save state format. See [State::save].
+    pub fn save_state(&self) -> Vec<u8> {
+        self.state.save()
+    }
+
+    /// Restores a state saved with [save_state](Self::save_state), with any execution method.
+    ///
+    /// Every cache is deleted so no code compiled for the previous memory is executed.
+    /// The current state is left unchanged if the save state is invalid.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), SaveStateError> {
+        *self.state = State::load(data)?;
+        self.reset_caches();
+
+        if let Some(movie) = &mut self.movie {
+            movie.seek(&self.state);
+        }
+
+        Ok(())
+    }
+
+    /// Restores the state as it was `frames` frames ago, using the snapshots taken by [run](Self::run).
+    ///
+    /// The snapshots more recent than the restored one are deleted. If there are not enough snapshots, the oldest one is
+    /// restored. Returns false if there is no snapshot to restore.
+    #[cfg(feature = "std")]
+    pub fn rewind(&mut self, frames: u32) -> bool {
+        match self.rewind.rewind(&self.state, frames) {
+            Some(snapshot) => self.load_state(&snapshot).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Returns the state of the virtual machine to modify it.
+    ///
+    /// Every cache is deleted, as with [load_state](Self::load_state), so the state can be modified in any way, or
+    /// replaced entirely.
+    ///
+    /// Returns None while a movie is recorded or played, as movies only store key inputs. Modified states can still be
+    /// loaded with [load_state](Self::load_state), which moves the movie to them.
+    pub fn state_mut(&mut self) -> Option<&mut State> {
+        if self.movie.is_some() {
+            return None;
+        }
+
+        self.reset_caches();
+        Some(&mut self.state)
+    }
+
+    /// Returns true if the sound timer was active during the last 60Hz timer tick.
+    pub fn is_sound_playing(&self) -> bool {
+        self.sound_playing
+    }
+
+    /// Returns the execution method used to execute instructions, or None if a custom backend is used.
+    pub fn execution_method(&self) -> Option<ExecutionMethod> {
+        self.execution_method
+    }
+
+    /// Sets the execution method used to execute instructions, replacing the current backend.
+    pub fn set_execution_method(&mut self, execution_method: ExecutionMethod) {
+        self.backend = execution_method.backend();
+        self.execution_method = Some(execution_method);
+        self.instructions = 0;
+        self.restore_breakpoints();
+    }
+
+    /// Replaces the backend used to execute instructions by a custom one.
+    pub fn set_backend(&mut self, backend: Box<dyn ExecutionBackend + Send>) {
+        self.backend = backend;
+        self.execution_method = None;
+        self.instructions = 0;
+        self.restore_breakpoints();
+    }
+
+    /// Adds the breakpoints to a new backend.
+    fn restore_breakpoints(&mut self) {
+        for &pc in self.breakpoints.keys() {
+            self.backend.add_breakpoint(pc);
+        }
+    }
+
+    /// Adds a breakpoint at `pc` that triggers every time it is reached. Returns false if a breakpoint is already set.
+    ///
+    /// Execution stops with [StopReason::Breakpoint] before executing the instruction at a breakpoint, whatever the
+    /// execution method. When execution is resumed, the instruction is executed.
+    pub fn add_breakpoint(&mut self, pc: u16) -> bool {
+        if self.breakpoints.contains_key(&pc) {
+            return false;
+        }
+
+        self.set_breakpoint(pc, Breakpoint::new());
+        true
+    }
+
+    /// Sets the breakpoint at `pc`, replacing the one already set there if any.
+    ///
+    /// Its condition is evaluated when execution reaches `pc`. Tracepoints add their message to the log, see
+    /// [Chip8::take_log_messages].
+    pub fn set_breakpoint(&mut self, pc: u16, breakpoint: Breakpoint) {
+        if self.breakpoints.insert(pc, breakpoint).is_none() {
+            self.backend.add_breakpoint(pc);
+        }
+    }
+
+    /// Removes the breakpoint at `pc`. Returns false if it is not set.
+    pub fn remove_breakpoint(&mut self, pc: u16) -> bool {
+        let removed = self.breakpoints.remove(&pc).is_some();
+        if removed {
+            self.backend.remove_breakpoint(pc);
+        }
+        removed
+    }
+
+    /// Returns the breakpoint at `pc`, if any.
+    pub fn breakpoint(&self, pc: u16) -> Option<&Breakpoint> {
+        self.breakpoints.get(&pc)
+    }
+
+    /// Returns the breakpoints by address.
+    pub fn breakpoints(&self) -> &BTreeMap<u16, Breakpoint> {
+        &self.breakpoints
+    }
+
+    /// Returns the messages logged by tracepoints since the last call, oldest first.
+    ///
+    /// When using [Chip8::run], they are sent in [Risp8Answer::Log] instead.
+    pub fn take_log_messages(&mut self) -> Vec<String> {
+        core::mem::take(&mut self.log_messages)
+    }
+
+    /// Adds a memory watchpoint. Returns false if the same watchpoint is already set.
+    ///
+    /// Execution stops with [StopReason::Watchpoint] after executing an instruction accessing a watched address, with
+    /// every execution method.
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) -> bool {
+        if self.watchpoints.contains(&watchpoint) {
+            return false;
+        }
+        self.watchpoints.push(watchpoint);
+        self.watch_ranges = watchpoint::watch_ranges(&self.watchpoints);
+        true
+    }
+
+    /// Removes a memory watchpoint. Returns false if it is not set.
+    pub fn remove_watchpoint(&mut self, watchpoint: Watchpoint) -> bool {
+        let len = self.watchpoints.len();
+        self.watchpoints.retain(|&w| w != watchpoint);
+        self.watch_ranges = watchpoint::watch_ranges(&self.watchpoints);
+        self.watchpoints.len() != len
+    }
+
+    /// Returns the memory watchpoints.
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    /// Returns every watched access of the last instruction that stopped execution with [StopReason::Watchpoint].
+    pub fn watchpoint_hits(&self) -> &[WatchpointHit] {
+        &self.watchpoint_hits
+    }
+
+    /// Returns the performance counters of the current backend, which are reset when the backend is replaced.
+    pub fn stats(&self) -> Stats {
+        Stats {
+            instructions: self.instructions,
+            ..self.backend.stats()
+        }
+    }
+
+    /// Returns the number of instructions executed between two 60Hz timer ticks.
+    pub fn instructions_per_frame(&self) -> u32 {
+        self.instructions_per_frame
+    }
+
+    /// Sets the number of instructions executed between two 60Hz timer ticks. 0 is treated as 1.
+    ///
+    /// Timers only advance with the number of executed instructions, so a program behaves the same whatever the
+    /// speed of the host and the execution method.
+    ///
+    /// Ignored while a movie is recorded or played, as movies replay with the instructions per frame of their header.
+    pub fn set_instructions_per_frame(&mut self, instructions_per_frame: u32) {
+        if self.movie.is_none() {
+            self.instructions_per_frame = instructions_per_frame.max(1);
+        }
+    }
+
+    /// Returns when [Risp8Answer::Frame] is sent by [run](Self::run).
+    #[cfg(feature = "std")]
+    pub fn frame_delivery(&self) -> FrameDelivery {
+        self.frame_delivery
+    }
+
+    /// Sets when [Risp8Answer::Frame] is sent by [run](Self::run).
+    #[cfg(feature = "std")]
+    pub fn set_frame_delivery(&mut self, frame_delivery: FrameDelivery) {
+        self.frame_delivery = frame_delivery;
+    }
+
+    /// Returns true if the screen has been modified since the last [Risp8Answer::Frame] has been sent.
+    pub fn is_screen_dirty(&self) -> bool {
+        self.state.screen_dirty
+    }
+
+    /// Returns true if [run](Self::run) executes frames at 60Hz in real time.
+    pub fn pacing(&self) -> bool {
+        self.pacing
+    }
+
+    /// When true, [run](Self::run) executes frames at 60Hz in real time. When false, it executes them as fast as
+    /// possible.
+    pub fn set_pacing(&mut self, pacing: bool) {
+        self.pacing = pacing;
+        self.next_frame = self.time_source.map_or(0, |now| now());
+    }
+
+    /// Executes the execution method once, executing at most `budget` instructions (at least 1).
+    ///
+    /// The budget is reduced so execution stops on the next timer tick, which then happens at the same instruction
+    /// for every execution method.
+    ///
+    /// Returns the reason why execution has to stop, if any.
+    fn step(&mut self, budget: u64) -> Option<StopReason> {
+        let pc = self.state.PC;
+        if self.breakpoint_hit.take() != Some(pc) {
+            if let Some(breakpoint) = self.breakpoints.get_mut(&pc) {
+                if breakpoint.hit(&self.state) {
+                    match &breakpoint.log_message {
+                        Some(message) => self.log_messages.push(message.format(&self.state)),
+                        None => {
+                            self.breakpoint_hit = Some(pc);
+                            return Some(StopReason::Breakpoint(pc));
+                        },
+                    }
+                }
+            }
+        }
+
+        if let Some((beg_addr, end_addr)) = self.state.invalidation.take() {
+            self.invalidate_caches(beg_addr, end_addr);
+        }
+
+        let remaining = self.instructions_per_frame.saturating_sub(self.state.timer_phase).max(1);
+        let mut budget = budget.min(remaining as u64);
+        if let Some(movie) = &mut self.movie {
+            // Stop on the next input of the movie so it is applied on the same instruction as when recorded.
+            if let Some(next_input) = movie.replay_inputs(&mut self.state) {
+                budget = budget.min(next_input);
+            }
+        }
+        let cycles = self.state.cycles;
+        // The state may have been replaced since the last step.
+        (self.state.watch_reads, self.state.watch_writes) = self.watch_ranges;
+
+        self.backend.step(&mut self.state, budget);
+        let watched_access = self.state.watched_access.take();
+
+        let executed = self.state.cycles - cycles;
+        self.instructions += executed;
+        self.state.timer_phase += executed as u32;
+        if self.state.timer_phase >= self.instructions_per_frame {
+            self.state.timer_phase = 0;
+            self.tick_timers();
+
+            if let Some(movie) = &mut self.movie {
+                if !movie.tick(&self.state) {
+                    return Some(StopReason::MovieDesync { cycle: self.state.cycles });
+                }
+            }
+        }
+
+        if let Some(access) = watched_access {
+            let hits: Vec<WatchpointHit> = watchpoint::watchpoint_hits(access, &self.watchpoints, &self.state.memory).collect();
+            if let Some(&hit) = hits.first() {
+                self.watchpoint_hits = hits;
+                return Some(StopReason::Watchpoint(hit));
+            }
+        }
+
+        if self.state.exited {
+            self.state.exited = false;
+            return Some(StopReason::Exited);
+        }
+
+        self.state.trap.take().map(StopReason::Trap)
+    }
+
+    /// Executes the execution method once and sends the answers through the channel.
+    #[cfg(feature = "std")]
     fn single_step(&mut self) {
-        match self.execution_method {
-            ExecutionMethod::Interpreter => self.interpreter(),
-            ExecutionMethod::CachedInterpreter => self.cached_interpreter(),
-            ExecutionMethod::CachedInterpreter2 => self.cached_interpreter_2(),
-            ExecutionMethod::CachedInterpreter3 => self.cached_interpreter_3(),
-            ExecutionMethod::Jit => self.jit(),
+        let frames = self.frames;
+        let movie_playing = matches!(self.movie_mode(), Some(MovieMode::Playing { .. }));
+        let reason = self.step(u64::MAX);
+
+        for message in self.take_log_messages() {
+            self.send(Risp8Answer::Log(message));
+        }
+
+        if movie_playing && !matches!(self.movie_mode(), Some(MovieMode::Playing { .. })) {
+            self.send(Risp8Answer::MovieEnded);
+        }
+
+        if self.frames != frames {
+            if self.sound_playing {
+                self.send(Risp8Answer::PlaySound);
+            } else {
+                self.send(Risp8Answer::StopSound);
+            }
+
+            let send_frame = match self.frame_delivery {
+                FrameDelivery::Never => false,
+                FrameDelivery::EveryFrame => true,
+                FrameDelivery::WhenDirty => self.state.screen_dirty,
+            };
+            if send_frame {
+                self.state.screen_dirty = false;
+                self.send(Risp8Answer::Frame(self.state.screen));
+            }
+        }
+
+        match reason {
+            Some(StopReason::Exited) => {
+                self.play = false;
+                self.send(Risp8Answer::Exited);
+            },
+            Some(StopReason::Trap(trap)) => {
+                self.play = false;
+                self.send(Risp8Answer::Trap(trap));
+            },
+            Some(StopReason::MovieDesync { cycle }) => {
+                self.play = false;
+                self.send(Risp8Answer::MovieDesync { cycle });
+            },
+            Some(StopReason::Breakpoint(pc)) => {
+                self.play = false;
+                self.send(Risp8Answer::BreakpointHit(pc));
+            },
+            Some(StopReason::Watchpoint(_)) => {
+                self.play = false;
+                for &hit in &self.watchpoint_hits {
+                    self.send(Risp8Answer::WatchpointHit(hit));
+                }
+            },
+            None => (),
         }
     }
 
     /// Returns true if the emulator has to be stopped (when the channel is closed or error).
+    #[cfg(feature = "std")]
     fn handle_channels(&mut self) -> bool {
-        while !self.channel_in.is_empty() {
-            let Ok(cmd) = self.channel_in.recv() else {
+        loop {
+            let cmd = match &self.channel_in {
+                Some(channel_in) if !channel_in.is_empty() => channel_in.recv(),
+                _ => return false,
+            };
+            let Ok(cmd) = cmd else {
                 return true;
             };
 
             match cmd {
-                Risp8Command::SetKey(key, pressed) => self.state.set_key(key, pressed),
-                Risp8Command::GetScreen => { let _ = self.channel_out.send(Risp8Answer::Screen(self.state.screen)); },
+                Risp8Command::SetKey(key, pressed) => self.set_key(key, pressed),
+                Risp8Command::GetScreen => { self.send(Risp8Answer::Screen(self.state.screen)); },
+                Risp8Command::GetState => { self.send(Risp8Answer::State(self.state.clone())); },
+                Risp8Command::GetStats => { self.send(Risp8Answer::Stats(self.stats())); },
+                Risp8Command::WriteMemory(address, data) => {
+                    if self.movie.is_none() {
+                        let _ = self.state.write_memory(address, &data);
+                    }
+                },
+                Risp8Command::SaveState => { self.send(Risp8Answer::SaveState(self.save_state())); },
+                Risp8Command::Rewind(frames) => { self.rewind(frames); },
+                Risp8Command::RecordMovie(seed) => {
+                    let result = self.record_movie(seed);
+                    self.send(Risp8Answer::MovieStarted(result));
+                },
+                Risp8Command::PlayMovie(movie, read_only) => {
+                    let result = self.play_movie(movie, read_only);
+                    self.send(Risp8Answer::MovieStarted(result));
+                },
+                Risp8Command::StopMovie => {
+                    let movie = self.stop_movie();
+                    self.send(Risp8Answer::Movie(movie));
+                },
+                Risp8Command::LoadState(data) => {
+                    let result = self.load_state(&data);
+                    self.send(Risp8Answer::StateLoaded(result));
+                },
                 Risp8Command::Play => self.play = true,
                 Risp8Command::Pause => self.play = false,
                 Risp8Command::SingleStep => self.single_step(),
-                Risp8Command::SetExecutionMethod(method) => self.execution_method = method,
+                Risp8Command::SetExecutionMethod(method) => self.set_execution_method(method),
+                Risp8Command::SetQuirks(quirks) => self.set_quirks(quirks),
+                Risp8Command::SetPlatform(platform) => { let _ = self.set_platform(platform); },
+                Risp8Command::SetFont(font) => self.set_font(&font),
+                Risp8Command::SetInstructionsPerFrame(instructions_per_frame) => self.set_instructions_per_frame(instructions_per_frame),
+                Risp8Command::SetPacing(pacing) => self.set_pacing(pacing),
+                Risp8Command::SetFrameDelivery(frame_delivery) => self.frame_delivery = frame_delivery,
+                Risp8Command::SetSeed(seed) => self.set_seed(seed),
+                Risp8Command::AddBreakpoint(pc) => { self.add_breakpoint(pc); },
+                Risp8Command::SetBreakpoint(pc, breakpoint) => self.set_breakpoint(pc, breakpoint),
+                Risp8Command::RemoveBreakpoint(pc) => { self.remove_breakpoint(pc); },
+                Risp8Command::AddWatchpoint(watchpoint) => { self.add_watchpoint(watchpoint); },
+                Risp8Command::RemoveWatchpoint(watchpoint) => { self.remove_watchpoint(watchpoint); },
                 Risp8Command::Exit => return true,
             }
         }
-
-        false
     }
 
-    fn handle_timers(&mut self) {
-        if self.timer.elapsed() >= Duration::from_micros(16666) {
-            if self.state.delay > 0 {
-                self.state.delay -= 1;
-            }
+    /// Sends an answer through the channel, if the core has one.
+    #[cfg(feature = "std")]
+    fn send(&self, answer: Risp8Answer) {
+        if let Some(channel_out) = &self.channel_out {
+            let _ = channel_out.send(answer);
+        }
+    }
 
-            if self.state.sound > 0 {
-                self.state.sound -= 1;
-                let _ = self.channel_out.send(Risp8Answer::PlaySound);
-            } else {
-                let _ = self.channel_out.send(Risp8Answer::StopSound);
-            }
+    /// Decrements the timers, called every [instructions_per_frame](Self::instructions_per_frame) instructions.
+    fn tick_timers(&mut self) {
+        if self.state.delay > 0 {
+            self.state.delay -= 1;
+        }
 
-            self.timer = Instant::now();
+        self.sound_playing = self.state.sound > 0;
+        if self.state.sound > 0 {
+            self.state.sound -= 1;
         }
+
+        self.frames += 1;
     }
 }
 
 /// Trait to get the address of a variable.
+#[cfg(all(feature = "std", target_arch = "x86_64"))]
 trait Address {
     /// Returns the address of `self`, possibly offsetted by the given number of bytes.
     fn address(&self, offset: usize) -> usize;
 }
 
+#[cfg(all(feature = "std", target_arch = "x86_64"))]
 impl<T> Address for T {
     fn address(&self, offset: usize) -> usize {
         self as *const T as usize + offset
@@ -309,6 +1302,8 @@ impl<T> Address for T {
 }
 
 /// Commands to send to the core.
+#[cfg(feature = "std")]
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug)]
 pub enum Risp8Command {
     /// Set a key as pressed or unpressed.
@@ -318,6 +1313,26 @@ pub enum Risp8Command {
     SetKey(usize, bool),
     /// Request to get the current state of the screen.
     GetScreen,
+    /// Request to get a copy of the state of the virtual machine.
+    GetState,
+    /// Request to get the performance counters of the backend.
+    GetStats,
+    /// Write the given data in memory at the given address.
+    ///
+    /// Ignored if the data goes past the end of memory, and while a movie is recorded or played.
+    WriteMemory(u16, Vec<u8>),
+    /// Request to save the current state.
+    SaveState,
+    /// Restore a state saved with [SaveState](Risp8Command::SaveState).
+    LoadState(Vec<u8>),
+    /// Restore the state as it was the given number of frames ago. See [Chip8::rewind].
+    Rewind(u32),
+    /// Restart the program and record a movie with the given seed. See [Chip8::record_movie].
+    RecordMovie(u64),
+    /// Restart the program and play the given movie, in read-only mode if true. See [Chip8::play_movie].
+    PlayMovie(Movie, bool),
+    /// Stop recording or playing the current movie, which is sent back in [Risp8Answer::Movie].
+    StopMovie,
     /// Resume emulation.
     Play,
     /// Pause emulation.
@@ -326,29 +1341,136 @@ pub enum Risp8Command {
     SingleStep,
     /// Set the execution method.
     SetExecutionMethod(ExecutionMethod),
+    /// Set the quirks used to execute instructions. Ignored while a movie is recorded or played.
+    SetQuirks(Quirks),
+    /// Reset the core with the configuration of the given platform, restarting the program.
+    ///
+    /// Ignored if the program does not fit in the memory of the platform.
+    SetPlatform(Platform),
+    /// Load the given font set in memory. Ignored while a movie is recorded or played.
+    SetFont(Font),
+    /// Set the number of instructions executed between two 60Hz timer ticks. 0 is treated as 1.
+    ///
+    /// Ignored while a movie is recorded or played.
+    SetInstructionsPerFrame(u32),
+    /// Execute frames at 60Hz in real time if true, as fast as possible if false.
+    SetPacing(bool),
+    /// Set when [Risp8Answer::Frame] is sent.
+    SetFrameDelivery(FrameDelivery),
+    /// Seed the random number generator used by Cxkk. Ignored while a movie is recorded or played.
+    SetSeed(u64),
+    /// Add a breakpoint at the given address. See [Chip8::add_breakpoint].
+    AddBreakpoint(u16),
+    /// Set the breakpoint at the given address. See [Chip8::set_breakpoint].
+    SetBreakpoint(u16, Breakpoint),
+    /// Remove the breakpoint at the given address.
+    RemoveBreakpoint(u16),
+    /// Add a memory watchpoint. See [Chip8::add_watchpoint].
+    AddWatchpoint(Watchpoint),
+    /// Remove a memory watchpoint.
+    RemoveWatchpoint(Watchpoint),
     /// Request to end the [run](Chip8::run) method.
     Exit,
 }
 
+/// The reasons why the core stops executing instructions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    /// The program executed the SUPER-CHIP exit instruction (00FD).
+    Exited,
+    /// The program faulted. PC points to the faulting instruction.
+    Trap(Trap),
+    /// The state does not match the checksum of the movie being played after `cycle` instructions.
+    MovieDesync { cycle: u64 },
+    /// Execution reached the breakpoint at the given address. The instruction at the breakpoint has not been executed.
+    Breakpoint(u16),
+    /// The last executed instruction accessed a watched address. This is its first watched access, see
+    /// [Chip8::watchpoint_hits] for the others.
+    Watchpoint(WatchpointHit),
+}
+
+/// Specifies when the core sends the screen in [Risp8Answer::Frame] at the end of the emulated frames.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FrameDelivery {
+    /// Frames are never sent, the screen has to be requested with [Risp8Command::GetScreen].
+    #[default]
+    Never,
+    /// The screen is sent at the end of every frame.
+    EveryFrame,
+    /// The screen is sent at the end of the frames during which it has been modified.
+    WhenDirty,
+}
+
 /// Specifies which method to use to execute instructions.
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ExecutionMethod {
     Interpreter,
     CachedInterpreter,
     CachedInterpreter2,
     CachedInterpreter3,
+    /// Only available on x86_64 with the `std` feature, the interpreter is used instead otherwise.
     Jit,
 }
 
+impl ExecutionMethod {
+    /// Creates a new backend for this execution method.
+    pub fn backend(self) -> Box<dyn ExecutionBackend + Send> {
+        match self {
+            Self::Interpreter => Box::new(Interpreter),
+            Self::CachedInterpreter => Box::new(CachedInterpreter::new()),
+            Self::CachedInterpreter2 => Box::new(CachedInterpreter2::new()),
+            Self::CachedInterpreter3 => Box::new(CachedInterpreter3::new()),
+            #[cfg(all(feature = "std", target_arch = "x86_64"))]
+            Self::Jit => Box::new(Jit::new()),
+            #[cfg(not(all(feature = "std", target_arch = "x86_64")))]
+            Self::Jit => Box::new(Interpreter),
+        }
+    }
+}
+
 /// Answers from the core.
+#[cfg(feature = "std")]
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug)]
 pub enum Risp8Answer {
-    /// A copy of the screen.
+    /// A copy of the screen, answer to [Risp8Command::GetScreen].
     Screen(Screen),
+    /// The screen at the end of an emulated frame, according to the [FrameDelivery] setting.
+    Frame(Screen),
+    /// A copy of the state of the virtual machine.
+    State(Box<State>),
+    /// The performance counters of the backend, answer to [Risp8Command::GetStats].
+    Stats(Stats),
+    /// The current state in the save state format, answer to [Risp8Command::SaveState].
+    SaveState(Vec<u8>),
+    /// The result of [Risp8Command::LoadState].
+    StateLoaded(Result<(), SaveStateError>),
+    /// The result of [Risp8Command::RecordMovie] or [Risp8Command::PlayMovie].
+    MovieStarted(Result<(), MovieError>),
+    /// The movie stopped by [Risp8Command::StopMovie], if any.
+    Movie(Option<Movie>),
+    /// The end of the movie being played has been reached.
+    MovieEnded,
+    /// The state does not match the checksum of the movie being played after `cycle` instructions, and the core has
+    /// been paused.
+    MovieDesync { cycle: u64 },
     /// Indicates that the sound should start to be continuously emited.
     ///
-    /// This is emitted 60 times per seconds for as long as a sound should be emitted.
+    /// This is emitted on every timer tick (60 times per seconds when pacing) for as long as a sound should be emitted.
     PlaySound,
     /// Indicates that the sound should stop.
     StopSound,
+    /// The program executed the SUPER-CHIP exit instruction (00FD) and the core has been paused.
+    Exited,
+    /// The program faulted and the core has been paused on the faulting instruction.
+    Trap(Trap),
+    /// Execution reached the breakpoint at the given address and the core has been paused before executing it.
+    BreakpointHit(u16),
+    /// An instruction accessed a watched address and the core has been paused after executing it.
+    ///
+    /// It is sent for each watched address accessed by the instruction.
+    WatchpointHit(WatchpointHit),
+    /// A message logged by a tracepoint.
+    Log(String),
 }
@@ -0,0 +1,343 @@
+//! Input movies, recording the key presses of a run to replay it exactly.
+//!
+//! Inputs are recorded with the number of instructions executed when they have been applied. As timers and the random
+//! number generator only depend on the executed instructions, replaying the inputs at the same instructions from
+//! power-on with the same settings reproduces the run exactly, whatever the execution method.
+
+use crate::{MovieError, Platform, Quirks, State};
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+/// Header of the movie files.
+const MAGIC: &str = "risp8-movie";
+
+/// An input movie.
+///
+/// Movies are saved in a line-based text format:
+/// ```text
+/// risp8-movie 1
+/// rom_hash 1C5B1A9E2F03D874
+/// platform schip
+/// quirks 010010
+/// instructions_per_frame 30
+/// seed 42
+/// rerecords 3
+/// input 1520 5 1
+/// input 1693 5 0
+/// checksum 1800 9A3F6B0C11D2E485
+/// ```
+/// `platform` is `none` when no platform is used. `quirks` lists the quirks in declaration order.
+/// `input` lines are the instruction index, the key and 1 if pressed or 0 if released.
+/// `checksum` lines are the instruction index and the checksum of the state at that point.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Movie {
+    /// The hash of the ROM the movie has been recorded with. See [Movie::rom_hash].
+    pub rom_hash: u64,
+    /// The platform the core is configured for when the movie starts.
+    pub platform: Option<Platform>,
+    /// The quirks used during the movie.
+    pub quirks: Quirks,
+    /// The number of instructions per frame during the movie.
+    pub instructions_per_frame: u32,
+    /// The seed of the random number generator at power-on.
+    pub seed: u64,
+    /// The number of times the movie has been re-recorded.
+    pub rerecords: u32,
+    /// The inputs, sorted by instruction index.
+    pub inputs: Vec<MovieInput>,
+    /// The checksums of the state, sorted by instruction index.
+    pub checksums: Vec<MovieChecksum>,
+}
+
+/// A key press or release in a movie.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MovieInput {
+    /// The number of instructions executed when the input is applied.
+    pub cycle: u64,
+    /// The key number (0 to 15).
+    pub key: u8,
+    /// True if pressed, false if released.
+    pub pressed: bool,
+}
+
+/// The checksum of the state at some point of a movie, used to detect desynchronizations.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MovieChecksum {
+    /// The number of instructions executed when the checksum has been taken.
+    pub cycle: u64,
+    /// The checksum of the save state.
+    pub checksum: u64,
+}
+
+impl Movie {
+    /// The version of the movies created by [Movie::save].
+    pub const VERSION: u32 = 1;
+    /// A checksum is taken every this number of frames.
+    pub const CHECKSUM_INTERVAL: u64 = 60;
+
+    /// Returns the hash of the given ROM, as stored in movies.
+    pub fn rom_hash(rom: &[u8]) -> u64 {
+        fnv1a(rom)
+    }
+
+    /// Returns the checksum of the given state, as stored in movies.
+    pub fn checksum(state: &State) -> u64 {
+        fnv1a(&state.save())
+    }
+
+    /// Serializes the movie in the risp8 movie format.
+    pub fn save(&self) -> String {
+        let mut out = String::new();
+        let quirks: String = quirks_to_array(self.quirks).iter().map(|&q| if q { '1' } else { '0' }).collect();
+
+        // Writing to a String never fails.
+        let _ = writeln!(out, "{MAGIC} {}", Self::VERSION);
+        let _ = writeln!(out, "rom_hash {:016X}", self.rom_hash);
+        let _ = writeln!(out, "platform {}", self.platform.map_or("none", Platform::name));
+        let _ = writeln!(out, "quirks {quirks}");
+        let _ = writeln!(out, "instructions_per_frame {}", self.instructions_per_frame);
+        let _ = writeln!(out, "seed {}", self.seed);
+        let _ = writeln!(out, "rerecords {}", self.rerecords);
+        for input in &self.inputs {
+            let _ = writeln!(out, "input {} {:X} {}", input.cycle, input.key, input.pressed as u8);
+        }
+        for checksum in &self.checksums {
+            let _ = writeln!(out, "checksum {} {:016X}", checksum.cycle, checksum.checksum);
+        }
+
+        out
+    }
+
+    /// Deserializes a movie saved with [Movie::save].
+    pub fn load(data: &str) -> Result<Self, MovieError> {
+        let mut lines = data.lines().enumerate().map(|(i, line)| (i + 1, line.split_whitespace().collect::<Vec<_>>()));
+
+        match lines.next() {
+            Some((_, header)) if header.len() == 2 && header[0] == MAGIC => {
+                let version = header[1].parse().map_err(|_| MovieError::InvalidFormat { line: 1 })?;
+                if version != Self::VERSION {
+                    return Err(MovieError::UnsupportedVersion(version));
+                }
+            },
+            _ => return Err(MovieError::InvalidFormat { line: 1 }),
+        }
+
+        let mut rom_hash = None;
+        let mut platform = None;
+        let mut quirks = None;
+        let mut instructions_per_frame = None;
+        let mut seed = None;
+        let mut rerecords = 0;
+        let mut inputs: Vec<MovieInput> = Vec::new();
+        let mut checksums: Vec<MovieChecksum> = Vec::new();
+
+        for (line, fields) in lines {
+            let invalid = MovieError::InvalidFormat { line };
+            match fields.as_slice() {
+                [] => (),
+                ["rom_hash", hash] => rom_hash = Some(u64::from_str_radix(hash, 16).map_err(|_| invalid)?),
+                ["platform", "none"] => platform = Some(None),
+                ["platform", name] => platform = Some(Some(Platform::from_name(name).ok_or(invalid)?)),
+                ["quirks", bits] => quirks = Some(parse_quirks(bits).ok_or(invalid)?),
+                ["instructions_per_frame", n] => instructions_per_frame = Some(n.parse().map_err(|_| invalid)?),
+                ["seed", n] => seed = Some(n.parse().map_err(|_| invalid)?),
+                ["rerecords", n] => rerecords = n.parse().map_err(|_| invalid)?,
+                ["input", cycle, key, pressed] => {
+                    let cycle = cycle.parse().map_err(|_| invalid)?;
+                    let key = u8::from_str_radix(key, 16).ok().filter(|&key| key <= 0xF).ok_or(invalid)?;
+                    let pressed = match *pressed {
+                        "0" => false,
+                        "1" => true,
+                        _ => return Err(invalid),
+                    };
+                    if inputs.last().is_some_and(|last| last.cycle > cycle) {
+                        return Err(invalid);
+                    }
+                    inputs.push(MovieInput { cycle, key, pressed });
+                },
+                ["checksum", cycle, checksum] => {
+                    let cycle = cycle.parse().map_err(|_| invalid)?;
+                    let checksum = u64::from_str_radix(checksum, 16).map_err(|_| invalid)?;
+                    if checksums.last().is_some_and(|last| last.cycle >= cycle) {
+                        return Err(invalid);
+                    }
+                    checksums.push(MovieChecksum { cycle, checksum });
+                },
+                _ => return Err(invalid),
+            }
+        }
+
+        Ok(Self {
+            rom_hash: rom_hash.ok_or(MovieError::MissingField("rom_hash"))?,
+            platform: platform.ok_or(MovieError::MissingField("platform"))?,
+            quirks: quirks.ok_or(MovieError::MissingField("quirks"))?,
+            instructions_per_frame: instructions_per_frame.filter(|&n| n > 0).ok_or(MovieError::MissingField("instructions_per_frame"))?,
+            seed: seed.ok_or(MovieError::MissingField("seed"))?,
+            rerecords,
+            inputs,
+            checksums,
+        })
+    }
+}
+
+/// The way a movie is used by the core.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MovieMode {
+    /// Inputs are recorded in the movie.
+    Recording,
+    /// Inputs are replayed from the movie. When `read_only` is false, an input from the user truncates the movie and
+    /// starts recording from there (re-record).
+    Playing { read_only: bool },
+    /// Playback reached the end of a read-only movie. Inputs are neither replayed nor recorded.
+    Finished,
+}
+
+/// A movie being recorded or played by the core.
+#[derive(Clone, Debug)]
+pub(crate) struct ActiveMovie {
+    pub(crate) movie: Movie,
+    pub(crate) mode: MovieMode,
+    /// The index of the next input to replay.
+    next_input: usize,
+}
+
+impl ActiveMovie {
+    pub(crate) fn new(movie: Movie, mode: MovieMode) -> Self {
+        Self {
+            movie,
+            mode,
+            next_input: 0,
+        }
+    }
+
+    /// Handles an input from the user. Returns true if it has to be applied to the state.
+    pub(crate) fn user_input(&mut self, state: &State, key: usize, pressed: bool) -> bool {
+        match self.mode {
+            MovieMode::Playing { read_only: true } => return false,
+            MovieMode::Playing { read_only: false } => {
+                // Keep the inputs already replayed.
+                self.truncate(self.next_input, state.cycles);
+                self.mode = MovieMode::Recording;
+            },
+            MovieMode::Recording | MovieMode::Finished => (),
+        }
+
+        if self.mode == MovieMode::Recording && key <= 0xF && state.keys[key] != pressed {
+            self.movie.inputs.push(MovieInput { cycle: state.cycles, key: key as u8, pressed });
+        }
+
+        true
+    }
+
+    /// Applies the inputs of the current instruction when playing.
+    ///
+    /// Returns the number of instructions until the next input, which execution must stop at. Playback continues after
+    /// the last input until the last checksum has been verified.
+    pub(crate) fn replay_inputs(&mut self, state: &mut State) -> Option<u64> {
+        let MovieMode::Playing { .. } = self.mode else {
+            return None;
+        };
+
+        while let Some(input) = self.movie.inputs.get(self.next_input) {
+            if input.cycle > state.cycles {
+                return Some(input.cycle - state.cycles);
+            }
+            state.set_key(input.key as usize, input.pressed);
+            self.next_input += 1;
+        }
+
+        self.end_playback(state);
+        None
+    }
+
+    /// Ends the playback once every input has been replayed and every checksum has been verified.
+    fn end_playback(&mut self, state: &State) {
+        let MovieMode::Playing { read_only } = self.mode else {
+            return;
+        };
+
+        if self.next_input == self.movie.inputs.len() && self.movie.checksums.last().is_none_or(|last| last.cycle <= state.cycles) {
+            self.mode = if read_only { MovieMode::Finished } else { MovieMode::Recording };
+        }
+    }
+
+    /// Called on every timer tick. Records or verifies the checksum of the state every
+    /// [Movie::CHECKSUM_INTERVAL] frames.
+    ///
+    /// Returns false if the checksum does not match the one of the movie.
+    pub(crate) fn tick(&mut self, state: &State) -> bool {
+        if state.cycles % (self.movie.instructions_per_frame as u64 * Movie::CHECKSUM_INTERVAL) != 0 {
+            return true;
+        }
+
+        match self.mode {
+            MovieMode::Recording => {
+                if self.movie.checksums.last().is_none_or(|last| last.cycle < state.cycles) {
+                    self.movie.checksums.push(MovieChecksum { cycle: state.cycles, checksum: Movie::checksum(state) });
+                }
+                true
+            },
+            MovieMode::Playing { .. } => {
+                let valid = match self.movie.checksums.binary_search_by_key(&state.cycles, |checksum| checksum.cycle) {
+                    Ok(i) => self.movie.checksums[i].checksum == Movie::checksum(state),
+                    Err(_) => true,
+                };
+                if valid {
+                    self.end_playback(state);
+                }
+                valid
+            },
+            MovieMode::Finished => true,
+        }
+    }
+
+    /// Moves the movie to the given state after it has been loaded.
+    ///
+    /// The inputs of the instruction of the state have not been applied yet, so they are replayed again. When recording,
+    /// this is a re-record and they are deleted with the inputs after them.
+    pub(crate) fn seek(&mut self, state: &State) {
+        self.next_input = self.movie.inputs.partition_point(|input| input.cycle < state.cycles);
+        if self.mode == MovieMode::Recording {
+            self.truncate(self.next_input, state.cycles);
+        }
+    }
+
+    /// Keeps the first `inputs` inputs and the checksums up to the given instruction index, counting a re-record.
+    fn truncate(&mut self, inputs: usize, cycle: u64) {
+        let checksums = self.movie.checksums.partition_point(|checksum| checksum.cycle <= cycle);
+        self.movie.inputs.truncate(inputs);
+        self.movie.checksums.truncate(checksums);
+        self.movie.rerecords += 1;
+    }
+}
+
+fn quirks_to_array(quirks: Quirks) -> [bool; 6] {
+    [quirks.vf_reset, quirks.shift_vx, quirks.increment_i, quirks.wrap_sprites, quirks.jump_vx, quirks.lores_big_sprites]
+}
+
+fn parse_quirks(bits: &str) -> Option<Quirks> {
+    let bits: Vec<bool> = bits.chars().map(|c| match c {
+        '0' => Some(false),
+        '1' => Some(true),
+        _ => None,
+    }).collect::<Option<_>>()?;
+
+    match bits.as_slice() {
+        &[vf_reset, shift_vx, increment_i, wrap_sprites, jump_vx, lores_big_sprites] => Some(Quirks {
+            vf_reset,
+            shift_vx,
+            increment_i,
+            wrap_sprites,
+            jump_vx,
+            lores_big_sprites,
+        }),
+        _ => None,
+    }
+}
+
+/// 64-bit FNV-1a hash.
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xCBF29CE484222325, |hash, &b| (hash ^ b as u64).wrapping_mul(0x100000001B3))
+}
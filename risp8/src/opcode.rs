@@ -45,8 +45,8 @@ impl From<u16> for Opcode {
     }
 }
 
-impl std::fmt::UpperHex for Opcode {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        std::fmt::UpperHex::fmt(&self.0, f)
+impl core::fmt::UpperHex for Opcode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::UpperHex::fmt(&self.0, f)
     }
 }
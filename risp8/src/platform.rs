@@ -0,0 +1,114 @@
+use crate::{Font, Quirks};
+
+/// Chip8 platforms, bundling the quirks, memory size, load address, font and speed of each of them.
+///
+/// See <https://chip-8.github.io/extensions/> for a description of the platforms.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Platform {
+    /// The original CHIP-8 interpreter of the RCA COSMAC VIP.
+    CosmacVip,
+    /// CHIP-48 on the HP-48 calculators.
+    Chip48,
+    /// SUPER-CHIP 1.1.
+    Schip,
+    /// Octo's XO-CHIP.
+    XoChip,
+    /// The ETI-660 learning computer, loading programs at 0x600.
+    Eti660,
+    /// CHIPOS on the DREAM 6800.
+    Dream6800,
+}
+
+impl Platform {
+    /// All the platforms.
+    pub const ALL: [Platform; 6] = [
+        Platform::CosmacVip,
+        Platform::Chip48,
+        Platform::Schip,
+        Platform::XoChip,
+        Platform::Eti660,
+        Platform::Dream6800,
+    ];
+
+    /// Returns the short name of the platform, as accepted by [Platform::from_name].
+    pub const fn name(self) -> &'static str {
+        match self {
+            Platform::CosmacVip => "vip",
+            Platform::Chip48 => "chip48",
+            Platform::Schip => "schip",
+            Platform::XoChip => "xochip",
+            Platform::Eti660 => "eti660",
+            Platform::Dream6800 => "dream6800",
+        }
+    }
+
+    /// Returns the platform with the given short name (case insensitive), or None if it does not exist.
+    pub fn from_name(name: &str) -> Option<Platform> {
+        Self::ALL.into_iter().find(|platform| platform.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Returns the quirks of the platform.
+    pub const fn quirks(self) -> Quirks {
+        match self {
+            Platform::CosmacVip | Platform::Eti660 | Platform::Dream6800 => Quirks {
+                vf_reset: true,
+                shift_vx: false,
+                increment_i: true,
+                wrap_sprites: false,
+                jump_vx: false,
+                lores_big_sprites: true,
+            },
+            Platform::Chip48 | Platform::Schip => Quirks {
+                vf_reset: false,
+                shift_vx: true,
+                increment_i: false,
+                wrap_sprites: false,
+                jump_vx: true,
+                lores_big_sprites: false,
+            },
+            Platform::XoChip => Quirks {
+                vf_reset: false,
+                shift_vx: false,
+                increment_i: true,
+                wrap_sprites: true,
+                jump_vx: false,
+                lores_big_sprites: true,
+            },
+        }
+    }
+
+    /// Returns the size of the addressable memory in bytes.
+    pub const fn memory_size(self) -> usize {
+        match self {
+            Platform::XoChip => 0x10000,
+            _ => 0x1000,
+        }
+    }
+
+    /// Returns the address where programs are loaded and start executing.
+    pub const fn load_address(self) -> u16 {
+        match self {
+            Platform::Eti660 => 0x600,
+            _ => 0x200,
+        }
+    }
+
+    /// Returns the number of instructions executed per 60Hz frame on this platform.
+    pub const fn instructions_per_frame(self) -> u32 {
+        match self {
+            Platform::CosmacVip | Platform::Eti660 | Platform::Dream6800 => 15,
+            Platform::Chip48 | Platform::Schip => 30,
+            Platform::XoChip => 1000,
+        }
+    }
+
+    /// Returns the font set of the platform.
+    pub const fn font(self) -> Font {
+        match self {
+            Platform::CosmacVip => Font::VIP,
+            Platform::Chip48 | Platform::Schip | Platform::XoChip => Font::CHIP48,
+            Platform::Eti660 => Font::ETI660,
+            Platform::Dream6800 => Font::DREAM6800,
+        }
+    }
+}
@@ -0,0 +1,124 @@
+//! Rewind buffer of compressed save states.
+//!
+//! Snapshots are compressed with PackBits, which is enough for the mostly empty memory and screen of Chip8 programs.
+
+use crate::State;
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+/// Ring of the latest compressed snapshots of the state.
+#[derive(Clone, Debug)]
+pub(crate) struct Rewind {
+    /// The compressed snapshots with the number of instructions executed when they have been taken.
+    snapshots: VecDeque<(u64, Vec<u8>)>,
+    /// The maximum number of snapshots kept. 0 disables rewinding.
+    capacity: usize,
+    /// The number of frames between two snapshots.
+    interval: u32,
+    /// The number of frames since the last snapshot.
+    frames: u32,
+}
+
+impl Rewind {
+    pub(crate) fn new(capacity: usize, interval: u32) -> Self {
+        Self {
+            snapshots: VecDeque::with_capacity(capacity),
+            capacity,
+            interval: interval.max(1),
+            frames: 0,
+        }
+    }
+
+    /// Called at the end of every frame, taking a snapshot of the state every `interval` frames.
+    pub(crate) fn end_frame(&mut self, state: &State) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        self.frames += 1;
+        if self.frames >= self.interval {
+            self.frames = 0;
+            if self.snapshots.len() == self.capacity {
+                self.snapshots.pop_front();
+            }
+            self.snapshots.push_back((state.cycles, pack(&state.save())));
+        }
+    }
+
+    /// Removes the snapshots of the last `frames` frames before the given state and returns the oldest of them
+    /// decompressed, or None if there is no snapshot.
+    ///
+    /// Snapshots that are not older than the given state are discarded first. If there are not enough snapshots,
+    /// returns the oldest one available.
+    pub(crate) fn rewind(&mut self, state: &State, frames: u32) -> Option<Vec<u8>> {
+        while self.snapshots.back().is_some_and(|&(cycles, _)| cycles >= state.cycles) {
+            self.snapshots.pop_back();
+        }
+
+        let count = frames.div_ceil(self.interval);
+        let mut snapshot = None;
+        for _ in 0..count {
+            match self.snapshots.pop_back() {
+                Some((_, s)) => snapshot = Some(s),
+                None => break,
+            }
+        }
+
+        self.frames = 0;
+        snapshot.map(|s| unpack(&s))
+    }
+
+    /// Deletes every snapshot.
+    pub(crate) fn clear(&mut self) {
+        self.snapshots.clear();
+        self.frames = 0;
+    }
+}
+
+/// Compresses the given data with PackBits.
+///
+/// Each packet starts with a header byte n: when n < 128, it is followed by n + 1 literal bytes. Otherwise it is
+/// followed by a single byte repeated 257 - n times.
+fn pack(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut i = 0;
+
+    while i < data.len() {
+        let run = data[i..].iter().take(128).take_while(|&&b| b == data[i]).count();
+        if run >= 2 {
+            out.push((257 - run) as u8);
+            out.push(data[i]);
+            i += run;
+        } else {
+            // Literal bytes until the next run of at least 2 bytes.
+            let start = i;
+            while i < data.len() && i - start < 128 && (i + 1 >= data.len() || data[i] != data[i + 1]) {
+                i += 1;
+            }
+            out.push((i - start - 1) as u8);
+            out.extend_from_slice(&data[start..i]);
+        }
+    }
+
+    out
+}
+
+/// Decompresses data compressed by [pack].
+fn unpack(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut i = 0;
+
+    while i < data.len() {
+        let n = data[i] as usize;
+        if n < 128 {
+            out.extend_from_slice(&data[i + 1..i + 2 + n]);
+            i += 2 + n;
+        } else {
+            out.extend(core::iter::repeat_n(data[i + 1], 257 - n));
+            i += 2;
+        }
+    }
+
+    out
+}
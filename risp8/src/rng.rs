@@ -0,0 +1,30 @@
+/// Pseudo-random number generator used by Cxkk.
+///
+/// It is a xorshift64 generator, simple enough to be inlined by the JIT so every execution method produces the same
+/// sequence for the same seed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Rng {
+    /// The xorshift64 state, never 0.
+    pub(crate) state: u64,
+}
+
+impl Rng {
+    /// Returns a new generator initialized from the given seed.
+    pub(crate) const fn new(seed: u64) -> Self {
+        // Mix the seed with splitmix64 so close seeds give unrelated sequences.
+        let mut z = seed.wrapping_add(0x9E3779B97F4A7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^= z >> 31;
+
+        Self { state: if z == 0 { 0x9E3779B97F4A7C15 } else { z } }
+    }
+
+    /// Advances the generator and returns its next byte, which is the highest byte of the new state.
+    pub(crate) fn next_byte(&mut self) -> u8 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        (self.state >> 56) as u8
+    }
+}
@@ -0,0 +1,239 @@
+//! Binary save state format.
+//!
+//! A save state starts with the magic `RSP8` and a little-endian u16 version, followed by every field of [State] in
+//! little-endian. Only the memory available to the program is stored.
+//!
+//! Version 1 layout, after the header:
+//! - SP (u8), PC (u16), I (u16), stack (16 u16), V (16 u8)
+//! - memory size (u32), memory (memory size bytes)
+//! - delay (u8), sound (u8), screen high resolution (u8), screen pixels (128 * 64 u8 bitplane masks), keys (16 u8)
+//! - RPL flags (16 u8), bitplanes (u8), audio pattern (16 u8), pitch (u8)
+//! - small font address (u16), big font address (u16)
+//! - RNG state (u64), wait key status (u8: 0 not waiting, 1 waiting, 2 key received) and key (u8)
+//! - quirks (u8 bitfield in declaration order), cycles (u64), timer phase (u32)
+
+use crate::{Font, Quirks, Screen, SaveStateError, State, WaitKey};
+use crate::rng::Rng;
+use crate::watchpoint::NO_WATCH;
+
+use alloc::vec::Vec;
+
+const MAGIC: [u8; 4] = *b"RSP8";
+
+impl State {
+    /// The version of the save states created by [State::save].
+    pub const SAVE_STATE_VERSION: u16 = 1;
+
+    /// Serializes the state in the risp8 save state format.
+    pub fn save(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.memory_size + Screen::HIRES_WIDTH * Screen::HIRES_HEIGHT + 256);
+        out.extend_from_slice(&MAGIC);
+        out.extend_from_slice(&Self::SAVE_STATE_VERSION.to_le_bytes());
+
+        out.push(self.SP as u8);
+        out.extend_from_slice(&self.PC.to_le_bytes());
+        out.extend_from_slice(&self.I.to_le_bytes());
+        self.stack.iter().for_each(|addr| out.extend_from_slice(&addr.to_le_bytes()));
+        out.extend_from_slice(&self.V);
+
+        out.extend_from_slice(&(self.memory_size as u32).to_le_bytes());
+        out.extend_from_slice(&self.memory[..self.memory_size]);
+
+        out.push(self.delay);
+        out.push(self.sound);
+        out.push(self.screen.is_hires() as u8);
+        self.screen.pixels.iter().for_each(|row| out.extend_from_slice(row));
+        self.keys.iter().for_each(|&key| out.push(key as u8));
+
+        out.extend_from_slice(&self.rpl);
+        out.push(self.planes);
+        out.extend_from_slice(&self.audio_pattern);
+        out.push(self.pitch);
+
+        out.extend_from_slice(&self.font_address.to_le_bytes());
+        out.extend_from_slice(&self.big_font_address.to_le_bytes());
+
+        out.extend_from_slice(&self.rng.state.to_le_bytes());
+        match self.wait_key {
+            WaitKey::NotWaiting => out.extend_from_slice(&[0, 0]),
+            WaitKey::Waiting => out.extend_from_slice(&[1, 0]),
+            WaitKey::Key(key) => out.extend_from_slice(&[2, key]),
+        }
+
+        let quirks = [
+            self.quirks.vf_reset,
+            self.quirks.shift_vx,
+            self.quirks.increment_i,
+            self.quirks.wrap_sprites,
+            self.quirks.jump_vx,
+            self.quirks.lores_big_sprites,
+        ];
+        out.push(quirks.iter().enumerate().fold(0, |bits, (i, &quirk)| bits | (quirk as u8) << i));
+        out.extend_from_slice(&self.cycles.to_le_bytes());
+        out.extend_from_slice(&self.timer_phase.to_le_bytes());
+
+        out
+    }
+
+    /// Deserializes a state saved with [State::save].
+    pub fn load(data: &[u8]) -> Result<Self, SaveStateError> {
+        let mut reader = Reader { data };
+
+        if reader.bytes::<4>()? != MAGIC {
+            return Err(SaveStateError::InvalidFormat);
+        }
+        let version = reader.u16()?;
+        if version != Self::SAVE_STATE_VERSION {
+            return Err(SaveStateError::UnsupportedVersion(version));
+        }
+
+        let sp = reader.u8()? as usize;
+        let pc = reader.u16()?;
+        let i = reader.u16()?;
+        let mut stack = [0; 16];
+        for addr in &mut stack {
+            *addr = reader.u16()?;
+        }
+        let v = reader.bytes::<16>()?;
+
+        let memory_size = reader.u32()? as usize;
+        if !(Self::INITIAL_PC..=Self::MEMORY_SIZE).contains(&memory_size) {
+            return Err(SaveStateError::Corrupted);
+        }
+        let mut memory = [0; Self::MEMORY_SIZE];
+        memory[..memory_size].copy_from_slice(reader.slice(memory_size)?);
+
+        let delay = reader.u8()?;
+        let sound = reader.u8()?;
+        let mut screen = Screen::new();
+        screen.set_hires(reader.bool()?);
+        for row in &mut screen.pixels {
+            row.copy_from_slice(reader.slice(Screen::HIRES_WIDTH)?);
+            if row.iter().any(|&pixel| pixel & !Screen::ALL_PLANES != 0) {
+                return Err(SaveStateError::Corrupted);
+            }
+        }
+        let mut keys = [false; 16];
+        for key in &mut keys {
+            *key = reader.bool()?;
+        }
+
+        let rpl = reader.bytes::<16>()?;
+        let planes = reader.u8()?;
+        let audio_pattern = reader.bytes::<16>()?;
+        let pitch = reader.u8()?;
+
+        let font_address = reader.u16()?;
+        let big_font_address = reader.u16()?;
+
+        let rng = Rng { state: reader.u64()? };
+        let wait_key = match reader.bytes::<2>()? {
+            [0, 0] => WaitKey::NotWaiting,
+            [1, 0] => WaitKey::Waiting,
+            [2, key] if key <= 0xF => WaitKey::Key(key),
+            _ => return Err(SaveStateError::Corrupted),
+        };
+
+        let quirks = reader.u8()?;
+        let quirks = Quirks {
+            vf_reset: quirks & 1 != 0,
+            shift_vx: quirks & 2 != 0,
+            increment_i: quirks & 4 != 0,
+            wrap_sprites: quirks & 8 != 0,
+            jump_vx: quirks & 16 != 0,
+            lores_big_sprites: quirks & 32 != 0,
+        };
+        let cycles = reader.u64()?;
+        let timer_phase = reader.u32()?;
+
+        if !reader.data.is_empty() ||
+            sp > stack.len() ||
+            planes > Screen::ALL_PLANES ||
+            rng.state == 0 ||
+            font_address as usize + Font::SMALL_LEN > Self::INITIAL_PC ||
+            big_font_address as usize + Font::BIG_LEN > Self::INITIAL_PC
+        {
+            return Err(SaveStateError::Corrupted);
+        }
+
+        Ok(Self {
+            SP: sp,
+            PC: pc,
+            I: i,
+            stack,
+            V: v,
+            memory,
+            memory_size,
+            delay,
+            sound,
+            screen,
+            // Send the restored screen.
+            screen_dirty: true,
+            keys,
+            rpl,
+            planes,
+            audio_pattern,
+            pitch,
+            font_address,
+            big_font_address,
+            rng,
+
+            wait_key,
+            quirks,
+            exited: false,
+            trap: None,
+            cycles,
+            timer_phase,
+            invalidation: None,
+            watch_reads: NO_WATCH,
+            watch_writes: NO_WATCH,
+            watched_access: None,
+        })
+    }
+}
+
+/// Reads little-endian values from a save state.
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn slice(&mut self, len: usize) -> Result<&'a [u8], SaveStateError> {
+        if self.data.len() < len {
+            return Err(SaveStateError::Truncated);
+        }
+        let (slice, data) = self.data.split_at(len);
+        self.data = data;
+        Ok(slice)
+    }
+
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N], SaveStateError> {
+        let mut bytes = [0; N];
+        bytes.copy_from_slice(self.slice(N)?);
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, SaveStateError> {
+        Ok(self.bytes::<1>()?[0])
+    }
+
+    fn bool(&mut self) -> Result<bool, SaveStateError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(SaveStateError::Corrupted),
+        }
+    }
+
+    fn u16(&mut self) -> Result<u16, SaveStateError> {
+        Ok(u16::from_le_bytes(self.bytes()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, SaveStateError> {
+        Ok(u32::from_le_bytes(self.bytes()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, SaveStateError> {
+        Ok(u64::from_le_bytes(self.bytes()?))
+    }
+}
@@ -0,0 +1,101 @@
+/// The Chip8 screen.
+///
+/// The pixel buffer is always allocated at the SUPER-CHIP hi-res size. In low resolution mode only the top-left
+/// [Screen::LORES_WIDTH]x[Screen::LORES_HEIGHT] pixels are used.
+///
+/// Each pixel holds one bit per XO-CHIP bitplane: bit 0 is plane 1 and bit 1 is plane 2.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Screen {
+    pub(crate) pixels: [[u8; Screen::HIRES_WIDTH]; Screen::HIRES_HEIGHT],
+    hires: bool,
+}
+
+impl Screen {
+    pub const LORES_WIDTH: usize = 64;
+    pub const LORES_HEIGHT: usize = 32;
+    pub const HIRES_WIDTH: usize = 128;
+    pub const HIRES_HEIGHT: usize = 64;
+    /// Mask of all the bitplanes.
+    pub const ALL_PLANES: u8 = 0b11;
+
+    /// Returns a new blank low resolution screen.
+    pub const fn new() -> Self {
+        Self {
+            pixels: [[0; Self::HIRES_WIDTH]; Self::HIRES_HEIGHT],
+            hires: false,
+        }
+    }
+
+    /// Returns true if the screen is in SUPER-CHIP high resolution mode.
+    pub const fn is_hires(&self) -> bool {
+        self.hires
+    }
+
+    /// Returns the width of the screen in the current resolution.
+    pub const fn width(&self) -> usize {
+        if self.hires { Self::HIRES_WIDTH } else { Self::LORES_WIDTH }
+    }
+
+    /// Returns the height of the screen in the current resolution.
+    pub const fn height(&self) -> usize {
+        if self.hires { Self::HIRES_HEIGHT } else { Self::LORES_HEIGHT }
+    }
+
+    /// Returns the color index (0 to 3) of the pixel at the given coordinates.
+    ///
+    /// Without XO-CHIP bitplanes, 0 is off and 1 is lit.
+    /// Panics if the coordinates are outside of the current resolution.
+    pub fn pixel(&self, x: usize, y: usize) -> u8 {
+        assert!(x < self.width() && y < self.height(), "Pixel ({x}, {y}) is outside of the screen");
+        self.pixels[y][x]
+    }
+
+    /// Returns an iterator over the rows of pixels in the current resolution.
+    pub fn rows(&self) -> impl Iterator<Item = &[u8]> {
+        let width = self.width();
+        self.pixels[..self.height()].iter().map(move |row| &row[..width])
+    }
+
+    /// Clears the given bitplanes.
+    pub(crate) fn clear(&mut self, planes: u8) {
+        for row in &mut self.pixels {
+            for pixel in row {
+                *pixel &= !planes;
+            }
+        }
+    }
+
+    /// Changes the resolution and clears the screen.
+    pub(crate) fn set_hires(&mut self, hires: bool) {
+        self.hires = hires;
+        self.clear(Self::ALL_PLANES);
+    }
+
+    /// Scrolls the given bitplanes by `dx` pixels to the right and `dy` pixels down (negative values to the left and
+    /// up). Pixels scrolled in from the edges are off.
+    pub(crate) fn scroll(&mut self, planes: u8, dx: isize, dy: isize) {
+        let width = self.width() as isize;
+        let height = self.height() as isize;
+        let source = self.pixels;
+
+        for y in 0..height {
+            for x in 0..width {
+                let (sx, sy) = (x - dx, y - dy);
+                let scrolled = if sx >= 0 && sx < width && sy >= 0 && sy < height {
+                    source[sy as usize][sx as usize] & planes
+                } else {
+                    0
+                };
+
+                let pixel = &mut self.pixels[y as usize][x as usize];
+                *pixel = *pixel & !planes | scrolled;
+            }
+        }
+    }
+}
+
+impl Default for Screen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
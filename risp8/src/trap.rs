@@ -0,0 +1,42 @@
+use core::fmt;
+
+/// Faults caused by the guest program.
+///
+/// When a trap occurs, the core is paused with PC pointing to the faulting instruction and the trap is sent as
+/// [Risp8Answer::Trap](crate::Risp8Answer::Trap). Resuming execution executes the faulting instruction again.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Trap {
+    /// The opcode at `pc` is not a valid instruction.
+    InvalidOpcode { pc: u16, opcode: u16 },
+    /// The subroutine call at `pc` has been executed with a full stack.
+    StackOverflow { pc: u16 },
+    /// The return instruction at `pc` has been executed with an empty stack.
+    StackUnderflow { pc: u16 },
+    /// The instruction at `pc` accessed `len` bytes at `address`, going past the end of memory.
+    MemoryOutOfRange { pc: u16, address: u16, len: u16 },
+}
+
+impl Trap {
+    /// Returns the address of the instruction that caused the trap.
+    pub const fn pc(&self) -> u16 {
+        match *self {
+            Trap::InvalidOpcode { pc, .. } => pc,
+            Trap::StackOverflow { pc } => pc,
+            Trap::StackUnderflow { pc } => pc,
+            Trap::MemoryOutOfRange { pc, .. } => pc,
+        }
+    }
+}
+
+impl fmt::Display for Trap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Trap::InvalidOpcode { pc, opcode } => write!(f, "invalid opcode {opcode:04X} at {pc:#X}"),
+            Trap::StackOverflow { pc } => write!(f, "stack overflow at {pc:#X}"),
+            Trap::StackUnderflow { pc } => write!(f, "stack underflow at {pc:#X}"),
+            Trap::MemoryOutOfRange { pc, address, len } => {
+                write!(f, "memory access of {len} bytes at {address:#X} out of range at {pc:#X}")
+            },
+        }
+    }
+}
@@ -0,0 +1,126 @@
+//! Memory watchpoints.
+//!
+//! The state only knows the smallest address ranges containing every read and write watchpoint. The instructions
+//! accessing memory record their access when it overlaps these ranges and end the current block, then the core checks
+//! the access against the watchpoints once the backend returns. The code compiled by the JIT checks the ranges at
+//! runtime and lets the interpreter execute the watched accesses.
+
+use crate::State;
+
+/// The kinds of memory accesses a [Watchpoint] fires on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    /// Both reads and writes.
+    Access,
+}
+
+impl WatchKind {
+    /// Returns true if a watchpoint of this kind fires on an access of the given kind.
+    const fn matches(self, access: WatchKind) -> bool {
+        matches!((self, access), (WatchKind::Access, _) | (WatchKind::Read, WatchKind::Read) | (WatchKind::Write, WatchKind::Write))
+    }
+}
+
+/// A watchpoint on the memory range [beg, end] inclusive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub beg: u16,
+    pub end: u16,
+    pub kind: WatchKind,
+}
+
+impl Watchpoint {
+    /// Creates a watchpoint on the memory range [beg, end] inclusive.
+    pub const fn new(beg: u16, end: u16, kind: WatchKind) -> Self {
+        Self { beg, end, kind }
+    }
+}
+
+/// An access to a watched memory address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WatchpointHit {
+    /// The address of the instruction accessing memory.
+    pub pc: u16,
+    /// The accessed address.
+    pub address: u16,
+    /// [WatchKind::Read] or [WatchKind::Write].
+    pub kind: WatchKind,
+    /// The value before the access. For reads it is the value read.
+    pub old: u8,
+    /// The value after the access. For reads it is the value read.
+    pub new: u8,
+}
+
+/// A memory access by the last executed instruction overlapping the watched ranges.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct WatchedAccess {
+    pc: u16,
+    kind: WatchKind,
+    address: u16,
+    len: u16,
+    /// The values before a write, which is at most 16 bytes long.
+    old: [u8; 16],
+}
+
+/// The range of the state without any watchpoint, which no access overlaps.
+pub(crate) const NO_WATCH: (u16, u16) = (u16::MAX, 0);
+
+/// Returns the smallest ranges containing every read and write watchpoint.
+pub(crate) fn watch_ranges(watchpoints: &[Watchpoint]) -> ((u16, u16), (u16, u16)) {
+    let extend = |(beg, end): (u16, u16), w: &Watchpoint| (beg.min(w.beg), end.max(w.end));
+    let reads = watchpoints.iter().filter(|w| w.kind.matches(WatchKind::Read)).fold(NO_WATCH, extend);
+    let writes = watchpoints.iter().filter(|w| w.kind.matches(WatchKind::Write)).fold(NO_WATCH, extend);
+    (reads, writes)
+}
+
+/// Returns the accesses of `access` to the addresses watched by `watchpoints`, with the given memory after the access.
+pub(crate) fn watchpoint_hits<'a>(access: WatchedAccess, watchpoints: &'a [Watchpoint], memory: &'a [u8]) -> impl Iterator<Item = WatchpointHit> + 'a {
+    (0..access.len).filter_map(move |i| {
+        let address = access.address + i;
+        watchpoints.iter()
+            .any(|w| w.kind.matches(access.kind) && (w.beg..=w.end).contains(&address))
+            .then(|| {
+                let new = memory[address as usize];
+                let old = if access.kind == WatchKind::Write { access.old[i as usize] } else { new };
+                WatchpointHit { pc: access.pc, address, kind: access.kind, old, new }
+            })
+    })
+}
+
+/// Returns true if [beg, beg + len) overlaps the range [range.0, range.1].
+const fn overlaps(range: (u16, u16), beg: u16, len: usize) -> bool {
+    beg <= range.1 && beg as usize + len > range.0 as usize
+}
+
+impl State {
+    /// Records the read of the `len` bytes at `address` if it overlaps the read watchpoints, and returns true if it
+    /// does. The access must be in memory.
+    ///
+    /// Must be called by the execution methods, as the accessing instruction is at PC - 2.
+    pub(crate) fn watch_read(&mut self, address: u16, len: usize) -> bool {
+        self.watch_access(WatchKind::Read, self.watch_reads, address, len)
+    }
+
+    /// Records the write of the `len` bytes at `address` and their current value if it overlaps the write
+    /// watchpoints, and returns true if it does. It must be called before writing, and the access must be in memory.
+    ///
+    /// Must be called by the execution methods, as the accessing instruction is at PC - 2.
+    pub(crate) fn watch_write(&mut self, address: u16, len: usize) -> bool {
+        self.watch_access(WatchKind::Write, self.watch_writes, address, len)
+    }
+
+    fn watch_access(&mut self, kind: WatchKind, range: (u16, u16), address: u16, len: usize) -> bool {
+        if !overlaps(range, address, len) {
+            return false;
+        }
+
+        let mut old = [0; 16];
+        if kind == WatchKind::Write {
+            old[..len].copy_from_slice(&self.memory[address as usize..address as usize + len]);
+        }
+        self.watched_access = Some(WatchedAccess { pc: self.PC.wrapping_sub(2), kind, address, len: len as u16, old });
+        true
+    }
+}
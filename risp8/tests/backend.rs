@@ -0,0 +1,92 @@
+//! Checks the core drives a custom execution backend through its hooks.
+
+use risp8::{Chip8Builder, ExecutionBackend, ExecutionMethod, State, Stats};
+
+use std::sync::{Arc, Mutex};
+
+/// 7001 1200: increments V0 in a loop.
+const PROGRAM: [u8; 4] = [0x70, 0x01, 0x12, 0x00];
+
+/// The calls received by [Recorder].
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Calls {
+    /// The budget of each step.
+    budgets: Vec<u64>,
+    invalidations: Vec<(u16, u16)>,
+    resets: u32,
+    breakpoints: Vec<(u16, bool)>,
+}
+
+/// A backend interpreting up to 4 instructions per step and recording the calls of the core.
+struct Recorder(Arc<Mutex<Calls>>);
+
+impl ExecutionBackend for Recorder {
+    fn step(&mut self, state: &mut State, budget: u64) {
+        self.0.lock().unwrap().budgets.push(budget);
+        for _ in 0..budget.min(4) {
+            state.interpret_instruction();
+        }
+    }
+
+    fn invalidate(&mut self, beg_addr: u16, end_addr: u16) {
+        self.0.lock().unwrap().invalidations.push((beg_addr, end_addr));
+    }
+
+    fn reset(&mut self) {
+        self.0.lock().unwrap().resets += 1;
+    }
+
+    fn add_breakpoint(&mut self, pc: u16) {
+        self.0.lock().unwrap().breakpoints.push((pc, true));
+    }
+
+    fn remove_breakpoint(&mut self, pc: u16) {
+        self.0.lock().unwrap().breakpoints.push((pc, false));
+    }
+
+    fn stats(&self) -> Stats {
+        Stats { blocks_executed: self.0.lock().unwrap().budgets.len() as u64, ..Stats::default() }
+    }
+}
+
+#[test]
+fn custom_backend() {
+    let mut chip8 = Chip8Builder::new(PROGRAM).instructions_per_frame(10).seed(0).build_sync().unwrap();
+    chip8.add_breakpoint(0x300);
+    assert_eq!(chip8.run_instructions(3), None);
+
+    // The breakpoints are added to the new backend.
+    let calls = Arc::new(Mutex::new(Calls::default()));
+    chip8.set_backend(Box::new(Recorder(calls.clone())));
+    assert_eq!(chip8.execution_method(), None);
+    assert_eq!(calls.lock().unwrap().breakpoints, [(0x300, true)]);
+    chip8.remove_breakpoint(0x300);
+
+    // Steps are cut on the timer ticks, every 10 instructions.
+    assert_eq!(chip8.run_instructions(20), None);
+    assert_eq!(chip8.state().v()[0], 12);
+    assert_eq!(chip8.stats(), Stats { instructions: 20, blocks_executed: 6, ..Stats::default() });
+
+    // Memory modified from outside is invalidated before the next step.
+    chip8.state_mut().unwrap().write_memory(0x201, &[0x02]).unwrap();
+    assert_eq!(chip8.run_instructions(2), None);
+    assert_eq!(chip8.state().v()[0], 14);
+
+    let saved = chip8.save_state();
+    chip8.load_state(&saved).unwrap();
+    assert_eq!(chip8.run_instructions(2), None);
+    assert_eq!(chip8.state().v()[0], 16);
+
+    let calls = calls.lock().unwrap();
+    assert_eq!(calls.budgets, [7, 3, 10, 6, 2, 3, 2, 2]);
+    assert_eq!(calls.invalidations, [(0x201, 0x201)]);
+    // Once for state_mut and once for load_state.
+    assert_eq!(calls.resets, 2);
+    assert_eq!(calls.breakpoints, [(0x300, true), (0x300, false)]);
+    drop(calls);
+
+    // Setting an execution method replaces the custom backend.
+    chip8.set_execution_method(ExecutionMethod::Jit);
+    assert_eq!(chip8.execution_method(), Some(ExecutionMethod::Jit));
+    assert_eq!(chip8.stats(), Stats::default());
+}
@@ -0,0 +1,119 @@
+//! Checks breakpoints stop every execution method on the same instruction.
+
+mod common;
+
+use common::{core, METHODS};
+
+use risp8::{Breakpoint, Expression, LogMessage, StopReason};
+
+/// 7001 1200: increments V0 in a loop.
+const PROGRAM: [u8; 4] = [0x70, 0x01, 0x12, 0x00];
+
+/// Increments V0, V1 and V2 in a loop, compiled as a single block.
+const BLOCK: [u8; 8] = [
+    0x70, 0x01, // 200: V0 += 1
+    0x71, 0x01, // 202: V1 += 1
+    0x72, 0x01, // 204: V2 += 1
+    0x12, 0x00, // 206: jump 200
+];
+
+#[test]
+fn breakpoint_in_block() {
+    for method in METHODS {
+        let mut chip8 = core(&BLOCK, method);
+        // Compile the block before setting the breakpoint in its middle.
+        assert_eq!(chip8.run_instructions(8), None);
+        assert!(chip8.add_breakpoint(0x204));
+        assert!(!chip8.add_breakpoint(0x204));
+
+        assert_eq!(chip8.run_instructions(100), Some(StopReason::Breakpoint(0x204)), "{method:?}");
+        assert_eq!(chip8.state().pc(), 0x204, "{method:?}");
+        assert_eq!(chip8.state().v()[..3], [3, 3, 2], "{method:?}");
+        assert_eq!(chip8.state().cycles(), 10, "{method:?}");
+
+        // Resuming executes the instruction at the breakpoint, then stops on the next iteration.
+        assert_eq!(chip8.run_instructions(100), Some(StopReason::Breakpoint(0x204)), "{method:?}");
+        assert_eq!(chip8.state().v()[..3], [4, 4, 3], "{method:?}");
+        assert_eq!(chip8.state().cycles(), 14, "{method:?}");
+
+        // Single instructions step past it.
+        assert_eq!(chip8.run_instructions(1), None, "{method:?}");
+        assert_eq!(chip8.state().pc(), 0x206, "{method:?}");
+        assert_eq!(chip8.state().v()[2], 4, "{method:?}");
+
+        // The whole block runs again once it is removed.
+        assert!(chip8.remove_breakpoint(0x204));
+        assert!(!chip8.remove_breakpoint(0x204));
+        assert_eq!(chip8.run_instructions(41), None, "{method:?}");
+        assert_eq!(chip8.state().v()[..3], [14, 14, 14], "{method:?}");
+    }
+}
+
+#[test]
+fn breakpoints_survive_backend_changes() {
+    for method in METHODS {
+        let mut chip8 = core(&BLOCK, METHODS[0]);
+        chip8.add_breakpoint(0x202);
+        assert_eq!(chip8.run_instructions(100), Some(StopReason::Breakpoint(0x202)));
+
+        // The new backend resumes past the breakpoint the previous one stopped on.
+        chip8.set_execution_method(method);
+        assert_eq!(chip8.run_instructions(100), Some(StopReason::Breakpoint(0x202)), "{method:?}");
+        assert_eq!(chip8.state().v()[..3], [2, 1, 1], "{method:?}");
+        assert_eq!(chip8.state().cycles(), 5, "{method:?}");
+    }
+}
+
+#[test]
+fn conditional_breakpoints() {
+    for method in METHODS {
+        let mut chip8 = core(&PROGRAM, method);
+        chip8.set_breakpoint(0x200, Breakpoint::conditional(Expression::parse("V0 == 5").unwrap()));
+
+        assert_eq!(chip8.run_instructions(1000), Some(StopReason::Breakpoint(0x200)), "{method:?}");
+        assert_eq!(chip8.state().v()[0], 5, "{method:?}");
+        assert_eq!(chip8.breakpoint(0x200).unwrap().hits, 1);
+
+        // The condition is false until V0 wraps around.
+        assert_eq!(chip8.run_instructions(500), None, "{method:?}");
+        assert_eq!(chip8.run_instructions(100), Some(StopReason::Breakpoint(0x200)), "{method:?}");
+        assert_eq!(chip8.state().cycles(), 522, "{method:?}");
+    }
+}
+
+#[test]
+fn hit_counts() {
+    for method in METHODS {
+        let mut chip8 = core(&PROGRAM, method);
+        chip8.set_breakpoint(0x202, Breakpoint { ignore_count: 3, ..Breakpoint::new() });
+
+        assert_eq!(chip8.run_instructions(1000), Some(StopReason::Breakpoint(0x202)), "{method:?}");
+        assert_eq!(chip8.state().v()[0], 4, "{method:?}");
+        assert_eq!(chip8.breakpoint(0x202).unwrap().hits, 4);
+
+        // It triggers every time once the ignored hits are over.
+        assert_eq!(chip8.run_instructions(1000), Some(StopReason::Breakpoint(0x202)), "{method:?}");
+        assert_eq!(chip8.state().v()[0], 5, "{method:?}");
+        assert_eq!(chip8.breakpoint(0x202).unwrap().hits, 5);
+    }
+}
+
+#[test]
+fn tracepoints() {
+    for method in METHODS {
+        let mut chip8 = core(&PROGRAM, method);
+        chip8.set_breakpoint(0x202, Breakpoint::tracepoint(LogMessage::parse("V0 = {V0:X}").unwrap()));
+        let mut even = Breakpoint::tracepoint(LogMessage::parse("{V0} is even").unwrap());
+        even.condition = Some(Expression::parse("V0 % 2 == 0").unwrap());
+        chip8.set_breakpoint(0x200, even);
+
+        assert_eq!(chip8.run_instructions(24), None, "{method:?}");
+        assert_eq!(chip8.state().v()[0], 12, "{method:?}");
+        let expected = [
+            "0 is even", "V0 = 1", "V0 = 2", "2 is even", "V0 = 3", "V0 = 4", "4 is even", "V0 = 5", "V0 = 6",
+            "6 is even", "V0 = 7", "V0 = 8", "8 is even", "V0 = 9", "V0 = A", "10 is even", "V0 = B", "V0 = C",
+        ];
+        assert_eq!(chip8.take_log_messages(), expected, "{method:?}");
+        assert!(chip8.take_log_messages().is_empty());
+    }
+}
@@ -0,0 +1,49 @@
+//! Checks cores built from ROMs in memory, driven through their channels.
+
+#![cfg(feature = "std")]
+
+use risp8::{Chip8, Chip8Builder, ExecutionMethod, LoadError, Risp8Answer, Risp8Command};
+
+use std::thread;
+
+/// 6007 7001 00FD: sets V0 to 8 then exits.
+const PROGRAM: [u8; 6] = [0x60, 0x07, 0x70, 0x01, 0x00, 0xFD];
+
+#[test]
+fn channels() {
+    for play in [false, true] {
+        let (mut chip8, sender, receiver) = Chip8Builder::new(PROGRAM)
+            .execution_method(ExecutionMethod::CachedInterpreter)
+            .play(play)
+            .channel_capacity(4)
+            .pacing(false)
+            .build()
+            .unwrap();
+        assert_eq!(chip8.execution_method(), Some(ExecutionMethod::CachedInterpreter));
+        let core = thread::spawn(move || chip8.run());
+
+        if play {
+            assert!(matches!(receiver.recv().unwrap(), Risp8Answer::Exited));
+        }
+        sender.send(Risp8Command::GetState).unwrap();
+        let Risp8Answer::State(state) = receiver.recv().unwrap() else {
+            panic!("unexpected answer");
+        };
+        // A paused core does not execute anything.
+        assert_eq!(state.v()[0], if play { 8 } else { 0 });
+        assert_eq!(state.cycles(), if play { 3 } else { 0 });
+
+        sender.send(Risp8Command::Exit).unwrap();
+        core.join().unwrap();
+    }
+}
+
+#[test]
+fn load_errors() {
+    let result = Chip8Builder::new(vec![0; 0xFE01]).build_sync();
+    assert!(matches!(result, Err(LoadError::ProgramTooLarge { len: 0xFE01, max_len: 0xFE00 })));
+    assert!(Chip8Builder::new(vec![0; 0xFE00]).build_sync().is_ok());
+
+    let result = Chip8::new("missing.ch8");
+    assert!(matches!(result, Err(LoadError::Io(_))));
+}
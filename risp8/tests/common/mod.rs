@@ -0,0 +1,24 @@
+//! Helpers shared by the integration tests.
+
+#![allow(dead_code)]
+
+use risp8::{Chip8, Chip8Builder, ExecutionMethod};
+
+/// Every execution method, starting with the interpreter which is the reference of the others.
+pub const METHODS: [ExecutionMethod; 5] = [
+    ExecutionMethod::Interpreter,
+    ExecutionMethod::CachedInterpreter,
+    ExecutionMethod::CachedInterpreter2,
+    ExecutionMethod::CachedInterpreter3,
+    ExecutionMethod::Jit,
+];
+
+/// Creates a synchronous core executing the given program with a fixed seed.
+pub fn core(program: &[u8], method: ExecutionMethod) -> Chip8 {
+    Chip8Builder::new(program)
+        .execution_method(method)
+        .seed(0)
+        .pacing(false)
+        .build_sync()
+        .unwrap()
+}
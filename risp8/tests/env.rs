@@ -0,0 +1,54 @@
+//! Checks the batched environment gives the same results whatever its number of threads.
+
+#![cfg(feature = "std")]
+
+use risp8::{Chip8Builder, DoneCondition, MemoryValue, VecEnvBuilder};
+
+/// Counts at 0x300 the frames where key 5 is held, drawing the count, and exits after 20 frames.
+const PROGRAM: [u8; 30] = [
+    0xA3, 0x00, // 200: I = 300
+    0x65, 0x05, // 202: V5 = 5
+    0xE5, 0xA1, // 204: skip if key V5 is not pressed
+    0x70, 0x01, // 206: V0 += 1
+    0xF0, 0x55, // 208: store V0 at 300
+    0xF0, 0x29, // 20A: I = font V0
+    0x00, 0xE0, // 20C: clear the screen
+    0xD1, 0x15, // 20E: draw
+    0x71, 0x01, // 210: V1 += 1
+    0x31, 0x14, // 212: skip if V1 == 20
+    0x12, 0x1A, // 214: jump 21A
+    0x00, 0xFD, // 216: exit
+    0x00, 0x00, // 218
+    0xA3, 0x00, // 21A: I = 300
+    0x12, 0x04, // 21C: jump 204
+];
+
+#[test]
+fn threads_are_consistent() {
+    let run = |threads| {
+        let core = Chip8Builder::new(PROGRAM).seed(0).instructions_per_frame(9);
+        let mut env = VecEnvBuilder::new(core, 7)
+            .reward(MemoryValue::U8(0x300), 0.5)
+            .done_condition(DoneCondition::Equal(MemoryValue::U8(0x300), 6))
+            .threads(threads)
+            .build()
+            .unwrap();
+
+        let mut results = Vec::new();
+        for step in 0..30u16 {
+            let actions: Vec<u16> = (0..7).map(|i| if (step + i) % 3 == 0 { 0 } else { 1 << 5 }).collect();
+            let step = env.step(&actions);
+            let step = (step.observations.to_vec(), step.rewards.to_vec(), step.dones.to_vec());
+            let counts: Vec<u8> = (0..env.len()).map(|i| env.core(i).state().memory()[0x300]).collect();
+            results.push((step, counts));
+        }
+        results
+    };
+
+    let reference = run(1);
+    assert!(reference.iter().any(|((_, _, dones), _)| dones.iter().any(|&done| done)));
+    assert!(reference.iter().any(|((_, rewards, _), _)| rewards.contains(&0.5)));
+    for threads in [2, 3, 7, 16] {
+        assert!(run(threads) == reference, "{threads} threads");
+    }
+}
@@ -0,0 +1,44 @@
+//! Checks custom font layouts on every execution method.
+
+mod common;
+
+use common::{core, METHODS};
+
+use risp8::{Font, FontError};
+
+#[test]
+fn invalid_layouts() {
+    let font = Font::new([0xAA; Font::SMALL_LEN], Font::SCHIP_BIG);
+    assert_eq!(font.with_addresses(0x1B1, 0).unwrap_err(), FontError::OutOfRange);
+    assert_eq!(font.with_addresses(0, 0x161).unwrap_err(), FontError::OutOfRange);
+    assert_eq!(font.with_addresses(0xFFFF, 0).unwrap_err(), FontError::OutOfRange);
+    assert_eq!(font.with_addresses(0, 0x4F).unwrap_err(), FontError::Overlapping);
+    assert_eq!(font.with_addresses(0xAF, 0x10).unwrap_err(), FontError::Overlapping);
+    assert_eq!(font.with_addresses(0x100, 0x100).unwrap_err(), FontError::Overlapping);
+
+    let font = font.with_addresses(0x1B0, 0x10).unwrap();
+    assert_eq!((font.small_address(), font.big_address()), (0x1B0, 0x10));
+    let font = font.with_addresses(0, 0x50).unwrap();
+    assert_eq!((font.small_address(), font.big_address()), (0, 0x50));
+}
+
+#[test]
+fn custom_layout() {
+    let program = [
+        0x60, 0x0B, // 200: V0 = B
+        0xF0, 0x29, // 202: I = small font V0
+        0xF0, 0x30, // 204: I = big font V0
+        0x12, 0x06, // 206: loop
+    ];
+    let font = Font::new([0xAA; Font::SMALL_LEN], [0x55; Font::BIG_LEN]).with_addresses(0x100, 0x20).unwrap();
+
+    for method in METHODS {
+        let mut chip8 = core(&program, method);
+        chip8.set_font(&font);
+        assert_eq!(chip8.run_instructions(2), None);
+        assert_eq!(chip8.state().i(), 0x100 + 11 * 5, "{method:?}");
+        assert_eq!(chip8.run_instructions(10), None);
+        assert_eq!(chip8.state().font(), font, "{method:?}");
+        assert_eq!(chip8.state().i(), 0x20 + 11 * 10, "{method:?}");
+    }
+}
@@ -0,0 +1,62 @@
+//! Checks the frames pushed through the channel according to the frame delivery setting.
+
+#![cfg(feature = "std")]
+
+use risp8::{Chip8Builder, FrameDelivery, Risp8Answer, Risp8Command, Screen};
+
+use std::thread;
+
+/// Draws the 0 of the font in the first frame, then loops without drawing and exits at the end of the 31st frame.
+const PROGRAM: [u8; 12] = [
+    0x00, 0xE0, // 200: clear the screen
+    0xD0, 0x05, // 202: draw 5 lines at (0, 0)
+    0x71, 0x01, // 204: V1 += 1
+    0x31, 0x14, // 206: skip if V1 == 20
+    0x12, 0x04, // 208: jump 204
+    0x00, 0xFD, // 20A: exit
+];
+
+/// Runs the program and returns the frames received until it exits.
+fn frames(frame_delivery: FrameDelivery) -> Vec<Screen> {
+    let (mut chip8, sender, receiver) = Chip8Builder::new(PROGRAM)
+        .instructions_per_frame(2)
+        .frame_delivery(frame_delivery)
+        .pacing(false)
+        .play(true)
+        .build()
+        .unwrap();
+    let core = thread::spawn(move || {
+        chip8.run();
+        chip8
+    });
+
+    let mut frames = Vec::new();
+    loop {
+        match receiver.recv().unwrap() {
+            Risp8Answer::Frame(screen) => frames.push(screen),
+            Risp8Answer::Exited => break,
+            _ => (),
+        }
+    }
+
+    sender.send(Risp8Command::Exit).unwrap();
+    let chip8 = core.join().unwrap();
+    assert_eq!(chip8.state().cycles(), 62);
+    // The screen is not dirty anymore once it has been sent.
+    assert_eq!(chip8.is_screen_dirty(), frame_delivery == FrameDelivery::Never);
+    frames
+}
+
+#[test]
+fn frame_delivery() {
+    assert!(frames(FrameDelivery::Never).is_empty());
+
+    let every = frames(FrameDelivery::EveryFrame);
+    assert_eq!(every.len(), 31);
+    assert!(every.windows(2).all(|w| w[0] == w[1]));
+    // The top-left pixel of the 0.
+    assert_eq!(every[0].pixel(0, 0), 1);
+
+    let dirty = frames(FrameDelivery::WhenDirty);
+    assert_eq!(dirty, [every[0]]);
+}
@@ -0,0 +1,172 @@
+//! Checks movies replay their runs exactly, are re-recorded and detect desynchronizations.
+
+mod common;
+
+use common::{core, METHODS};
+
+use risp8::{Chip8, Font, Movie, MovieInput, MovieMode, Quirks, StopReason};
+
+/// Counts the instructions while key 0 is held and draws random numbers in a loop.
+const PROGRAM: [u8; 14] = [
+    0x61, 0x00, // 200: V1 = 0
+    0xE1, 0x9E, // 202: skip if key V1 is pressed
+    0x12, 0x08, // 204: jump 208
+    0x72, 0x01, // 206: V2 += 1
+    0xC0, 0xFF, // 208: V0 = random
+    0x73, 0x01, // 20A: V3 += 1
+    0x12, 0x02, // 20C: jump 202
+];
+
+/// Records a movie pressing key 0 twice, stopping after `frames` frames.
+fn record(chip8: &mut Chip8, frames: u64) -> Movie {
+    let frame = chip8.instructions_per_frame() as u64;
+    chip8.record_movie(42).unwrap();
+    for (cycle, pressed) in [(frame * 10 + 3, true), (frame * 50 + 1, false), (frame * 70, true), (frame * 90 + 7, false)] {
+        assert_eq!(chip8.run_instructions(cycle - chip8.state().cycles()), None);
+        chip8.set_key(0, pressed);
+    }
+    assert_eq!(chip8.run_instructions(frame * frames - chip8.state().cycles()), None);
+    chip8.stop_movie().unwrap()
+}
+
+#[test]
+fn round_trip() {
+    let mut reference = None;
+    for method in METHODS {
+        let mut chip8 = core(&PROGRAM, method);
+        let movie = record(&mut chip8, 180);
+        let expected = chip8.save_state();
+        assert_eq!(movie.inputs.len(), 4);
+        assert_eq!(movie.checksums.len(), 3);
+        assert_eq!(movie.seed, 42);
+        assert_eq!(Movie::load(&movie.save()).unwrap(), movie);
+
+        let mut other = core(&PROGRAM, method);
+        other.set_seed(7);
+        other.play_movie(movie.clone(), true).unwrap();
+        assert_eq!(other.run_instructions(movie.checksums[0].cycle), None, "{method:?}");
+        // Read-only movies ignore the inputs of the user.
+        other.set_key(5, true);
+        assert!(!other.state().keys()[5]);
+        assert_eq!(other.movie().unwrap().inputs, movie.inputs);
+
+        assert_eq!(other.run_instructions(movie.checksums[2].cycle - movie.checksums[0].cycle), None, "{method:?}");
+        assert_eq!(other.movie_mode(), Some(MovieMode::Finished));
+        assert!(other.save_state() == expected, "{method:?} replayed differently");
+
+        match &reference {
+            None => reference = Some(movie),
+            Some(reference) => assert_eq!(&movie, reference, "{method:?}"),
+        }
+    }
+}
+
+#[test]
+fn rerecord() {
+    for method in METHODS {
+        let mut chip8 = core(&PROGRAM, method);
+        let movie = record(&mut chip8, 200);
+        let frame = chip8.instructions_per_frame() as u64;
+
+        // An input of the user while playing keeps the inputs already replayed.
+        chip8.play_movie(movie.clone(), false).unwrap();
+        assert_eq!(chip8.run_instructions(frame * 60), None);
+        chip8.set_key(3, true);
+        assert_eq!(chip8.movie_mode(), Some(MovieMode::Recording));
+        let rerecorded = chip8.movie().unwrap();
+        assert_eq!(rerecorded.rerecords, 1);
+        assert_eq!(rerecorded.inputs[..2], movie.inputs[..2]);
+        assert_eq!(rerecorded.inputs[2], MovieInput { cycle: frame * 60, key: 3, pressed: true });
+        assert_eq!(rerecorded.inputs.len(), 3);
+        assert_eq!(rerecorded.checksums, movie.checksums[..1]);
+
+        // Loading a state while recording deletes the inputs from its instruction on, which it has been saved before.
+        let mut chip8 = core(&PROGRAM, method);
+        chip8.record_movie(42).unwrap();
+        assert_eq!(chip8.run_instructions(frame * 20), None);
+        chip8.set_key(2, true);
+        assert_eq!(chip8.run_instructions(frame * 10), None);
+        let saved = chip8.save_state();
+        chip8.set_key(1, true);
+        assert_eq!(chip8.run_instructions(frame * 40), None);
+        chip8.set_key(1, false);
+        assert_eq!(chip8.movie().unwrap().checksums.len(), 1);
+        chip8.load_state(&saved).unwrap();
+        let rerecorded = chip8.movie().unwrap();
+        assert_eq!(rerecorded.rerecords, 1);
+        assert_eq!(rerecorded.inputs, [MovieInput { cycle: frame * 20, key: 2, pressed: true }]);
+        assert!(rerecorded.checksums.is_empty(), "{method:?}");
+    }
+}
+
+#[test]
+fn desync() {
+    for method in METHODS {
+        let mut chip8 = core(&PROGRAM, method);
+        let movie = record(&mut chip8, 180);
+        let end = movie.checksums[2].cycle;
+        // The last input is before the second checksum, which must still be verified.
+        assert!(movie.inputs[3].cycle < movie.checksums[1].cycle);
+
+        for i in 0..movie.checksums.len() {
+            let mut tampered = movie.clone();
+            tampered.checksums[i].checksum ^= 1;
+            let cycle = tampered.checksums[i].cycle;
+
+            chip8.play_movie(tampered, true).unwrap();
+            assert_eq!(chip8.run_instructions(end), Some(StopReason::MovieDesync { cycle }), "{method:?} checksum {i}");
+            assert_eq!(chip8.state().cycles(), cycle);
+        }
+    }
+}
+
+#[test]
+fn instructions_per_frame_is_fixed() {
+    let mut chip8 = core(&PROGRAM, METHODS[0]);
+    let movie = record(&mut chip8, 100);
+    let frame = movie.instructions_per_frame;
+
+    chip8.record_movie(42).unwrap();
+    chip8.set_instructions_per_frame(frame + 1);
+    assert_eq!(chip8.instructions_per_frame(), frame);
+    chip8.stop_movie();
+
+    chip8.set_instructions_per_frame(frame + 1);
+    chip8.play_movie(movie, true).unwrap();
+    assert_eq!(chip8.instructions_per_frame(), frame);
+    chip8.set_instructions_per_frame(frame + 1);
+    assert_eq!(chip8.instructions_per_frame(), frame);
+}
+
+#[test]
+fn state_changes_are_ignored() {
+    for method in METHODS {
+        let mut chip8 = core(&PROGRAM, method);
+        let frame = chip8.instructions_per_frame() as u64;
+        chip8.record_movie(42).unwrap();
+        assert_eq!(chip8.run_instructions(frame * 30), None);
+
+        // None of these are stored in the movie, so they would desync its playback.
+        let quirks = chip8.state().quirks();
+        chip8.set_seed(7);
+        chip8.set_quirks(Quirks { vf_reset: !quirks.vf_reset, ..quirks });
+        chip8.set_font(&Font::VIP);
+        assert!(chip8.state_mut().is_none());
+        assert_eq!(chip8.state().quirks(), quirks);
+        assert_eq!(chip8.state().memory()[..Font::SMALL_LEN], Font::CHIP48.small()[..]);
+
+        chip8.set_key(0, true);
+        assert_eq!(chip8.run_instructions(frame * 100), None);
+        let movie = chip8.stop_movie().unwrap();
+        let expected = chip8.save_state();
+
+        // Once the movie is stopped they apply again.
+        chip8.set_seed(7);
+        assert!(chip8.state_mut().is_some());
+
+        chip8.play_movie(movie, true).unwrap();
+        assert_eq!(chip8.run_instructions(frame * 130), None, "{method:?}");
+        assert_eq!(chip8.movie_mode(), Some(MovieMode::Finished));
+        assert!(chip8.save_state() == expected, "{method:?} replayed differently");
+    }
+}
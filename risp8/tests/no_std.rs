@@ -0,0 +1,53 @@
+//! Checks the core without the `std` feature, run with `cargo test --no-default-features`.
+
+#![cfg(not(feature = "std"))]
+
+mod common;
+
+use common::METHODS;
+
+use risp8::{Chip8Builder, ExecutionMethod, Stats};
+
+/// Stores random numbers at 300, 301, ... in a loop.
+const PROGRAM: [u8; 8] = [
+    0xA3, 0x00, // 200: I = 300
+    0xC0, 0xFF, // 202: V0 = random
+    0xF0, 0x55, // 204: store V0 at I, I += 1
+    0x12, 0x02, // 206: jump 202
+];
+
+fn now() -> u64 {
+    42
+}
+
+#[test]
+fn seed_from_time_source() {
+    let mut seeded = Chip8Builder::new(PROGRAM).seed(42).build_sync().unwrap();
+    let mut timed = Chip8Builder::new(PROGRAM).time_source(now).build_sync().unwrap();
+    let mut unseeded = Chip8Builder::new(PROGRAM).build_sync().unwrap();
+    let mut zero = Chip8Builder::new(PROGRAM).seed(0).build_sync().unwrap();
+
+    for chip8 in [&mut seeded, &mut timed, &mut unseeded, &mut zero] {
+        assert_eq!(chip8.run_frame(), None);
+    }
+    assert_eq!(timed.state().memory()[0x300..0x310], seeded.state().memory()[0x300..0x310]);
+    assert_eq!(unseeded.state().memory()[0x300..0x310], zero.state().memory()[0x300..0x310]);
+    assert_ne!(seeded.state().memory()[0x300..0x310], zero.state().memory()[0x300..0x310]);
+}
+
+#[test]
+fn jit_falls_back_to_the_interpreter() {
+    for method in METHODS {
+        let mut chip8 = Chip8Builder::new(PROGRAM).execution_method(method).seed(0).build_sync().unwrap();
+        assert_eq!(chip8.run_instructions(100), None);
+
+        let stats = chip8.stats();
+        assert_eq!(stats.instructions, 100);
+        assert_eq!(stats.blocks_compiled == 0, matches!(method, ExecutionMethod::Interpreter | ExecutionMethod::Jit), "{method:?}");
+    }
+
+    // Without a time source frames are never paced.
+    let mut chip8 = Chip8Builder::new(PROGRAM).pacing(true).build_sync().unwrap();
+    assert_eq!(chip8.next_frame_delay(), 0);
+    assert_eq!(chip8.stats(), Stats::default());
+}
@@ -0,0 +1,69 @@
+//! Checks the platform presets configure the core in one step.
+
+mod common;
+
+use common::METHODS;
+
+use risp8::{Chip8Builder, Font, LoadError, Platform};
+
+/// 7001 1x02: increments V0 in a loop, at the load address x00 of the platform.
+fn program(load_address: u16) -> [u8; 4] {
+    let [hi, lo] = (0x1000 | load_address).to_be_bytes();
+    [0x70, 0x01, hi, lo]
+}
+
+#[test]
+fn presets() {
+    for platform in Platform::ALL {
+        assert_eq!(Platform::from_name(&platform.name().to_uppercase()), Some(platform));
+
+        let load_address = platform.load_address();
+        for method in METHODS {
+            let mut chip8 = Chip8Builder::new(program(load_address))
+                .platform(platform)
+                .execution_method(method)
+                .seed(0)
+                .build_sync()
+                .unwrap();
+
+            let state = chip8.state();
+            assert_eq!(state.pc(), load_address, "{platform:?}");
+            assert_eq!(state.memory_size(), platform.memory_size(), "{platform:?}");
+            assert_eq!(state.quirks(), platform.quirks(), "{platform:?}");
+            assert_eq!(state.font(), platform.font(), "{platform:?}");
+            assert_eq!(chip8.instructions_per_frame(), platform.instructions_per_frame(), "{platform:?}");
+
+            assert_eq!(chip8.run_instructions(100), None);
+            assert_eq!(chip8.state().v()[0], 50, "{platform:?} {method:?}");
+        }
+    }
+
+    assert_eq!(Platform::from_name("chip-9"), None);
+    assert_eq!(Platform::Eti660.load_address(), 0x600);
+    assert_eq!(Platform::XoChip.memory_size(), 0x10000);
+    assert_eq!(Platform::CosmacVip.font(), Font::VIP);
+}
+
+#[test]
+fn set_platform() {
+    let mut chip8 = Chip8Builder::new(program(0x200)).seed(0).build_sync().unwrap();
+    assert_eq!(chip8.run_instructions(10), None);
+
+    // The program is restarted with the configuration of the platform.
+    chip8.set_platform(Platform::Schip).unwrap();
+    assert_eq!(chip8.state().cycles(), 0);
+    assert_eq!(chip8.state().v()[0], 0);
+    assert_eq!(chip8.state().memory_size(), 0x1000);
+    assert_eq!(chip8.instructions_per_frame(), 30);
+
+    // A program too large for the memory of the platform leaves the state unchanged.
+    let mut rom = vec![0; 0x1000];
+    rom[..4].copy_from_slice(&program(0x200));
+    let mut chip8 = Chip8Builder::new(rom).platform(Platform::XoChip).seed(0).build_sync().unwrap();
+    assert_eq!(chip8.run_instructions(10), None);
+    let result = chip8.set_platform(Platform::CosmacVip);
+    assert!(matches!(result, Err(LoadError::ProgramTooLarge { len: 0x1000, max_len: 0xE00 })), "{result:?}");
+    assert_eq!(chip8.state().v()[0], 5);
+    assert_eq!(chip8.state().memory_size(), 0x10000);
+    assert_eq!(chip8.state().quirks(), Platform::XoChip.quirks());
+}
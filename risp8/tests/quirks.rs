@@ -0,0 +1,185 @@
+//! Checks every execution method behaves the same under every combination of quirks.
+
+mod common;
+
+use common::{core, METHODS};
+
+use risp8::{Platform, Quirks};
+
+/// Returns the 64 combinations of quirks.
+fn all_quirks() -> impl Iterator<Item = Quirks> {
+    (0..64).map(|i| Quirks {
+        vf_reset: i & 1 != 0,
+        shift_vx: i & 2 != 0,
+        increment_i: i & 4 != 0,
+        wrap_sprites: i & 8 != 0,
+        jump_vx: i & 16 != 0,
+        lores_big_sprites: i & 32 != 0,
+    })
+}
+
+/// Runs the program with every method and quirks, and checks the resulting states are the same as the interpreter's.
+fn check(program: &[u8], count: u64) {
+    for quirks in all_quirks() {
+        let mut reference = None;
+        for method in METHODS {
+            let mut chip8 = core(program, method);
+            chip8.set_quirks(quirks);
+            let reason = chip8.run_instructions(count);
+            let result = (reason, chip8.save_state());
+
+            match &reference {
+                None => reference = Some(result),
+                Some(reference) => assert!(*reference == result, "{method:?} differs with {quirks:?}"),
+            }
+        }
+    }
+}
+
+#[test]
+fn chip8_quirks() {
+    let mut program = vec![
+        0x60, 0x81, // 200: V0 = 81
+        0x61, 0x03, // 202: V1 = 03
+        0x6F, 0x05, // 204: VF = 05
+        0x82, 0x00, // 206: V2 = V0
+        0x82, 0x11, // 208: V2 |= V1
+        0x63, 0x0F, // 20A: V3 = 0F
+        0x6F, 0x07, // 20C: VF = 07
+        0x83, 0x12, // 20E: V3 &= V1
+        0x84, 0x06, // 210: V4 = V0 >> 1
+        0x85, 0x0E, // 212: V5 = V0 << 1
+        0x86, 0x16, // 214: V6 = V1 >> 1
+        0x87, 0x1E, // 216: V7 = V1 << 1
+        0xA3, 0x00, // 218: I = 300
+        0xF7, 0x55, // 21A: store V0-V7
+        0xF2, 0x65, // 21C: load V0-V2
+        0x60, 0x3C, // 21E: V0 = 60
+        0x61, 0x1E, // 220: V1 = 30
+        0xF8, 0x29, // 222: I = font V8
+        0xD0, 0x15, // 224: draw across the bottom-right corner
+        0x62, 0x02, // 226: V2 = 02
+        0xB2, 0x30, // 228: jump 230 + V0 or V2
+    ];
+    program.resize(0x100, 0);
+    program[0x32..0x34].copy_from_slice(&[0x12, 0x32]); // 232: loop
+    program[0x6C..0x6E].copy_from_slice(&[0x12, 0x6C]); // 26C: loop
+
+    check(&program, 200);
+}
+
+#[test]
+fn schip_quirks() {
+    let mut program = vec![
+        0x00, 0xFF, // 200: high resolution
+        0x60, 0x07, // 202: V0 = 07
+        0xF0, 0x30, // 204: I = big font V0
+        0x61, 0x78, // 206: V1 = 120
+        0x62, 0x3A, // 208: V2 = 58
+        0xD1, 0x2A, // 20A: draw the big digit across the edge
+        0xA2, 0x80, // 20C: I = 280
+        0x61, 0x10, // 20E: V1 = 16
+        0xD1, 0x20, // 210: draw a 16x16 sprite across the bottom edge
+        0x00, 0xC3, // 212: scroll down 3
+        0x00, 0xFB, // 214: scroll right
+        0xD1, 0x20, // 216: draw a 16x16 sprite
+        0x00, 0xFC, // 218: scroll left
+        0x63, 0x55, // 21A: V3 = 55
+        0xF3, 0x75, // 21C: save V0-V3 to the flags
+        0x63, 0x00, // 21E: V3 = 0
+        0xF3, 0x85, // 220: load V0-V3 from the flags
+        0x00, 0xFE, // 222: low resolution
+        0xD1, 0x20, // 224: draw a 16 lines sprite across the bottom edge
+        0x00, 0xFD, // 226: exit
+    ];
+    program.resize(0x100, 0);
+    for (i, byte) in program[0x80..0xA0].iter_mut().enumerate() {
+        *byte = (i as u8).wrapping_mul(37) | 0x81;
+    }
+
+    check(&program, 100);
+}
+
+#[test]
+fn xochip_quirks() {
+    let mut program = vec![
+        0x60, 0x01, // 200: V0 = 1
+        0x61, 0x02, // 202: V1 = 2
+        0x62, 0x03, // 204: V2 = 3
+        0x30, 0x01, // 206: skip the next double-width instruction
+        0xF0, 0x00, 0x12, 0x34, // 208: I = 1234
+        0xF0, 0x00, 0x03, 0x00, // 20C: I = 300
+        0x52, 0x02, // 210: save V2-V0
+        0x50, 0x22, // 212: save V0-V2
+        0x52, 0x02, // 214: save V2-V0
+        0x55, 0x33, // 216: load V5-V3
+        0x53, 0x53, // 218: load V3-V5
+        0xF3, 0x01, // 21A: select both planes
+        0xA2, 0x80, // 21C: I = 280
+        0x64, 0x05, // 21E: V4 = 5
+        0x65, 0x06, // 220: V5 = 6
+        0xD4, 0x54, // 222: draw 4 lines on both planes
+        0xF2, 0x01, // 224: select the second plane
+        0x00, 0xE0, // 226: clear it
+        0xF3, 0x01, // 228: select both planes
+        0x00, 0xD2, // 22A: scroll up 2
+        0xF1, 0x01, // 22C: select the first plane
+        0xD4, 0x53, // 22E: draw 3 lines
+        0xA1, 0x00, // 230: I = 100
+        0x60, 0x77, // 232: V0 = 77
+        0xF0, 0x55, // 234: store V0 in low memory
+        0xA2, 0x80, // 236: I = 280
+        0xF0, 0x02, // 238: load the audio pattern
+        0xF1, 0x3A, // 23A: set the pitch
+        0xF0, 0x00, 0x02, 0x4C, // 23C: I = 24C
+        0x60, 0x60, // 240: V0 = 60
+        0xF0, 0x55, // 242: store V0 at 24C, making 6AAA into 60AA
+        0x30, 0x00, // 244: do not skip
+        0xF0, 0x00, 0x12, 0x34, // 246: I = 1234
+        0x12, 0x4C, // 24A: jump 24C
+        0x6A, 0xAA, // 24C: VA = AA, modified above
+        0x12, 0x4E, // 24E: loop
+    ];
+    program.resize(0x100, 0);
+    for (i, byte) in program[0x80..0x90].iter_mut().enumerate() {
+        *byte = (i as u8).wrapping_mul(91) | 0x11;
+    }
+
+    check(&program, 100);
+}
+
+#[test]
+fn lores_big_sprites() {
+    let mut program = vec![
+        0xA2, 0x80, // 200: I = 280
+        0xD0, 0x00, // 202: draw a 16 lines sprite at (0, 0)
+        0x00, 0xFF, // 204: high resolution
+        0xD0, 0x00, // 206: draw a 16 lines sprite at (0, 0)
+        0x12, 0x08, // 208: loop
+    ];
+    program.resize(0x100, 0);
+    program[0x80..0xA0].fill(0xFF);
+
+    for method in METHODS {
+        // SUPER-CHIP 1.1 draws 8x16 sprites in lores.
+        let mut chip8 = core(&program, method);
+        chip8.set_quirks(Platform::Schip.quirks());
+        assert_eq!(chip8.run_instructions(2), None);
+        let lit = chip8.screen().rows().flatten().filter(|&&pixel| pixel != 0).count();
+        assert_eq!(lit, 8 * 16, "{method:?}");
+        assert_eq!(chip8.screen().pixel(7, 15), 1, "{method:?}");
+        assert_eq!(chip8.screen().pixel(8, 0), 0, "{method:?}");
+
+        // They are always 16x16 in hires.
+        assert_eq!(chip8.run_instructions(2), None);
+        let lit = chip8.screen().rows().flatten().filter(|&&pixel| pixel != 0).count();
+        assert_eq!(lit, 16 * 16, "{method:?}");
+
+        // XO-CHIP draws 16x16 sprites in lores.
+        let mut chip8 = core(&program, method);
+        chip8.set_quirks(Platform::XoChip.quirks());
+        assert_eq!(chip8.run_instructions(2), None);
+        let lit = chip8.screen().rows().flatten().filter(|&&pixel| pixel != 0).count();
+        assert_eq!(lit, 16 * 16, "{method:?}");
+    }
+}
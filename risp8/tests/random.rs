@@ -0,0 +1,72 @@
+//! Checks the random numbers of Cxkk are reproducible from their seed on every execution method.
+
+mod common;
+
+use common::METHODS;
+
+use risp8::{Chip8, Chip8Builder, ExecutionMethod};
+
+/// Stores random numbers from 300 onwards, alternating full bytes and masked ones.
+const PROGRAM: [u8; 10] = [
+    0xA3, 0x00, // 200: I = 300
+    0xC0, 0xFF, // 202: V0 = random
+    0xC1, 0x0F, // 204: V1 = random & 0F
+    0xF1, 0x55, // 206: store V0-V1 at I, I += 2
+    0x12, 0x02, // 208: jump 202
+];
+
+fn build(method: ExecutionMethod, seed: u64) -> Chip8 {
+    Chip8Builder::new(PROGRAM).execution_method(method).seed(seed).build_sync().unwrap()
+}
+
+/// Returns the 64 random numbers stored by the program.
+fn numbers(chip8: &Chip8) -> Vec<u8> {
+    chip8.state().memory()[0x300..0x340].to_vec()
+}
+
+#[test]
+fn same_sequence_for_same_seed() {
+    let mut reference = None;
+    for method in METHODS {
+        let mut chip8 = build(method, 1234);
+        assert_eq!(chip8.run_instructions(1 + 32 * 4), None);
+        let numbers = numbers(&chip8);
+        assert!(numbers.chunks(2).all(|pair| pair[1] <= 0x0F), "{method:?}");
+
+        match &reference {
+            None => reference = Some(numbers),
+            Some(reference) => assert_eq!(&numbers, reference, "{method:?}"),
+        }
+    }
+
+    let reference = reference.unwrap();
+    assert!(reference.iter().step_by(2).any(|&n| n > 0x0F));
+
+    // A different seed gives a different sequence, and reseeding restarts it.
+    let mut chip8 = build(ExecutionMethod::Jit, 1235);
+    assert_eq!(chip8.run_instructions(1 + 32 * 4), None);
+    assert_ne!(numbers(&chip8), reference);
+
+    chip8.set_seed(1234);
+    chip8.state_mut().unwrap().set_i(0x300);
+    chip8.state_mut().unwrap().set_pc(0x202).unwrap();
+    assert_eq!(chip8.run_instructions(32 * 4), None);
+    assert_eq!(numbers(&chip8), reference);
+}
+
+#[test]
+fn save_states_keep_the_generator() {
+    for method in METHODS {
+        let mut chip8 = build(method, 99);
+        assert_eq!(chip8.run_instructions(1 + 16 * 4), None);
+        let saved = chip8.save_state();
+        assert_eq!(chip8.run_instructions(16 * 4), None);
+        let expected = numbers(&chip8);
+
+        // Another seed is replaced by the one of the save state.
+        let mut other = build(method, 100);
+        other.load_state(&saved).unwrap();
+        assert_eq!(other.run_instructions(16 * 4), None);
+        assert_eq!(numbers(&other), expected, "{method:?}");
+    }
+}
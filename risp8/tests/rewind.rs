@@ -0,0 +1,52 @@
+//! Checks the rewind snapshots taken by the run loop.
+
+#![cfg(feature = "std")]
+
+use risp8::{Chip8Builder, Receiver, Risp8Answer, Risp8Command, Sender, State};
+
+use std::thread;
+
+/// Increments V0 once per 3-instruction frame, and exits on the 100th frame.
+const PROGRAM: [u8; 8] = [
+    0x70, 0x01, // 200: V0 += 1
+    0x30, 0x64, // 202: skip if V0 == 100
+    0x12, 0x00, // 204: jump 200
+    0x00, 0xFD, // 206: exit
+];
+
+fn state(sender: &Sender<Risp8Command>, receiver: &Receiver<Risp8Answer>) -> Box<State> {
+    sender.send(Risp8Command::GetState).unwrap();
+    match receiver.recv().unwrap() {
+        Risp8Answer::State(state) => state,
+        answer => panic!("unexpected answer {answer:?}"),
+    }
+}
+
+#[test]
+fn rewind_frames() {
+    for (capacity, interval, rewinds) in [(20, 1, [(10, 90), (30, 81), (1, 81)]), (8, 5, [(7, 90), (5, 85), (100, 65)])] {
+        let (mut chip8, sender, receiver) = Chip8Builder::new(PROGRAM)
+            .instructions_per_frame(3)
+            .rewind(capacity, interval)
+            .pacing(false)
+            .play(true)
+            .build()
+            .unwrap();
+        let core = thread::spawn(move || chip8.run());
+        // Sound answers are sent every frame until the program exits.
+        while !matches!(receiver.recv().unwrap(), Risp8Answer::Exited) {}
+        assert_eq!(state(&sender, &receiver).v()[0], 100);
+
+        // Each rewind restores the snapshot taken at the end of the frame the given number of frames ago, or the
+        // oldest one.
+        for (frames, v0) in rewinds {
+            sender.send(Risp8Command::Rewind(frames)).unwrap();
+            let state = state(&sender, &receiver);
+            assert_eq!(state.v()[0], v0, "{capacity} snapshots every {interval} frames, rewind {frames}");
+            assert_eq!(state.cycles(), v0 as u64 * 3);
+        }
+
+        sender.send(Risp8Command::Exit).unwrap();
+        core.join().unwrap();
+    }
+}
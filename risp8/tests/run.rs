@@ -0,0 +1,25 @@
+//! Checks the synchronous execution API.
+
+mod common;
+
+use common::{core, METHODS};
+
+use risp8::StopReason;
+
+/// Increments V0 until it reaches 0x10, then exits.
+const PROGRAM: [u8; 8] = [
+    0x70, 0x01, // 200: V0 += 1
+    0x30, 0x10, // 202: skip if V0 == 10
+    0x12, 0x00, // 204: jump 200
+    0x00, 0xFD, // 206: exit
+];
+
+#[test]
+fn run_instructions_without_limit() {
+    for method in METHODS {
+        let mut chip8 = core(&PROGRAM, method);
+        assert_eq!(chip8.run_instructions(1), None);
+        assert_eq!(chip8.run_instructions(u64::MAX), Some(StopReason::Exited), "{method:?}");
+        assert_eq!(chip8.state().cycles(), 48, "{method:?}");
+    }
+}
@@ -0,0 +1,91 @@
+//! Checks save states restore the execution on every method and reject invalid data.
+
+mod common;
+
+use common::{core, METHODS};
+
+use risp8::{SaveStateError, State};
+
+/// Increments V0 in a loop until it reaches 0x10, then patches the loop to add 2 instead of 1 and restarts it.
+const PROGRAM: [u8; 16] = [
+    0x70, 0x01, // 200: V0 += 1
+    0x30, 0x10, // 202: skip if V0 == 10
+    0x12, 0x00, // 204: jump 200
+    0xA2, 0x01, // 206: I = 201
+    0x60, 0x02, // 208: V0 = 2
+    0xF0, 0x55, // 20A: store V0 at 201
+    0x60, 0x00, // 20C: V0 = 0
+    0x12, 0x00, // 20E: jump 200
+];
+
+#[test]
+fn round_trip() {
+    let mut reference = None;
+    for method in METHODS {
+        let mut chip8 = core(&PROGRAM, method);
+        assert_eq!(chip8.run_instructions(10), None);
+        let saved = chip8.save_state();
+        assert_eq!(State::load(&saved).unwrap().save(), saved);
+
+        assert_eq!(chip8.run_instructions(100), None);
+        let expected = chip8.save_state();
+        assert_eq!(chip8.state().memory()[0x201], 0x02, "{method:?}");
+
+        // The loop compiled with the patch must not be executed after restoring the original code.
+        chip8.load_state(&saved).unwrap();
+        assert_eq!(chip8.save_state(), saved, "{method:?}");
+        assert_eq!(chip8.run_instructions(100), None);
+        assert!(chip8.save_state() == expected, "{method:?} resumed differently");
+
+        match &reference {
+            None => reference = Some(expected),
+            Some(reference) => assert!(*reference == expected, "{method:?} differs from the interpreter"),
+        }
+    }
+}
+
+/// Returns the error of loading the given save state with `bytes` written at `offset`.
+fn load_modified(data: &[u8], offset: usize, bytes: &[u8]) -> SaveStateError {
+    let mut data = data.to_vec();
+    data[offset..offset + bytes.len()].copy_from_slice(bytes);
+    State::load(&data).unwrap_err()
+}
+
+#[test]
+fn invalid_data() {
+    let mut chip8 = core(&PROGRAM, METHODS[0]);
+    assert_eq!(chip8.run_instructions(10), None);
+    let data = chip8.save_state();
+    let len = data.len();
+
+    assert_eq!(load_modified(&data, 0, b"RSP9"), SaveStateError::InvalidFormat);
+    assert_eq!(load_modified(&data, 4, &2u16.to_le_bytes()), SaveStateError::UnsupportedVersion(2));
+    assert_eq!(load_modified(&data, 4, &0u16.to_le_bytes()), SaveStateError::UnsupportedVersion(0));
+
+    for end in 0..len {
+        assert_eq!(State::load(&data[..end]).unwrap_err(), SaveStateError::Truncated, "{end} bytes");
+    }
+    assert_eq!(State::load(&[data.as_slice(), &[0]].concat()).unwrap_err(), SaveStateError::Corrupted);
+
+    let screen = 63 + chip8.state().memory_size() + 2;
+    let cases: [(&str, usize, &[u8]); 11] = [
+        ("SP", 6, &[17]),
+        ("memory size", 59, &0x1FFu32.to_le_bytes()),
+        ("memory size", 59, &0x10001u32.to_le_bytes()),
+        ("high resolution", screen, &[2]),
+        ("first pixel", screen + 1, &[4]),
+        ("last pixel", screen + 128 * 64, &[0x80]),
+        ("bitplanes", len - 45, &[4]),
+        ("small font address", len - 27, &0x1C0u16.to_le_bytes()),
+        ("big font address", len - 25, &0x1B0u16.to_le_bytes()),
+        ("RNG state", len - 23, &0u64.to_le_bytes()),
+        ("wait key", len - 15, &[2, 0x10]),
+    ];
+    for (field, offset, bytes) in cases {
+        assert_eq!(load_modified(&data, offset, bytes), SaveStateError::Corrupted, "{field}");
+    }
+
+    // The state is unchanged when loading fails.
+    assert_eq!(chip8.load_state(&data[..len - 1]), Err(SaveStateError::Truncated));
+    assert_eq!(chip8.save_state(), data);
+}
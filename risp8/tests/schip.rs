@@ -0,0 +1,67 @@
+//! Checks the SUPER-CHIP 1.1 instructions on every execution method.
+
+mod common;
+
+use common::{core, METHODS};
+
+use risp8::{Chip8, Font, Screen, StopReason};
+
+/// Draws the big 8 in hires, scrolls it, saves and restores the RPL flags, then goes back to lores and exits.
+const PROGRAM: [u8; 34] = [
+    0x00, 0xFF, // 200: high resolution
+    0x60, 0x00, // 202: V0 = 0
+    0x61, 0x00, // 204: V1 = 0
+    0x62, 0x08, // 206: V2 = 8
+    0xF2, 0x30, // 208: I = big font V2
+    0xD0, 0x1A, // 20A: draw 10 lines at (0, 0)
+    0x00, 0xC4, // 20C: scroll down 4
+    0x00, 0xFB, // 20E: scroll right 4
+    0x00, 0xFC, // 210: scroll left 4
+    0x63, 0x55, // 212: V3 = 55
+    0x64, 0x66, // 214: V4 = 66
+    0xF4, 0x75, // 216: save V0-V4 to the flags
+    0x63, 0x00, // 218: V3 = 0
+    0x64, 0x00, // 21A: V4 = 0
+    0xF4, 0x85, // 21C: load V0-V4 from the flags
+    0x00, 0xFE, // 21E: low resolution
+    0x00, 0xFD, // 220: exit
+];
+
+/// Returns true if the screen only shows the big 8 with its top-left corner at (0, y).
+fn shows_big_eight(chip8: &Chip8, y: usize) -> bool {
+    let screen = chip8.screen();
+    let digit = &Font::SCHIP_BIG[80..90];
+    screen.rows().enumerate().all(|(j, row)| {
+        row.iter().enumerate().all(|(i, &pixel)| {
+            let line = j.checked_sub(y).and_then(|line| digit.get(line)).copied().unwrap_or(0);
+            let lit = i < 8 && line & 0x80 >> i != 0;
+            pixel == lit as u8
+        })
+    })
+}
+
+#[test]
+fn schip_instructions() {
+    for method in METHODS {
+        let mut chip8 = core(&PROGRAM, method);
+        assert_eq!(chip8.run_instructions(6), None);
+        assert!(chip8.screen().is_hires(), "{method:?}");
+        assert_eq!((chip8.screen().width(), chip8.screen().height()), (Screen::HIRES_WIDTH, Screen::HIRES_HEIGHT));
+        assert_eq!(chip8.state().i(), chip8.state().font().big_address() + 80, "{method:?}");
+        assert!(shows_big_eight(&chip8, 0), "{method:?}");
+
+        assert_eq!(chip8.run_instructions(1), None);
+        assert!(shows_big_eight(&chip8, 4), "{method:?} scrolled down");
+        assert_eq!(chip8.run_instructions(2), None);
+        assert!(shows_big_eight(&chip8, 4), "{method:?} scrolled right and left");
+
+        assert_eq!(chip8.run_instructions(6), None);
+        assert_eq!(chip8.state().v()[..5], [0, 0, 8, 0x55, 0x66], "{method:?}");
+
+        assert_eq!(chip8.run_instructions(100), Some(StopReason::Exited), "{method:?}");
+        assert_eq!(chip8.state().cycles(), 17, "{method:?}");
+        assert!(!chip8.screen().is_hires(), "{method:?}");
+        assert_eq!(chip8.screen().rows().count(), Screen::LORES_HEIGHT, "{method:?}");
+        assert!(chip8.screen().rows().flatten().all(|&pixel| pixel == 0), "{method:?}");
+    }
+}
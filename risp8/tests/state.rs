@@ -0,0 +1,29 @@
+//! Checks the state modified through the core is executed by every method.
+
+mod common;
+
+use common::{core, METHODS};
+
+/// 7001 1200: increments V0 in a loop.
+const PROGRAM: [u8; 4] = [0x70, 0x01, 0x12, 0x00];
+
+#[test]
+fn modified_code_is_executed() {
+    for method in METHODS {
+        let mut chip8 = core(&PROGRAM, method);
+        assert_eq!(chip8.run_instructions(100), None);
+        assert_eq!(chip8.state().v()[0], 50, "{method:?}");
+
+        // Patch the compiled loop to add 2.
+        chip8.state_mut().unwrap().write_memory(0x201, &[0x02]).unwrap();
+        assert_eq!(chip8.run_instructions(10), None);
+        assert_eq!(chip8.state().v()[0], 60, "{method:?}");
+
+        // Replace the whole state by the one of a program incrementing V1.
+        let mut other = core(&[0x71, 0x01, 0x12, 0x00], method);
+        assert_eq!(other.run_instructions(2), None);
+        *chip8.state_mut().unwrap() = *other.state();
+        assert_eq!(chip8.run_instructions(10), None);
+        assert_eq!(chip8.state().v()[..2], [0, 6], "{method:?}");
+    }
+}
@@ -0,0 +1,111 @@
+//! Checks the performance counters reported by each backend.
+
+mod common;
+
+use common::{core, METHODS};
+
+use risp8::{ExecutionMethod, Stats};
+
+use std::time::Duration;
+
+/// Writes the BCD of an incrementing counter in a loop.
+const PROGRAM: [u8; 10] = [
+    0x60, 0x00, // 200: V0 = 0
+    0xA3, 0x00, // 202: I = 300
+    0xF0, 0x33, // 204: BCD of V0 at I
+    0x70, 0x01, // 206: V0 += 1
+    0x12, 0x04, // 208: jump 204
+];
+
+/// Returns true if the method executes instructions one by one without any cache.
+fn interpreted(method: ExecutionMethod) -> bool {
+    match method {
+        ExecutionMethod::Interpreter => true,
+        // The JIT falls back to the interpreter where it is not available.
+        ExecutionMethod::Jit => !cfg!(all(feature = "std", target_arch = "x86_64")),
+        _ => false,
+    }
+}
+
+#[test]
+fn counters() {
+    for method in METHODS {
+        let mut chip8 = core(&PROGRAM, method);
+        // The 2 setup instructions then 30 iterations.
+        assert_eq!(chip8.run_instructions(92), None);
+        let stats = chip8.stats();
+        assert_eq!(stats.instructions, 92, "{method:?}");
+
+        if interpreted(method) {
+            assert_eq!(stats, Stats { instructions: 92, ..Stats::default() }, "{method:?}");
+            continue;
+        }
+
+        // Each Fx33 invalidates the written range.
+        assert_eq!(stats.invalidations, 30, "{method:?}");
+        assert_eq!(stats.blocks_compiled, stats.cache_misses, "{method:?}");
+        assert!(stats.blocks_executed > 0 && stats.blocks_executed <= stats.cache_hits + stats.cache_misses, "{method:?}");
+        assert_eq!(stats.compile_time.is_zero(), method != ExecutionMethod::Jit, "{method:?}");
+
+        // The loop is already cached, writing outside of it does not drop its blocks.
+        assert_eq!(chip8.run_instructions(90), None);
+        let next = chip8.stats();
+        assert_eq!(next.instructions, 182, "{method:?}");
+        assert_eq!(next.invalidations, 60, "{method:?}");
+        assert_eq!(next.blocks_compiled, stats.blocks_compiled, "{method:?}");
+        assert_eq!(next.cache_misses, stats.cache_misses, "{method:?}");
+        assert!(next.cache_hits > stats.cache_hits, "{method:?}");
+
+        // Replacing the backend resets the counters.
+        chip8.set_execution_method(method);
+        assert_eq!(chip8.stats(), Stats::default(), "{method:?}");
+    }
+}
+
+#[test]
+fn format() {
+    assert_eq!(Stats::default().hit_rate(), None);
+    assert_eq!(Stats::default().to_string(), "0 instructions, 0 blocks, 0 compiled, 0 invalidations");
+
+    let stats = Stats {
+        instructions: 10,
+        blocks_executed: 4,
+        cache_hits: 3,
+        cache_misses: 1,
+        blocks_compiled: 1,
+        compile_time: Duration::from_micros(1500),
+        invalidations: 2,
+    };
+    assert_eq!(stats.hit_rate(), Some(75.0));
+    assert_eq!(stats.to_string(), "10 instructions, 4 blocks, 75.0% hits, 1 compiled in 1.5ms, 2 invalidations");
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn get_stats() {
+    use risp8::{Chip8Builder, Risp8Answer, Risp8Command};
+
+    use std::thread;
+
+    let (mut chip8, sender, receiver) = Chip8Builder::new(PROGRAM)
+        .execution_method(ExecutionMethod::CachedInterpreter)
+        .instructions_per_frame(10)
+        .pacing(false)
+        .build()
+        .unwrap();
+    let core = thread::spawn(move || chip8.run());
+
+    // A single step executes the first block, up to the Fx33.
+    sender.send(Risp8Command::SingleStep).unwrap();
+    sender.send(Risp8Command::GetStats).unwrap();
+    loop {
+        if let Risp8Answer::Stats(stats) = receiver.recv().unwrap() {
+            let expected = Stats { instructions: 3, blocks_executed: 1, cache_misses: 1, blocks_compiled: 1, invalidations: 1, ..Stats::default() };
+            assert_eq!(stats, expected);
+            break;
+        }
+    }
+
+    sender.send(Risp8Command::Exit).unwrap();
+    core.join().unwrap();
+}
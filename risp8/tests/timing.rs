@@ -0,0 +1,71 @@
+//! Checks timers advance with the number of executed instructions, and the real-time pacing on top of it.
+
+mod common;
+
+use common::METHODS;
+
+use risp8::Chip8Builder;
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// 6F3C FF15 FF18 1206: sets the delay and sound timers to 60 then loops.
+const PROGRAM: [u8; 8] = [0x6F, 0x3C, 0xFF, 0x15, 0xFF, 0x18, 0x12, 0x06];
+
+#[test]
+fn timers_tick_by_instruction_count() {
+    for method in METHODS {
+        let mut chip8 = Chip8Builder::new(PROGRAM)
+            .execution_method(method)
+            .instructions_per_frame(10)
+            .seed(0)
+            .build_sync()
+            .unwrap();
+
+        assert_eq!(chip8.run_instructions(3), None);
+        assert_eq!((chip8.state().delay(), chip8.state().sound()), (60, 60));
+
+        // The frame ends on the next tick, wherever it started.
+        assert_eq!(chip8.run_frame(), None);
+        assert_eq!(chip8.state().cycles(), 10, "{method:?}");
+        assert_eq!((chip8.state().delay(), chip8.state().sound()), (59, 59), "{method:?}");
+        assert!(chip8.is_sound_playing());
+
+        for _ in 0..20 {
+            assert_eq!(chip8.run_frame(), None);
+        }
+        assert_eq!(chip8.state().cycles(), 210, "{method:?}");
+        assert_eq!(chip8.state().delay(), 39, "{method:?}");
+
+        // A different speed changes how many instructions a frame lasts, not the timers.
+        chip8.set_instructions_per_frame(0);
+        assert_eq!(chip8.instructions_per_frame(), 1);
+        assert_eq!(chip8.run_instructions(39), None);
+        assert_eq!((chip8.state().delay(), chip8.state().sound()), (0, 0), "{method:?}");
+        assert_eq!(chip8.run_frame(), None);
+        assert!(!chip8.is_sound_playing());
+    }
+}
+
+static NOW: AtomicU64 = AtomicU64::new(0);
+
+fn now() -> u64 {
+    NOW.load(Ordering::Relaxed)
+}
+
+#[test]
+fn pacing() {
+    let mut chip8 = Chip8Builder::new(PROGRAM).time_source(now).build_sync().unwrap();
+    assert!(chip8.pacing());
+    assert_eq!(chip8.next_frame_delay(), 16_667);
+
+    NOW.store(20_000, Ordering::Relaxed);
+    assert_eq!(chip8.next_frame_delay(), 13_334);
+
+    // A late core does not try to catch up.
+    NOW.store(100_000, Ordering::Relaxed);
+    assert_eq!(chip8.next_frame_delay(), 0);
+    assert_eq!(chip8.next_frame_delay(), 16_667);
+
+    chip8.set_pacing(false);
+    assert_eq!(chip8.next_frame_delay(), 0);
+}
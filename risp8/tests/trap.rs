@@ -0,0 +1,96 @@
+//! Checks guest faults stop every execution method with the same trap, on the faulting instruction.
+
+mod common;
+
+use common::{core, METHODS};
+
+use risp8::{Chip8Builder, Platform, StopReason, Trap};
+
+#[test]
+fn invalid_opcode_in_block() {
+    // 9xyN with N != 0 is not 9xy0, whether or not VX == VY.
+    for opcode in [0x9011u16, 0x9015, 0x901F] {
+        let [hi, lo] = opcode.to_be_bytes();
+        let program = [
+            0x60, 0x01, // 200: V0 = 1
+            0x61, 0x02, // 202: V1 = 2
+            hi, lo,     // 204: invalid
+            0x62, 0x03, // 206: V2 = 3
+        ];
+
+        for method in METHODS {
+            let mut chip8 = core(&program, method);
+            let reason = chip8.run_instructions(10);
+            assert_eq!(reason, Some(StopReason::Trap(Trap::InvalidOpcode { pc: 0x204, opcode })), "{method:?}");
+            assert_eq!(chip8.state().pc(), 0x204, "{method:?}");
+            assert_eq!(chip8.state().v()[..3], [1, 2, 0], "{method:?}");
+            assert_eq!(chip8.state().cycles(), 3, "{method:?}");
+
+            // Resuming executes the faulting instruction again.
+            assert_eq!(chip8.run_instructions(10), reason, "{method:?}");
+        }
+    }
+}
+
+#[test]
+fn stack_faults() {
+    // 200: call 200, overflowing the stack.
+    for method in METHODS {
+        let mut chip8 = core(&[0x22, 0x00], method);
+        assert_eq!(chip8.run_instructions(100), Some(StopReason::Trap(Trap::StackOverflow { pc: 0x200 })), "{method:?}");
+        assert_eq!(chip8.state().sp(), 16, "{method:?}");
+    }
+
+    // 200: V0 = 1, 202: return with an empty stack.
+    for method in METHODS {
+        let mut chip8 = core(&[0x60, 0x01, 0x00, 0xEE], method);
+        assert_eq!(chip8.run_instructions(100), Some(StopReason::Trap(Trap::StackUnderflow { pc: 0x202 })), "{method:?}");
+        assert_eq!(chip8.state().v()[0], 1, "{method:?}");
+    }
+}
+
+#[test]
+fn memory_out_of_range() {
+    // Opcode, distance of I from the end of memory and length of the access.
+    let cases = [
+        (0xF033u16, 2, 3),  // BCD
+        (0xF355, 3, 4),     // store V0-V3
+        (0xF365, 3, 4),     // load V0-V3
+        (0xD015, 4, 5),     // 5 lines sprite
+        (0xD010, 31, 32),   // 16x16 sprite
+        (0x5022, 2, 3),     // save V0-V2
+        (0x5023, 2, 3),     // load V0-V2
+        (0xF002, 15, 16),   // audio pattern
+    ];
+
+    for platform in [Platform::CosmacVip, Platform::XoChip] {
+        for (opcode, distance, len) in cases {
+            let [hi, lo] = opcode.to_be_bytes();
+            let program = [
+                0x60, 0x05, // 200: V0 = 5
+                hi, lo,     // 202: access at I
+                0x61, 0x06, // 204: V1 = 6
+            ];
+
+            for method in METHODS {
+                let mut chip8 = Chip8Builder::new(program).platform(platform).execution_method(method).seed(0).build_sync().unwrap();
+                let address = (chip8.state().memory_size() - distance) as u16;
+                chip8.state_mut().unwrap().set_i(address);
+                let memory = chip8.state().memory().to_vec();
+
+                let reason = chip8.run_instructions(10);
+                let trap = Trap::MemoryOutOfRange { pc: 0x202, address, len };
+                assert_eq!(reason, Some(StopReason::Trap(trap)), "{platform:?} {opcode:04X} {method:?}");
+                assert_eq!(chip8.state().pc(), 0x202, "{platform:?} {opcode:04X} {method:?}");
+                assert_eq!(chip8.state().i(), address, "{platform:?} {opcode:04X} {method:?}");
+                assert_eq!(chip8.state().v()[..2], [5, 0], "{platform:?} {opcode:04X} {method:?}");
+                assert!(chip8.state().memory() == memory, "{platform:?} {opcode:04X} {method:?}");
+
+                // The access ending on the last byte of memory is valid.
+                chip8.state_mut().unwrap().set_i(address - 1);
+                assert_eq!(chip8.run_instructions(2), None, "{platform:?} {opcode:04X} {method:?}");
+                assert_eq!(chip8.state().v()[1], 6, "{platform:?} {opcode:04X} {method:?}");
+            }
+        }
+    }
+}
@@ -0,0 +1,101 @@
+//! Checks memory watchpoints stop every execution method after the same accesses.
+
+mod common;
+
+use common::{core, METHODS};
+
+use risp8::{StopReason, WatchKind, Watchpoint, WatchpointHit};
+
+/// Writes the BCD of 123 at 300, reads it back, stores it at 310 and draws it as a sprite.
+const PROGRAM: [u8; 20] = [
+    0xA3, 0x00, // 200: I = 300
+    0x6A, 0x7B, // 202: VA = 123
+    0xFA, 0x33, // 204: BCD of VA at I
+    0xA3, 0x00, // 206: I = 300
+    0xF2, 0x65, // 208: load V0 to V2 from I
+    0xA3, 0x10, // 20A: I = 310
+    0xF3, 0x55, // 20C: store V0 to V3 at I
+    0xA3, 0x00, // 20E: I = 300
+    0xD0, 0x13, // 210: draw 3 lines at (V0, V1)
+    0x12, 0x12, // 212: jump 212
+];
+
+const fn hit(pc: u16, address: u16, kind: WatchKind, old: u8, new: u8) -> WatchpointHit {
+    WatchpointHit { pc, address, kind, old, new }
+}
+
+const fn read(pc: u16, address: u16, value: u8) -> WatchpointHit {
+    hit(pc, address, WatchKind::Read, value, value)
+}
+
+#[test]
+fn write_watchpoints() {
+    for method in METHODS {
+        let mut chip8 = core(&PROGRAM, method);
+        assert!(chip8.add_watchpoint(Watchpoint::new(0x301, 0x301, WatchKind::Write)));
+        assert!(chip8.add_watchpoint(Watchpoint::new(0x312, 0x3FF, WatchKind::Write)));
+        assert!(!chip8.add_watchpoint(Watchpoint::new(0x301, 0x301, WatchKind::Write)));
+
+        // Fx33 writes 3 bytes, one of them watched.
+        let expected = hit(0x204, 0x301, WatchKind::Write, 0, 2);
+        assert_eq!(chip8.run_instructions(100), Some(StopReason::Watchpoint(expected)), "{method:?}");
+        assert_eq!(chip8.watchpoint_hits(), [expected], "{method:?}");
+        assert_eq!(chip8.state().pc(), 0x206, "{method:?}");
+        assert_eq!(chip8.state().cycles(), 3, "{method:?}");
+
+        // Fx55 writes 4 bytes, the last two watched. Reads are not reported.
+        let expected = [hit(0x20C, 0x312, WatchKind::Write, 0, 3), hit(0x20C, 0x313, WatchKind::Write, 0, 0)];
+        assert_eq!(chip8.run_instructions(100), Some(StopReason::Watchpoint(expected[0])), "{method:?}");
+        assert_eq!(chip8.watchpoint_hits(), expected, "{method:?}");
+        assert_eq!(chip8.state().pc(), 0x20E, "{method:?}");
+
+        assert_eq!(chip8.run_instructions(100), None, "{method:?}");
+    }
+}
+
+#[test]
+fn read_watchpoints() {
+    for method in METHODS {
+        let mut chip8 = core(&PROGRAM, method);
+        chip8.add_watchpoint(Watchpoint::new(0x300, 0x302, WatchKind::Read));
+        chip8.add_watchpoint(Watchpoint::new(0x310, 0x313, WatchKind::Read));
+
+        // Fx65 reads the 3 digits written by Fx33, which is not reported.
+        let expected = [read(0x208, 0x300, 1), read(0x208, 0x301, 2), read(0x208, 0x302, 3)];
+        assert_eq!(chip8.run_instructions(100), Some(StopReason::Watchpoint(expected[0])), "{method:?}");
+        assert_eq!(chip8.watchpoint_hits(), expected, "{method:?}");
+        assert_eq!(chip8.state().v()[..3], [1, 2, 3], "{method:?}");
+        assert_eq!(chip8.state().pc(), 0x20A, "{method:?}");
+
+        // Dxyn reads the sprite.
+        let expected = [read(0x210, 0x300, 1), read(0x210, 0x301, 2), read(0x210, 0x302, 3)];
+        assert_eq!(chip8.run_instructions(100), Some(StopReason::Watchpoint(expected[0])), "{method:?}");
+        assert_eq!(chip8.watchpoint_hits(), expected, "{method:?}");
+        assert_eq!(chip8.state().pc(), 0x212, "{method:?}");
+
+        assert_eq!(chip8.run_instructions(100), None, "{method:?}");
+    }
+}
+
+#[test]
+fn access_watchpoints() {
+    for method in METHODS {
+        let mut chip8 = core(&PROGRAM, method);
+        let watchpoint = Watchpoint::new(0x302, 0x302, WatchKind::Access);
+        chip8.add_watchpoint(watchpoint);
+
+        let mut hits = Vec::new();
+        while let Some(StopReason::Watchpoint(hit)) = chip8.run_instructions(100) {
+            hits.push(hit);
+            assert_eq!(chip8.watchpoint_hits(), [hit], "{method:?}");
+        }
+        let expected = [hit(0x204, 0x302, WatchKind::Write, 0, 3), read(0x208, 0x302, 3), read(0x210, 0x302, 3)];
+        assert_eq!(hits, expected, "{method:?}");
+
+        // Execution is not stopped anymore once the watchpoint is removed.
+        assert!(chip8.remove_watchpoint(watchpoint));
+        assert!(!chip8.remove_watchpoint(watchpoint));
+        chip8.state_mut().unwrap().set_pc(0x200).unwrap();
+        assert_eq!(chip8.run_instructions(100), None, "{method:?}");
+    }
+}
@@ -0,0 +1,47 @@
+//! Checks the XO-CHIP extensions behave the same on every execution method.
+
+mod common;
+
+use common::{core, METHODS};
+
+use risp8::{Chip8Builder, Platform};
+
+#[test]
+fn skip_modified_long_load() {
+    let program = [
+        0x30, 0x00, // 200: skip if V0 == 0
+        0x60, 0x00, // 202: patched into F000 below, never executed
+        0x71, 0x01, // 204: V1 += 1
+        0x72, 0x01, // 206: V2 += 1
+        0xA2, 0x02, // 208: I = 202
+        0x63, 0xF0, // 20A: V3 = F0
+        0x64, 0x00, // 20C: V4 = 00
+        0x53, 0x42, // 20E: save V3-V4 at 202
+        0x12, 0x00, // 210: jump 200
+    ];
+
+    for method in METHODS {
+        let mut chip8 = core(&program, method);
+        // Ends the block at 200 after the skip, so the skipped instruction is outside of it.
+        chip8.add_breakpoint(0x202);
+        assert_eq!(chip8.run_instructions(100), None);
+        // The skip is 4 bytes long once 202 holds F000.
+        assert_eq!(chip8.state().v()[1..3], [1, 14], "{method:?}");
+    }
+}
+
+#[test]
+fn long_load_at_end_of_memory() {
+    for method in METHODS {
+        let mut chip8 = Chip8Builder::new([0x00, 0xE0]).platform(Platform::XoChip).execution_method(method).seed(0).build_sync().unwrap();
+        let state = chip8.state_mut().unwrap();
+        // FFF8 is cached or compiled, FFFC is always interpreted and wraps PC around.
+        state.write_memory(0xFFF8, &[0xF0, 0x00, 0x12, 0x34, 0xF0, 0x00, 0x56, 0x78]).unwrap();
+        state.set_pc(0xFFF8).unwrap();
+
+        assert_eq!(chip8.run_instructions(1), None);
+        assert_eq!((chip8.state().i(), chip8.state().pc()), (0x1234, 0xFFFC), "{method:?}");
+        assert_eq!(chip8.run_instructions(1), None);
+        assert_eq!((chip8.state().i(), chip8.state().pc()), (0x5678, 0), "{method:?}");
+    }
+}